@@ -1,10 +1,25 @@
 /*
  * Ant search via stigmergy
  *
+ * Ants wander away from their hive until they see food, then carry it home
+ * one unit at a time, laying pheromones on the way. Ants who sense the
+ * trail follow it away from the hive, back to the food.
+ *
  */
+extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate sekai;
-use sekai::world::World;
+use sekai::cli;
+use sekai::config::{Config, ConfigError};
+use sekai::world::{World, WorldView};
 use sekai::entity::Entity;
+use sekai::observer::{Custom, EntityCount, Metrics};
+use sekai::registry::{Definition, Registry};
+use sekai::simulation::Simulation;
+use rand::{Rng, SeedableRng, StdRng};
+use std::f32::consts::PI;
 
 #[derive(Debug)]
 struct AntWorld {
@@ -12,11 +27,53 @@ struct AntWorld {
     pheromone_trail: Vec<Pheromone>,
     ant_swarm: Vec<Ant>,
     ant_hive: (f32, f32), // where all ants want to go c:
+    params: AntParams,
+    rng: StdRng,
+    delivered: u32, // units of food carried home so far
 }
 impl World<Pheromone> for AntWorld {
     // todo: figure out if a ant can see another ant
     fn update(&mut self) {
-        // TODO: call update on each pheromone trail item
+        // the trail fades, and pheromones which have run out are gone
+        for pheromone in &mut self.pheromone_trail {
+            pheromone.update();
+        }
+        self.pheromone_trail.retain(|pheromone| pheromone.lifetime > 0.0);
+
+        let (hive_x, hive_y) = self.ant_hive;
+        for idx in 0..self.ant_swarm.len() {
+            let (x, y) = (self.ant_swarm[idx].x, self.ant_swarm[idx].y);
+            if self.ant_swarm[idx].carrying {
+                // head home, marking the way back to the food
+                self.lay_pheromone(x, y);
+                let ant = &mut self.ant_swarm[idx];
+                ant.step_towards(hive_x, hive_y);
+                if (ant.x, ant.y) == self.ant_hive {
+                    ant.carrying = false;
+                    self.delivered += 1;
+                }
+            } else if let Some(food_idx) = self.food_in_sight(x, y) {
+                let food = &mut self.food_locations[food_idx];
+                let ant = &mut self.ant_swarm[idx];
+                ant.step_towards(food.x, food.y);
+                if (ant.x, ant.y) == (food.x, food.y) {
+                    food.resource -= 1;
+                    ant.carrying = true;
+                }
+            } else if let Some(pheromone) = self.trail_outwards(idx) {
+                self.ant_swarm[idx].receive_message(pheromone);
+            } else if distance(x, y, hive_x, hive_y) > 2.0 * self.params.food_distance {
+                // lost ants make their way back towards the hive
+                self.ant_swarm[idx].step_towards(hive_x, hive_y);
+            } else {
+                let angle = self.rng.gen_range(0.0, 2.0 * PI);
+                let ant = &mut self.ant_swarm[idx];
+                ant.x += ant.stride * angle.cos();
+                ant.y += ant.stride * angle.sin();
+            }
+        }
+        // food which has all been carried away is gone
+        self.food_locations.retain(|food| food.resource > 0);
     }
 
     // returns the number of ants in the swarm
@@ -32,20 +89,100 @@ impl World<Pheromone> for AntWorld {
     }
 }
 
+impl WorldView for AntWorld {
+    type Entity = Ant;
+    fn entities(&self) -> &[Ant] {
+        &self.ant_swarm
+    }
+}
+
 impl AntWorld {
     // add a new ant
     fn add_entity(&mut self, ant: Ant) {
         self.ant_swarm.push(ant);
     }
 
+    #[cfg(test)]
     fn new() -> Self {
+        AntWorld::from_params(&AntParams::default(), 0)
+    }
+
+    // a hive without ants, with food scattered around it as the params say,
+    // seeded for where the food lies and where ants wander
+    fn from_params(params: &AntParams, seed: u64) -> Self {
+        let mut rng = StdRng::from_seed(&[seed as usize][..]);
+        let food_locations = (0..params.food_sources)
+            .map(|_| {
+                let angle = rng.gen_range(0.0, 2.0 * PI);
+                Food {
+                    x: params.food_distance * angle.cos(),
+                    y: params.food_distance * angle.sin(),
+                    resource: params.resource,
+                }
+            })
+            .collect();
         AntWorld {
-            food_locations: Vec::new(),
+            food_locations,
             pheromone_trail: Vec::new(),
             ant_swarm: Vec::new(),
             ant_hive: (0.0, 0.0),
+            params: params.clone(),
+            rng,
+            delivered: 0,
         }
     }
+
+    // the nearest food with some left which an ant at (x, y) can see
+    fn food_in_sight(&self, x: f32, y: f32) -> Option<usize> {
+        self.food_locations
+            .iter()
+            .enumerate()
+            .filter(|&(_, food)| food.resource > 0)
+            .map(|(idx, food)| (idx, distance(x, y, food.x, food.y)))
+            .filter(|&(_, dist)| dist <= self.params.sight)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(idx, _)| idx)
+    }
+
+    // the pheromone an ant senses furthest from the hive, if it is further
+    // than the ant, so following the trail leads away to the food
+    fn trail_outwards(&self, idx: usize) -> Option<Pheromone> {
+        let ant = &self.ant_swarm[idx];
+        let (hive_x, hive_y) = self.ant_hive;
+        let from_hive = |x, y| distance(x, y, hive_x, hive_y);
+        self.pheromone_trail
+            .iter()
+            .filter(|pheromone| ant.senses(pheromone))
+            .filter(|pheromone| from_hive(pheromone.x, pheromone.y) > from_hive(ant.x, ant.y))
+            .max_by(|a, b| from_hive(a.x, a.y).partial_cmp(&from_hive(b.x, b.y)).unwrap())
+            .cloned()
+    }
+
+    // lays a fresh pheromone, or refreshes one already there
+    fn lay_pheromone(&mut self, x: f32, y: f32) {
+        let lifetime = self.params.pheromone_lifetime;
+        let near = self.params.stride / 2.0;
+        if let Some(pheromone) = self
+            .pheromone_trail
+            .iter_mut()
+            .find(|pheromone| distance(x, y, pheromone.x, pheromone.y) < near)
+        {
+            pheromone.lifetime = lifetime;
+            return;
+        }
+        self.pheromone_trail.push(Pheromone {
+            x,
+            y,
+            lifetime,
+            decay: 1.0,
+            sensitivity: self.params.pheromone_sensitivity,
+        });
+    }
+}
+
+// Euclidean distance between two points on the ground
+fn distance(x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
 }
 
 #[derive(Debug)]
@@ -54,31 +191,104 @@ struct Ant {
     y: f32,                         // 2D world
     stride: f32,                    // how much the ant travels per tick
     pheromone_sense_threshold: u32, // minimum value needed to follow pheromone trail
+    carrying: bool,                 // whether the ant is taking food home
 }
 impl Entity<Pheromone> for Ant {
     // todo: receive message, send message,
-    fn update(&mut self, world: &World<Pheromone>) {}
+    fn update(&mut self, _world: &dyn World<Pheromone>) {}
+    // steps towards a pheromone the ant can sense
     fn receive_message(&mut self, message: Pheromone) {
-        // TODO increase current position by some step size in the
-        // direction of the message.  Maybe the world should decide where the
-        // next message to send is?  IE: the world says in which direction the
-        // ant should step
-        self.x += message.x;
-        self.y += message.y;
-        //message.update();
+        if self.senses(&message) {
+            self.step_towards(message.x, message.y);
+        }
     }
 }
 impl Ant {
+    #[cfg(test)]
     fn new() -> Self {
+        Ant::from_params(&AntParams::default())
+    }
+
+    fn from_params(params: &AntParams) -> Self {
         Ant {
             x: 0_f32,
             y: 0_f32,
-            stride: 1_f32,
-            pheromone_sense_threshold: 2_u32,
+            stride: params.stride,
+            pheromone_sense_threshold: params.pheromone_sense_threshold,
+            carrying: false,
+        }
+    }
+
+    // whether the pheromone is strong and close enough to follow
+    fn senses(&self, pheromone: &Pheromone) -> bool {
+        pheromone.lifetime >= self.pheromone_sense_threshold as f32
+            && distance(self.x, self.y, pheromone.x, pheromone.y) <= pheromone.sensitivity
+    }
+
+    // moves a stride towards a point, stopping on it if it is closer
+    fn step_towards(&mut self, x: f32, y: f32) {
+        let dist = distance(self.x, self.y, x, y);
+        if dist <= self.stride {
+            self.x = x;
+            self.y = y;
+        } else {
+            self.x += (x - self.x) * self.stride / dist;
+            self.y += (y - self.y) * self.stride / dist;
         }
     }
 }
 
+// Parameters of the colony, loaded from a TOML or JSON file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct AntParams {
+    ants: usize,
+    stride: f32,
+    pheromone_sense_threshold: u32,
+    sight: f32,                 // how far away an ant can see food
+    food_sources: usize,        // piles of food around the hive
+    food_distance: f32,         // how far each pile is from the hive
+    resource: u32,              // units of food in each pile
+    pheromone_lifetime: f32,    // ticks a pheromone lasts unless refreshed
+    pheromone_sensitivity: f32, // how far away an ant can sense a pheromone
+}
+
+impl Default for AntParams {
+    fn default() -> Self {
+        AntParams {
+            ants: 10,
+            stride: 1.0,
+            pheromone_sense_threshold: 2,
+            sight: 2.0,
+            food_sources: 3,
+            food_distance: 8.0,
+            resource: 20,
+            pheromone_lifetime: 40.0,
+            pheromone_sensitivity: 2.0,
+        }
+    }
+}
+
+impl Config for AntParams {
+    fn validate(&self) -> Result<(), ConfigError> {
+        let positive = [
+            ("stride", self.stride),
+            ("sight", self.sight),
+            ("pheromone_lifetime", self.pheromone_lifetime),
+            ("pheromone_sensitivity", self.pheromone_sensitivity),
+        ];
+        for &(key, value) in &positive {
+            if !(value > 0.0 && value.is_finite()) {
+                return Err(ConfigError::invalid(key, "must be positive"));
+            }
+        }
+        if !(self.food_distance >= 0.0 && self.food_distance.is_finite()) {
+            return Err(ConfigError::invalid("food_distance", "must not be negative"));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 struct Food {
     // Food has a location and some limited resource count
@@ -104,8 +314,67 @@ impl Pheromone {
     }
 }
 
+// a hive with all its ants at home
+fn colony(params: &AntParams, seed: u64) -> AntWorld {
+    let mut world = AntWorld::from_params(params, seed);
+    for _ in 0..params.ants {
+        world.add_entity(Ant::from_params(params));
+    }
+    world
+}
+
+fn colony_metrics(_params: &AntParams) -> Metrics<AntWorld> {
+    let mut metrics = Metrics::new();
+    metrics.add_probe(EntityCount);
+    metrics.add_probe(Custom::new("pheromones", |world: &AntWorld| {
+        world.pheromone_trail.len() as f64
+    }));
+    metrics.add_probe(Custom::new("delivered", |world: &AntWorld| {
+        f64::from(world.delivered)
+    }));
+    metrics.add_probe(Custom::new("food", |world: &AntWorld| {
+        world.food_locations.iter().map(|food| f64::from(food.resource)).sum()
+    }));
+    metrics
+}
+
+// makes the colony runnable by name from the sekai command line
+fn registry() -> Registry {
+    let mut ant = Definition::new("ant", colony);
+    ant.set_description("Ants searching for food via stigmergy");
+    ant.set_metrics(colony_metrics);
+
+    let mut registry = Registry::new();
+    registry.add(ant);
+    registry
+}
+
 fn main() {
-    println!("This is the main function");
+    // with arguments, e.g. `run ant --set ants=50`, act as the sekai
+    // command line
+    if std::env::args().len() > 1 {
+        cli::main(&registry());
+        return;
+    }
+    let params = AntParams::default();
+    let mut metrics = colony_metrics(&params);
+    let mut sim = Simulation::new(colony(&params, 0));
+    sim.run(500, &mut [&mut metrics]);
+
+    println!("tick  delivered  food left  pheromones");
+    for (index, &tick) in metrics.ticks().iter().enumerate() {
+        if tick % 50 != 0 {
+            continue;
+        }
+        let value = |name: &str| metrics.series(name).unwrap().values[index];
+        println!(
+            "{:4}  {:9}  {:9}  {:10}",
+            tick,
+            value("delivered"),
+            value("food"),
+            value("pheromones")
+        );
+    }
 }
 
 #[test]
@@ -117,3 +386,35 @@ fn test_world_update() {
     println!("{:#?}", world.ant_swarm);
     assert_eq!(world.num_entities(), 10);
 }
+
+#[test]
+fn test_forage() {
+    let mut world = AntWorld::new();
+    world.food_locations = vec![Food {
+        x: 2.0,
+        y: 0.0,
+        resource: 1,
+    }];
+    world.add_entity(Ant::new());
+
+    // two strides out to the food, two back, marking the way
+    for _ in 0..4 {
+        world.update();
+    }
+    assert_eq!(world.delivered, 1);
+    assert!(world.food_locations.is_empty());
+    let trail: Vec<(f32, f32)> = world.pheromone_trail.iter().map(|p| (p.x, p.y)).collect();
+    assert_eq!(trail, vec![(2.0, 0.0), (1.0, 0.0)]);
+
+    // a newcomer follows the trail away from the hive
+    world.add_entity(Ant::new());
+    world.update();
+    assert_eq!((world.ant_swarm[1].x, world.ant_swarm[1].y), (1.0, 0.0));
+
+    // but not once it has faded below the threshold
+    let mut ant = Ant::new();
+    let mut faded = world.pheromone_trail[0].clone();
+    faded.lifetime = 1.0;
+    ant.receive_message(faded);
+    assert_eq!((ant.x, ant.y), (0.0, 0.0));
+}
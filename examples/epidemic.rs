@@ -0,0 +1,556 @@
+/*
+ * SIR/SEIR epidemic spreading between people
+ *
+ * Every person is susceptible, exposed (infected but not yet infectious),
+ * infectious or recovered. People catch the disease from the infectious
+ * people they are in contact with, either those within a radius as they
+ * wander a square, or their neighbors on a contact network.
+ *
+ * Every tick a susceptible person is infected with probability
+ * 1 - exp(-transmission * infectious contacts / contacts), so with everyone
+ * in contact the counts follow the classic mean field equations, solved by
+ * `mean_field` for comparison, though updating once a tick makes them rise
+ * a little later.
+ *
+ */
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate rand;
+extern crate sekai;
+
+use rand::{Rng, SeedableRng, StdRng};
+use sekai::cli;
+use sekai::config::{Config, ConfigError};
+use sekai::detect::ExtinctionDetector;
+use sekai::entity::{Entity, Observable};
+use sekai::graph::{self, Graph};
+use sekai::observer::{Custom, Metrics};
+use sekai::registry::{Definition, Registry};
+use sekai::simulation::Simulation;
+use sekai::world::{World, WorldView};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compartment {
+    Susceptible,
+    Exposed,
+    Infectious,
+    Recovered,
+}
+
+// the message a person receives when their compartment changes
+type Transition = Compartment;
+
+#[derive(Debug, Clone)]
+struct Person {
+    id: u64,
+    pos: Vec<f32>, // only used in space
+    compartment: Compartment,
+}
+impl Entity<Transition> for Person {
+    fn update(&mut self, _world: &dyn World<Transition>) {}
+    fn receive_message(&mut self, next: Transition) {
+        self.compartment = next;
+    }
+}
+impl Observable for Person {
+    fn id(&self) -> u64 {
+        self.id
+    }
+    fn position(&self) -> &[f32] {
+        &self.pos
+    }
+    fn field(&self, name: &str) -> Option<f64> {
+        match name {
+            // 0 susceptible, 1 exposed, 2 infectious, 3 recovered
+            "compartment" => Some(self.compartment as u8 as f64),
+            _ => None,
+        }
+    }
+}
+
+// Which compartments the disease passes through
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Model {
+    #[default]
+    Sir,
+    Seir,
+}
+
+// Who is in contact with whom
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Topology {
+    // people wander a wrapping square, in contact with everyone within the
+    // radius, taking a step in a random direction every tick
+    Space { size: f32, radius: f32, step: f32 },
+    // everyone is in contact with everyone
+    Complete,
+    // Erdős–Rényi network
+    Random { mean_degree: f64 },
+    // Watts–Strogatz network
+    SmallWorld { k: usize, rewire: f64 },
+    // Barabási–Albert network
+    ScaleFree { m: usize },
+}
+
+// Parameters of the outbreak, loaded from a TOML or JSON file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct EpidemicParams {
+    population: usize,
+    initial_infected: usize,
+    model: Model,
+    topology: Topology,
+    transmission: f64, // infection rate when every contact is infectious
+    incubation: f64,   // rate of exposed people becoming infectious
+    recovery: f64,     // rate of infectious people recovering
+}
+
+impl Default for EpidemicParams {
+    fn default() -> Self {
+        EpidemicParams {
+            population: 500,
+            initial_infected: 5,
+            model: Model::Sir,
+            topology: Topology::Complete,
+            transmission: 0.3,
+            incubation: 0.2,
+            recovery: 0.1,
+        }
+    }
+}
+
+impl Config for EpidemicParams {
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.initial_infected > self.population {
+            return Err(ConfigError::invalid(
+                "initial_infected",
+                "must not exceed the population",
+            ));
+        }
+        let rates = [
+            ("transmission", self.transmission),
+            ("incubation", self.incubation),
+            ("recovery", self.recovery),
+        ];
+        for &(key, rate) in &rates {
+            if rate.is_nan() || rate < 0.0 {
+                return Err(ConfigError::invalid(key, "must not be negative"));
+            }
+        }
+        let topology_valid = match self.topology {
+            Topology::Space { size, radius, step } => size > 0.0 && radius >= 0.0 && step >= 0.0,
+            Topology::Complete => true,
+            Topology::Random { mean_degree } => mean_degree >= 0.0,
+            Topology::SmallWorld { k, rewire } => {
+                k < self.population / 2 && (0.0..=1.0).contains(&rewire)
+            }
+            Topology::ScaleFree { m } => m >= 1 && m < self.population,
+        };
+        if !topology_valid {
+            return Err(ConfigError::invalid(
+                "topology",
+                "sizes must be positive, degrees must fit the population and \
+                 rewiring must be between 0 and 1",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct EpidemicWorld {
+    people: Vec<Person>,
+    network: Option<Graph>, // None in space or with everyone in contact
+    params: EpidemicParams,
+    rng: StdRng,
+}
+impl World<Transition> for EpidemicWorld {
+    fn update(&mut self) {
+        let pressure = self.pressure();
+        let params = &self.params;
+        // every person moves at most one compartment, decided on the state
+        // from before the tick
+        let mut transitions = Vec::new();
+        for (index, person) in self.people.iter().enumerate() {
+            let (rate, next) = match person.compartment {
+                Compartment::Susceptible if params.model == Model::Seir => {
+                    (params.transmission * pressure[index], Compartment::Exposed)
+                }
+                Compartment::Susceptible => {
+                    (params.transmission * pressure[index], Compartment::Infectious)
+                }
+                Compartment::Exposed => (params.incubation, Compartment::Infectious),
+                Compartment::Infectious => (params.recovery, Compartment::Recovered),
+                Compartment::Recovered => continue,
+            };
+            if rate > 0.0 && self.rng.gen::<f64>() < 1.0 - (-rate).exp() {
+                transitions.push((index, next));
+            }
+        }
+        for (index, next) in transitions {
+            self.people[index].receive_message(next);
+        }
+
+        if let Topology::Space { size, step, .. } = self.params.topology {
+            for person in &mut self.people {
+                let angle = self.rng.gen_range(0.0, 2.0 * std::f32::consts::PI);
+                person.pos[0] = (person.pos[0] + step * angle.cos()).rem_euclid(size);
+                person.pos[1] = (person.pos[1] + step * angle.sin()).rem_euclid(size);
+            }
+        }
+    }
+    fn num_entities(&self) -> usize {
+        self.people.len()
+    }
+    // tells everyone, e.g. to reset the outbreak
+    fn receive_message(&mut self, message: Transition) {
+        for person in &mut self.people {
+            person.receive_message(message);
+        }
+    }
+}
+impl WorldView for EpidemicWorld {
+    type Entity = Person;
+    fn entities(&self) -> &[Person] {
+        &self.people
+    }
+}
+impl EpidemicWorld {
+    fn from_params(params: &EpidemicParams, seed: u64) -> Self {
+        let mut rng = StdRng::from_seed(&[seed as usize][..]);
+        let n = params.population;
+        let network = match params.topology {
+            Topology::Space { .. } | Topology::Complete => None,
+            Topology::Random { mean_degree } => {
+                let p = if n > 1 { mean_degree / (n - 1) as f64 } else { 0.0 };
+                Some(graph::erdos_renyi(n, p, &mut rng))
+            }
+            Topology::SmallWorld { k, rewire } => {
+                Some(graph::watts_strogatz(n, k, rewire, &mut rng))
+            }
+            Topology::ScaleFree { m } => Some(graph::barabasi_albert(n, m, &mut rng)),
+        };
+        let size = match params.topology {
+            Topology::Space { size, .. } => size,
+            _ => 0.0,
+        };
+        let mut people: Vec<Person> = (0..n)
+            .map(|id| Person {
+                id: id as u64,
+                pos: if let Topology::Space { .. } = params.topology {
+                    vec![rng.gen_range(0.0, size), rng.gen_range(0.0, size)]
+                } else {
+                    Vec::new()
+                },
+                compartment: Compartment::Susceptible,
+            })
+            .collect();
+        // the first cases are picked at random
+        let mut order: Vec<usize> = (0..n).collect();
+        rng.shuffle(&mut order);
+        for &index in order.iter().take(params.initial_infected) {
+            people[index].compartment = Compartment::Infectious;
+        }
+        EpidemicWorld {
+            people,
+            network,
+            params: params.clone(),
+            rng,
+        }
+    }
+
+    // the fraction of each person's contacts who are infectious
+    fn pressure(&self) -> Vec<f64> {
+        let infectious =
+            |index: &usize| self.people[*index].compartment == Compartment::Infectious;
+        match (&self.network, self.params.topology) {
+            (Some(network), _) => (0..self.people.len())
+                .map(|node| {
+                    let contacts = network.neighbors(node);
+                    if contacts.is_empty() {
+                        0.0
+                    } else {
+                        contacts.iter().filter(|i| infectious(i)).count() as f64
+                            / contacts.len() as f64
+                    }
+                })
+                .collect(),
+            (None, Topology::Space { size, radius, .. }) => {
+                let sources: Vec<usize> = (0..self.people.len()).filter(infectious).collect();
+                (0..self.people.len())
+                    .map(|index| {
+                        let pos = &self.people[index].pos;
+                        let close = |other: &usize| {
+                            *other != index
+                                && wrapped_distance(pos, &self.people[*other].pos, size) <= radius
+                        };
+                        let contacts = (0..self.people.len()).filter(close).count();
+                        if contacts == 0 {
+                            0.0
+                        } else {
+                            sources.iter().filter(|i| close(i)).count() as f64 / contacts as f64
+                        }
+                    })
+                    .collect()
+            }
+            // everyone else is a contact, so no network is needed
+            (None, Topology::Complete) => {
+                let others = self.people.len().saturating_sub(1).max(1) as f64;
+                let total = (0..self.people.len()).filter(infectious).count();
+                (0..self.people.len())
+                    .map(|index| {
+                        let sources = total - infectious(&index) as usize;
+                        sources as f64 / others
+                    })
+                    .collect()
+            }
+            (None, _) => vec![0.0; self.people.len()],
+        }
+    }
+
+    fn count(&self, compartment: Compartment) -> usize {
+        self.people
+            .iter()
+            .filter(|person| person.compartment == compartment)
+            .count()
+    }
+}
+
+// distance on a square whose edges wrap around
+fn wrapped_distance(a: &[f32], b: &[f32], size: f32) -> f32 {
+    let dx = (a[0] - b[0]).abs();
+    let dy = (a[1] - b[1]).abs();
+    let dx = dx.min(size - dx);
+    let dy = dy.min(size - dy);
+    (dx * dx + dy * dy).sqrt()
+}
+
+// people who can still pass the disease on
+fn active(world: &EpidemicWorld) -> usize {
+    world.count(Compartment::Exposed) + world.count(Compartment::Infectious)
+}
+
+// Solves the mean field equations, giving the fractions of susceptible,
+// exposed, infectious and recovered people at every tick
+fn mean_field(params: &EpidemicParams, ticks: usize) -> Vec<[f64; 4]> {
+    let (beta, sigma, gamma) = (params.transmission, params.incubation, params.recovery);
+    let seir = params.model == Model::Seir;
+    let derivative = |y: [f64; 4]| {
+        let infections = beta * y[0] * y[2];
+        let onsets = if seir { sigma * y[1] } else { 0.0 };
+        let recoveries = gamma * y[2];
+        let to_infectious = if seir { onsets } else { infections };
+        [
+            -infections,
+            if seir { infections - onsets } else { 0.0 },
+            to_infectious - recoveries,
+            recoveries,
+        ]
+    };
+    let add = |y: [f64; 4], dy: [f64; 4], h: f64| {
+        [
+            y[0] + h * dy[0],
+            y[1] + h * dy[1],
+            y[2] + h * dy[2],
+            y[3] + h * dy[3],
+        ]
+    };
+
+    let infected = params.initial_infected as f64 / params.population as f64;
+    let mut y = [1.0 - infected, 0.0, infected, 0.0];
+    let mut solution = vec![y];
+    // fourth order Runge-Kutta, in steps of a tenth of a tick
+    let h = 0.1;
+    for _ in 0..ticks {
+        for _ in 0..10 {
+            let k1 = derivative(y);
+            let k2 = derivative(add(y, k1, h / 2.0));
+            let k3 = derivative(add(y, k2, h / 2.0));
+            let k4 = derivative(add(y, k3, h));
+            for i in 0..4 {
+                y[i] += h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]);
+            }
+        }
+        solution.push(y);
+    }
+    solution
+}
+
+fn epidemic_metrics(_params: &EpidemicParams) -> Metrics<EpidemicWorld> {
+    let mut metrics = Metrics::new();
+    let compartments = [
+        ("susceptible", Compartment::Susceptible),
+        ("exposed", Compartment::Exposed),
+        ("infectious", Compartment::Infectious),
+        ("recovered", Compartment::Recovered),
+    ];
+    for &(name, compartment) in &compartments {
+        metrics.add_probe(Custom::new(name, move |world: &EpidemicWorld| {
+            world.count(compartment) as f64
+        }));
+    }
+    metrics
+}
+
+// makes the outbreak runnable by name from the sekai command line
+fn registry() -> Registry {
+    let mut epidemic = Definition::new("epidemic", EpidemicWorld::from_params);
+    epidemic.set_description("SIR/SEIR epidemic in space or on a contact network");
+    epidemic.set_metrics(epidemic_metrics);
+    epidemic.record_trajectory(&["compartment"]);
+    // stop once nobody can pass the disease on
+    epidemic.stop_when(|_| vec![Box::new(ExtinctionDetector::new(active))]);
+
+    let mut registry = Registry::new();
+    registry.add(epidemic);
+    registry
+}
+
+fn main() {
+    // with arguments, e.g. `run epidemic --set model=seir`, act as the
+    // sekai command line
+    if std::env::args().len() > 1 {
+        cli::main(&registry());
+        return;
+    }
+    let params = EpidemicParams::default();
+    let mut metrics = epidemic_metrics(&params);
+    let mut sim = Simulation::new(EpidemicWorld::from_params(&params, 0));
+    let mut outbreak = ExtinctionDetector::new(active);
+    let end = sim.run_until(200, &mut [&mut metrics], &mut [&mut outbreak]);
+
+    let expected = mean_field(&params, sim.tick() as usize);
+    println!("tick  susceptible  infectious  recovered  (mean field infectious)");
+    for (index, &tick) in metrics.ticks().iter().enumerate() {
+        if tick % 10 != 0 {
+            continue;
+        }
+        let value = |name: &str| metrics.series(name).unwrap().values[index];
+        println!(
+            "{:4}  {:11}  {:10}  {:9}  ({:.0})",
+            tick,
+            value("susceptible"),
+            value("infectious"),
+            value("recovered"),
+            expected[tick as usize][2] * params.population as f64
+        );
+    }
+    println!("run ended: {}", end);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sekai::config;
+
+    fn run(params: &EpidemicParams, seed: u64, ticks: usize) -> Vec<[f64; 4]> {
+        let mut world = EpidemicWorld::from_params(params, seed);
+        let n = params.population as f64;
+        let fractions = |world: &EpidemicWorld| {
+            [
+                world.count(Compartment::Susceptible) as f64 / n,
+                world.count(Compartment::Exposed) as f64 / n,
+                world.count(Compartment::Infectious) as f64 / n,
+                world.count(Compartment::Recovered) as f64 / n,
+            ]
+        };
+        let mut counts = vec![fractions(&world)];
+        for _ in 0..ticks {
+            world.update();
+            counts.push(fractions(&world));
+        }
+        counts
+    }
+
+    #[test]
+    fn test_matches_mean_field() {
+        for &model in &[Model::Sir, Model::Seir] {
+            let params = EpidemicParams {
+                population: 1000,
+                initial_infected: 20,
+                model,
+                topology: Topology::Complete,
+                ..EpidemicParams::default()
+            };
+            let (runs, ticks) = (4, 120);
+            let mut mean = vec![[0.0; 4]; ticks + 1];
+            for seed in 0..runs {
+                for (tick, fractions) in run(&params, seed, ticks).iter().enumerate() {
+                    for i in 0..4 {
+                        mean[tick][i] += fractions[i] / runs as f64;
+                    }
+                }
+            }
+            let expected = mean_field(&params, ticks);
+            // updating once a tick lags the equations on the way up, so
+            // compare how high the epidemic peaks and how it ends
+            let peak = |fractions: &[[f64; 4]]| fractions.iter().map(|y| y[2]).fold(0.0, f64::max);
+            let (simulated, solved) = (peak(&mean), peak(&expected));
+            assert!(
+                (simulated - solved).abs() < 0.03,
+                "{:?} peaks at {} instead of {}",
+                model,
+                simulated,
+                solved
+            );
+            for i in 0..4 {
+                let off = (mean[ticks][i] - expected[ticks][i]).abs();
+                assert!(off < 0.03, "{:?} ends {} off the mean field", model, off);
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_spread_without_contacts() {
+        let params = EpidemicParams {
+            topology: Topology::Space {
+                size: 100.0,
+                radius: 0.0,
+                step: 1.0,
+            },
+            ..EpidemicParams::default()
+        };
+        let end = run(&params, 1, 100);
+        let last = end.last().unwrap();
+        assert_eq!(last[0], 0.99);
+        assert!(last[3] > 0.0);
+    }
+
+    #[test]
+    fn test_everyone_in_contact() {
+        let params = EpidemicParams {
+            population: 5,
+            initial_infected: 2,
+            ..EpidemicParams::default()
+        };
+        let world = EpidemicWorld::from_params(&params, 0);
+        assert!(world.network.is_none());
+        // nobody counts themselves among their contacts
+        for (person, pressure) in world.people.iter().zip(world.pressure()) {
+            match person.compartment {
+                Compartment::Infectious => assert_eq!(pressure, 0.25),
+                _ => assert_eq!(pressure, 0.5),
+            }
+        }
+    }
+
+    #[test]
+    fn test_params() {
+        let params: EpidemicParams = config::from_toml_str(
+            "model = \"seir\"\n[topology.small_world]\nk = 3\nrewire = 0.1\n",
+        )
+        .unwrap();
+        assert_eq!(params.model, Model::Seir);
+        let world = EpidemicWorld::from_params(&params, 0);
+        assert_eq!(world.network.as_ref().unwrap().edge_count(), 1500);
+        assert_eq!(world.count(Compartment::Infectious), 5);
+
+        let error = config::from_toml_str::<EpidemicParams>("initial_infected = 600\n").unwrap_err();
+        assert_eq!(error.key(), Some("initial_infected"));
+    }
+}
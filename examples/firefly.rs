@@ -25,66 +25,103 @@ extern crate rand;
 extern crate rayon;
 extern crate sekai;
 
-use rayon::prelude::*;
-use sekai::world::World;
-use sekai::entity::Entity;
+use sekai::channel::{Channel, ChannelParams};
+use sekai::cli;
+use sekai::cluster::{ClusterProbe, Method};
+use sekai::config::{self, Config, ConfigError};
+use sekai::detect::ExtinctionDetector;
+use sekai::world::{World, WorldView};
+use sekai::entity::{Entity, Observable};
+use sekai::event::{EventQueue, EventWorld};
+use sekai::mailbox::{Aggregate, Combine, Mailbox, Mean, Reducer};
+use sekai::observer::{Custom, EntityCount, Metrics, ScalarStats};
+use sekai::output::{CsvWriter, RunMetadata};
+use sekai::registry::{Definition, Registry};
+use sekai::schedule::{Schedule, Scheduler};
+use sekai::render::{write_svg, Bounds, FrameRenderer, ImageFormat, Rgb, Scene};
+use sekai::simulation::Simulation;
+use sekai::trajectory::{Trajectory, TrajectoryRecorder};
+use std::sync::atomic::{AtomicU64, Ordering};
 use rand::distributions::Normal;
 use rand::distributions::IndependentSample;
+use rand::{Rng, SeedableRng, StdRng};
 
 #[derive(Debug, Clone)]
 struct FireflyWorld {
     firefly_swarm: Vec<Firefly>,
+    scheduler: Scheduler, // the order fireflies react to each other in
+    channel: Channel<(u64, Color)>, // messages on their way to a firefly
+    mailbox: Mailbox<Color>, // messages arrived this tick, by firefly index
+    events: Option<EventQueue<FireflyEvent>>, // flashes to come, with events
+    clock: f64, // the ticks run so far, with events
 }
 impl World<Color> for FireflyWorld {
     // todo: figure out if a firefly can see another firefly
     fn update(&mut self) {
-        println!("*** UPDATING WORLD ***");
+        // With events, flashes happen at their own times rather than as
+        // cooldowns run out, so just handle those due within this tick
+        if let Some(mut queue) = self.events.take() {
+            self.clock += 1.0;
+            while queue.next_time().is_some_and(|time| time <= self.clock) {
+                if let Some((_, event)) = queue.pop() {
+                    self.handle(event, &mut queue);
+                }
+            }
+            self.events = Some(queue);
+            return;
+        }
+
+        // Hand over the messages which arrived during the last tick, each
+        // firefly seeing them averaged into one
+        for (id, message) in self.channel.advance(1.0) {
+            if let Some(index) = self.firefly_swarm.iter().position(|f| f.id == id) {
+                self.mailbox.post(index, message);
+            }
+        }
+        self.mailbox.deliver(&mut self.firefly_swarm);
 
         // Update all fireflies
         // TODO: use iterator for update. currently dosn't work
         /*
         self.firefly_swarm
             .par_iter_mut()
-            .map(|ref mut firefly| firefly.update((self.clone()) as &mut World<Color>));
+            .map(|ref mut firefly| firefly.update((self.clone()) as &mut dyn World<Color>));
         */
 
         let num_fireflies = self.num_entities();
         for i in 0..num_fireflies {
             // call each firefly's update function
             let mut cur_firefly = self.firefly_swarm[i].clone();
-            cur_firefly.update(self as &mut World<Color>);
+            cur_firefly.update(self as &mut dyn World<Color>);
             self.firefly_swarm[i] = cur_firefly;
         }
 
         // Remove dead fireflies
         self.firefly_swarm
-            .retain(|ref firefly| firefly.lifetime != 0);
+            .retain(|firefly| firefly.lifetime != 0);
 
         // Compare remaining fireflies
 
-        // clone all fireflies
-        let firefly_swarm_b = self.firefly_swarm.clone();
-        //let mut iter = self.firefly_swarm.iter_mut();
-
-        for (index_a, firefly_a) in self.firefly_swarm.iter_mut().enumerate() {
-            for (index_b, firefly_b) in firefly_swarm_b.iter().enumerate() {
-                if index_a == index_b {
-                    continue;
+        // Fireflies step towards the flashes they can see, activated as the
+        // schedule says
+        self.scheduler
+            .update(&mut self.firefly_swarm, |index_a, firefly_a, swarm| {
+                for (index_b, firefly_b) in swarm.iter().enumerate() {
+                    if index_a == index_b {
+                        continue;
+                    }
+                    let dist = FireflyWorld::get_dist(&firefly_a.pos, &firefly_b.pos);
+                    let close: bool =
+                        dist < firefly_a.sight_range && firefly_b.cur_flash_cooldown == 0;
+
+                    if close {
+                        // Fireflies step towards each other
+                        let new_pos_a = firefly_a.unit_step(&firefly_b.pos, dist);
+
+                        firefly_a.update_position(&new_pos_a);
+                    }
                 }
-                let dist = FireflyWorld::get_dist(&firefly_a.pos, &firefly_b.pos);
-                let close: bool = dist < Firefly::SIGHT_RANGE && firefly_b.cur_flash_cooldown == 0;
-
-                if close {
-                    println!("Close with {:?} and {:?}", firefly_a.pos, firefly_b.pos);
-
-                    // Fireflies step towards each other
-                    let new_pos_a = (&firefly_a).unit_step(&firefly_b.pos, dist);
-
-                    firefly_a.update_position(&new_pos_a);
-                    println!("New position: {:?}", firefly_a.pos);
-                }
-            }
-        }
+            });
 
         // Iterate through all fireflies in a specifc range,
         // average color
@@ -101,40 +138,136 @@ impl World<Color> for FireflyWorld {
         self.firefly_swarm.len()
     }
 
-    // calls receive message on every firefly
+    // sends the message to every firefly, through the channel
     fn receive_message(&mut self, message: Color) {
-        for firefly in &mut self.firefly_swarm {
-            firefly.receive_message(message.clone());
+        for firefly in &self.firefly_swarm {
+            let dist = FireflyWorld::get_dist(&firefly.pos, &message.pos);
+            self.channel
+                .send((firefly.id, message.clone()), dist as f64);
+        }
+    }
+}
+
+impl WorldView for FireflyWorld {
+    type Entity = Firefly;
+    fn entities(&self) -> &[Firefly] {
+        &self.firefly_swarm
+    }
+}
+
+// Flashes as timed events, instead of counting cooldowns down every tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FireflyEvent {
+    // a firefly flashes, `after` ticks since its last event
+    Flash { id: u64, after: u32 },
+}
+
+impl EventWorld for FireflyWorld {
+    type Event = FireflyEvent;
+    fn handle(&mut self, event: FireflyEvent, queue: &mut EventQueue<FireflyEvent>) {
+        let FireflyEvent::Flash { id, after } = event;
+        let index = match self.firefly_swarm.iter().position(|f| f.id == id) {
+            Some(index) => index,
+            None => return,
+        };
+        // the firefly ages until the flash, and dies instead if it runs out
+        let lifetime = self.firefly_swarm[index].lifetime.saturating_sub(after);
+        if lifetime == 0 {
+            self.firefly_swarm.swap_remove(index);
+            return;
+        }
+        let flasher = &mut self.firefly_swarm[index];
+        flasher.lifetime = lifetime;
+        flasher.cur_flash_cooldown = flasher.flash_cooldown;
+        let pos = flasher.pos.clone();
+        let interval = flasher.flash_interval();
+
+        // Fireflies that see the flash step towards it
+        for (other, firefly) in self.firefly_swarm.iter_mut().enumerate() {
+            let dist = FireflyWorld::get_dist(&firefly.pos, &pos);
+            if other != index && dist < firefly.sight_range {
+                let step = firefly.unit_step(&pos, dist);
+                firefly.update_position(&step);
+            }
         }
+        queue.schedule_in(interval as f64, FireflyEvent::Flash { id, after: interval });
     }
 }
 
 impl FireflyWorld {
+    // an empty world where fireflies react to each other synchronously,
+    // and messages arrive on the next tick
+    fn new() -> Self {
+        FireflyWorld::from_params(&FireflyParams::default(), 0)
+    }
+
+    // an empty world activating fireflies and delivering messages as the
+    // params say, seeded for the random schedules and channels
+    fn from_params(params: &FireflyParams, seed: u64) -> Self {
+        FireflyWorld {
+            firefly_swarm: Vec::new(),
+            scheduler: Scheduler::new(params.schedule, seed),
+            channel: Channel::new(params.channel, seed),
+            mailbox: Mailbox::new(),
+            events: None,
+            clock: 0.0,
+        }
+    }
+
     // birth of new entity
     fn add_entity(&mut self, firefly: Firefly) {
         self.firefly_swarm.push(firefly);
     }
 
     // death of some entity
+    #[allow(dead_code)]
     fn remove_entity(&mut self, idx: usize) {
         self.firefly_swarm.swap_remove(idx);
     }
 
+    // schedules the first flash of every firefly, for running the swarm
+    // with events
+    fn schedule_flashes(&self, queue: &mut EventQueue<FireflyEvent>) {
+        for firefly in &self.firefly_swarm {
+            let after = firefly.cur_flash_cooldown.div_ceil(firefly.flash_rate);
+            queue.schedule_in(after as f64, FireflyEvent::Flash { id: firefly.id, after });
+        }
+    }
+
+    // switches the swarm over to flashing as events, each update handling
+    // the flashes of one tick
+    fn start_events(&mut self) {
+        let mut queue = EventQueue::new();
+        self.schedule_flashes(&mut queue);
+        self.events = Some(queue);
+    }
+
+    #[cfg(test)]
     fn create_swarm(&mut self, n: usize, distribution: usize) {
-        if distribution == 1 {
-            let mut rng = rand::thread_rng();
+        let mut rng = rand::thread_rng();
+        self.create_swarm_with(n, distribution, &FireflyParams::default(), &mut rng);
+    }
 
-            // mean 0, standard deviation 100:
-            let normal = Normal::new(0.0, 100.0);
+    fn create_swarm_with<R: Rng>(
+        &mut self,
+        n: usize,
+        distribution: usize,
+        params: &FireflyParams,
+        rng: &mut R,
+    ) {
+        if distribution == 1 {
+            // mean 0, standard deviation from the params:
+            let normal = Normal::new(0.0, params.spread);
             for _ in 0..n {
                 let position: Vec<f32> =
-                    (0..3).map(|_| normal.ind_sample(&mut rng) as f32).collect();
-                let firefly = Firefly::new_at(position);
+                    (0..3).map(|_| normal.ind_sample(rng) as f32).collect();
+                let firefly = Firefly::from_params(position, params);
                 self.add_entity(firefly);
             }
         }
     }
     //This outputs the midpoint between two fireflies.
+    #[cfg(test)]
     fn calc_midpoint(&mut self, ff1: &Firefly, ff2: &Firefly) -> Vec<f32> {
         //Iterate over the coordinates in firefly 1.
         ff1
@@ -149,6 +282,7 @@ impl FireflyWorld {
     }
 
     // serializes fireflyswarm
+    #[cfg(test)]
     fn serialize(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string(&self.firefly_swarm)
     }
@@ -188,7 +322,24 @@ impl Color {
     }
 }
 
-impl<'a> std::ops::Mul<f32> for &'a Color {
+// lets the flashes a firefly sees in a tick be averaged
+impl Combine for Color {
+    fn combine(&self, other: &Color) -> Color {
+        Color {
+            red: self.red + other.red,
+            green: self.green + other.green,
+            blue: self.blue + other.blue,
+            pos: self.pos.combine(&other.pos),
+        }
+    }
+    fn scale(&self, factor: f64) -> Color {
+        let mut color = self * factor as f32;
+        color.pos = self.pos.scale(factor);
+        color
+    }
+}
+
+impl std::ops::Mul<f32> for &Color {
     type Output = Color;
     fn mul(self, rhs: f32) -> Self::Output {
         Color {
@@ -202,6 +353,7 @@ impl<'a> std::ops::Mul<f32> for &'a Color {
 
 #[derive(Debug, Clone, Serialize)]
 struct Firefly {
+    id: u64,
     pos: Vec<f32>,
     color: Color,            // RGB
     flash_cooldown: u32,     // initial flash cooldown
@@ -209,35 +361,100 @@ struct Firefly {
     flash_rate: u32,         // the amt by which flash cooldown decreases
     lifetime: u32,           // the number of ticks a firefly lives for
     reproduction_range: f32, // for far a firefly must be to reproduce
+    sight_range: f32,        // how far a firefly can see another flash
+}
+
+// Parameters of the swarm, loaded from a TOML or JSON file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct FireflyParams {
+    swarm_size: usize,       // fireflies scattered around the origin
+    spread: f64,             // standard deviation of the scattered swarm
+    sight_range: f32,
+    flash_cooldown: u32,
+    flash_rate: u32,
+    lifetime: u32,
+    reproduction_range: f32,
+    schedule: Schedule, // how fireflies are activated every tick
+    channel: ChannelParams, // how messages between fireflies are delivered
+    events: bool, // flash as timed events instead of counting cooldowns down
+}
+
+impl Default for FireflyParams {
+    fn default() -> Self {
+        FireflyParams {
+            swarm_size: 0,
+            spread: 100.0,
+            sight_range: 5.0,
+            flash_cooldown: 10,
+            flash_rate: 1,
+            lifetime: 50,
+            reproduction_range: 5.0,
+            schedule: Schedule::Synchronous,
+            channel: ChannelParams::default(),
+            events: false,
+        }
+    }
+}
+
+impl Config for FireflyParams {
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.spread <= 0.0 {
+            return Err(ConfigError::invalid("spread", "must be positive"));
+        }
+        if self.sight_range < 0.0 {
+            return Err(ConfigError::invalid("sight_range", "must not be negative"));
+        }
+        if self.flash_rate == 0 || self.flash_rate > self.flash_cooldown {
+            return Err(ConfigError::invalid(
+                "flash_rate",
+                "must be between 1 and flash_cooldown",
+            ));
+        }
+        if let Schedule::Poisson { rate } = self.schedule {
+            if rate <= 0.0 {
+                return Err(ConfigError::invalid("schedule", "rate must be positive"));
+            }
+        }
+        self.schedule.validate().map_err(|e| e.within("schedule"))?;
+        self.channel.validate().map_err(|e| e.within("channel"))
+    }
 }
 
 impl Firefly {
-    // associated const for sight range
-    const SIGHT_RANGE: f32 = 5_f32;
+    // hands out a unique id to every firefly ever born
+    fn next_id() -> u64 {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    }
 
     // constructor
+    #[cfg(test)]
     fn new(num_dimensions: usize) -> Self {
-        Firefly {
-            pos: Vec::with_capacity(num_dimensions),
-            color: Color::new(num_dimensions),
-            flash_cooldown: 10,      // TODO: placeholder
-            cur_flash_cooldown: 10,  // TODO: placeholder
-            flash_rate: 1,           // TODO: placeholder
-            lifetime: 50,            // TODO: placeholder
-            reproduction_range: 5.0, // TODO: placeholder
-        }
+        let mut firefly = Firefly::from_params(Vec::new(), &FireflyParams::default());
+        firefly.pos.reserve(num_dimensions);
+        firefly.color = Color::new(num_dimensions);
+        firefly
     }
 
     // construct at position
+    #[cfg(test)]
     fn new_at(pos: Vec<f32>) -> Self {
+        Firefly::from_params(pos, &FireflyParams::default())
+    }
+
+    // construct at position with the given parameters
+    fn from_params(pos: Vec<f32>, params: &FireflyParams) -> Self {
         Firefly {
-            pos: pos.clone(),
+            id: Firefly::next_id(),
             color: Color::new(pos.len()),
-            flash_cooldown: 10,      // TODO: placeholder
-            cur_flash_cooldown: 10,  // TODO: placeholder
-            flash_rate: 1,           // TODO: placeholder
-            lifetime: 50,            // TODO: placeholder
-            reproduction_range: 5.0, // TODO: placeholder
+            pos,
+            flash_cooldown: params.flash_cooldown,
+            cur_flash_cooldown: params.flash_cooldown,
+            flash_rate: params.flash_rate,
+            lifetime: params.lifetime,
+            reproduction_range: params.reproduction_range,
+            sight_range: params.sight_range,
         }
     }
 
@@ -251,6 +468,11 @@ impl Firefly {
             .collect()
     }
 
+    // the number of ticks between two flashes
+    fn flash_interval(&self) -> u32 {
+        self.flash_cooldown.div_ceil(self.flash_rate)
+    }
+
     // updates this firefly's position by some calculated delta
     fn update_position(&mut self, delta: &[f32]) {
         self.pos = self.pos
@@ -261,11 +483,36 @@ impl Firefly {
     }
 }
 
+// fireflies react to the average of the flashes they see
+impl Aggregate<Color> for Firefly {
+    fn reducer(&self) -> &dyn Reducer<Color> {
+        &Mean
+    }
+}
+
+// exposes firefly state to the trajectory recorder
+impl Observable for Firefly {
+    fn id(&self) -> u64 {
+        self.id
+    }
+    fn position(&self) -> &[f32] {
+        &self.pos
+    }
+    fn field(&self, name: &str) -> Option<f64> {
+        match name {
+            "lifetime" => Some(self.lifetime as f64),
+            "cur_flash_cooldown" => Some(self.cur_flash_cooldown as f64),
+            "red" => Some(self.color.red as f64),
+            _ => None,
+        }
+    }
+}
+
 /// Fireflies communicate with lights, represented in the
 /// tuple (RGB)
 impl Entity<Color> for Firefly {
     // todo: receive message, send message,
-    fn update(&mut self, _world: &World<Color>) {
+    fn update(&mut self, _world: &dyn World<Color>) {
         // At end of cooldown
         if self.cur_flash_cooldown == 0 {
             // Reset cooldown
@@ -281,8 +528,9 @@ impl Entity<Color> for Firefly {
         // Lose some life
         self.lifetime -= 1;
 
-        // Tick down flash cooldown
-        self.cur_flash_cooldown -= self.flash_rate;
+        // Tick down flash cooldown, stopping at 0 when the rate does not
+        // divide it, so a flash comes every flash_interval ticks
+        self.cur_flash_cooldown = self.cur_flash_cooldown.saturating_sub(self.flash_rate);
     }
     fn receive_message(&mut self, message: Color) {
         // If a firefly sees some color, it must by some logic
@@ -301,43 +549,156 @@ impl Entity<Color> for Firefly {
 
         // Fireflies step towards each other
         let dist = FireflyWorld::get_dist(&self.pos, &message.pos);
-        let new_pos = (&self).unit_step(&message.pos, dist);
+        let new_pos = self.unit_step(&message.pos, dist);
         self.update_position(&new_pos);
-        println!("New position: {:?}", self.pos,);
     }
 }
 
-fn main() {
-    let mut world = FireflyWorld {
-        firefly_swarm: Vec::new(),
-    };
+// the swarm statistics recorded every tick, grouping fireflies that can
+// see each other
+fn firefly_metrics(params: &FireflyParams) -> Metrics<FireflyWorld> {
+    let mut metrics = Metrics::new();
+    metrics.add_probe(EntityCount);
+    metrics.add_probe(ClusterProbe::new(
+        "groups",
+        Method::Components {
+            threshold: params.sight_range as f64,
+        },
+    ));
+    metrics.add_probe(ScalarStats::new("lifetime", |firefly: &Firefly| {
+        firefly.lifetime as f64
+    }));
+    metrics.add_probe(Custom::new("flashing", |world: &FireflyWorld| {
+        world
+            .firefly_swarm
+            .iter()
+            .filter(|firefly| firefly.cur_flash_cooldown == 0)
+            .count() as f64
+    }));
+    metrics
+}
 
-    // create a swarm
-    //world.create_swarm(1e6 as usize, 1);
+// the fireflies whose lifetime has not run out
+fn living(world: &FireflyWorld) -> usize {
+    world
+        .firefly_swarm
+        .iter()
+        .filter(|firefly| firefly.lifetime > 0)
+        .count()
+}
 
-    world.add_entity(Firefly::new_at(vec![5_f32, 12_f32]));
-    world.add_entity(Firefly::new_at(vec![0_f32, 0_f32]));
-    world.add_entity(Firefly::new_at(vec![0_f32, 1_f32]));
-    world.add_entity(Firefly::new_at(vec![7_f32, 10_f32]));
+// makes the swarm runnable by name from the sekai command line
+fn registry() -> Registry {
+    let mut firefly = Definition::new("firefly", |params: &FireflyParams, seed| {
+        let mut world = FireflyWorld::from_params(params, seed);
+        let mut rng = StdRng::from_seed(&[seed as usize][..]);
+        world.create_swarm_with(params.swarm_size, 1, params, &mut rng);
+        if params.events {
+            world.start_events();
+        }
+        world
+    });
+    firefly.set_description("Fireflies synchronizing their flashes");
+    firefly.set_metrics(firefly_metrics);
+    firefly.record_trajectory(&["lifetime", "cur_flash_cooldown"]);
+    firefly.stop_when(|_| vec![Box::new(ExtinctionDetector::new(living))]);
+
+    let mut registry = Registry::new();
+    registry.add(firefly);
+    registry
+}
 
-    for _ in 0..1 {
-        world.update();
+fn main() {
+    // with arguments, e.g. `run firefly --config swarm.toml`, act as the
+    // sekai command line
+    if std::env::args().len() > 1 {
+        cli::main(&registry());
+        return;
+    }
+    let params = FireflyParams::default();
+
+    let mut world = FireflyWorld::new();
+
+    world.add_entity(Firefly::from_params(vec![5_f32, 12_f32], &params));
+    world.add_entity(Firefly::from_params(vec![0_f32, 0_f32], &params));
+    world.add_entity(Firefly::from_params(vec![0_f32, 1_f32], &params));
+    world.add_entity(Firefly::from_params(vec![7_f32, 10_f32], &params));
+
+    // record swarm statistics every tick
+    let mut metrics = firefly_metrics(&params);
+
+    // stream the statistics to stdout as CSV
+    let mut metadata = RunMetadata::new("firefly");
+    metadata
+        .set_parameters(&params)
+        .expect("Failed to record parameters");
+    metrics.add_sink(CsvWriter::new(std::io::stdout(), metadata));
+
+    // record how every firefly moves
+    let mut trajectory = TrajectoryRecorder::new(Vec::new(), &["lifetime", "cur_flash_cooldown"]);
+
+    // draw every tick, flashing fireflies in yellow
+    let frames_dir = std::env::temp_dir().join("sekai_firefly_frames");
+    let mut frames = FrameRenderer::new(&frames_dir, ImageFormat::Png, |world: &FireflyWorld| {
+        let mut scene = Scene::new(200, 200, Bounds::new(-5.0, -5.0, 15.0, 15.0));
+        scene.add_points(world, 3.0, |firefly: &Firefly| {
+            if firefly.cur_flash_cooldown == 0 {
+                Rgb::new(255, 230, 90)
+            } else {
+                let c = &firefly.color;
+                Rgb::new(c.red as u8, c.green as u8, c.blue as u8)
+            }
+        });
+        scene
+    })
+    .expect("Failed to create frame directory");
+
+    // stop early if every firefly's lifetime runs out
+    let mut extinction = ExtinctionDetector::new(living);
+
+    let mut sim = Simulation::new(world);
+    let termination = sim.run_until(
+        20,
+        &mut [&mut metrics, &mut trajectory, &mut frames],
+        &mut [&mut extinction],
+    );
+    eprintln!("run ended: {}", termination);
+    metrics.finish().expect("Failed to write metrics");
+    frames.finish().expect("Failed to render frames");
+    eprintln!("frames written to {}", frames_dir.display());
+
+    let recorded = trajectory.finish().expect("Failed to record trajectory");
+    let trajectory = Trajectory::read(&recorded[..]).expect("Failed to read trajectory");
+    let first = &trajectory.frames[0].entities[0];
+    for (tick, state) in trajectory.entity_between(first.id, 1, 5) {
+        eprintln!("firefly {} at tick {}: {:?}", first.id, tick, state.position);
     }
 
-    println!("{}", world.num_entities());
+    // summarize the whole run as one vector image of every firefly's trail
+    let mut scene = Scene::new(200, 200, Bounds::new(-5.0, -5.0, 15.0, 15.0));
+    scene.add_trails(&trajectory, 1, 20, |_| Rgb::new(255, 230, 90));
+    let svg_path = std::env::temp_dir().join("sekai_firefly_trails.svg");
+    let svg_file = std::fs::File::create(&svg_path).expect("Failed to create svg");
+    write_svg(&scene, svg_file).expect("Failed to write svg");
+    eprintln!("trails written to {}", svg_path.display());
+
+    // keep the parameters the run actually used next to its results
+    let config_path = std::env::temp_dir().join("sekai_firefly_config.toml");
+    config::save(&params, &config_path).expect("Failed to write config");
+    eprintln!("config written to {}", config_path.display());
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use sekai::channel::Delay;
+    use sekai::event::EventSimulation;
     #[test]
     fn test_world_update() {
-        let mut world = FireflyWorld {
-            firefly_swarm: Vec::new(),
-        };
+        let mut world = FireflyWorld::new();
 
         // create a swarm
-        world.create_swarm(1e6 as usize, 1);
+        world.create_swarm(1e3 as usize, 1);
 
         //world.add_entity(Firefly::new_at(vec![5_f32, 12_f32]));
         //world.add_entity(Firefly::new_at(vec![0_f32, 0_f32]));
@@ -353,9 +714,7 @@ mod test {
 
     #[test]
     fn test_get_dist() {
-        let world = FireflyWorld {
-            firefly_swarm: Vec::new(),
-        };
+        let world = FireflyWorld::new();
 
         let mut a = Firefly::new(2);
         let mut b = Firefly::new(2);
@@ -367,9 +726,7 @@ mod test {
 
     #[test]
     fn test_unit_step() {
-        let world = FireflyWorld {
-            firefly_swarm: Vec::new(),
-        };
+        let world = FireflyWorld::new();
 
         let mut a = Firefly::new(2);
         let mut b = Firefly::new(2);
@@ -386,9 +743,7 @@ mod test {
 
     #[test]
     fn test_midpoint() {
-        let mut world = FireflyWorld {
-            firefly_swarm: Vec::new(),
-        };
+        let mut world = FireflyWorld::new();
 
         let mut a = Firefly::new(3);
         let mut b = Firefly::new(3);
@@ -404,19 +759,124 @@ mod test {
 
     #[test]
     fn test_create_swarm() {
-        let mut world = FireflyWorld {
-            firefly_swarm: Vec::new(),
-        };
+        let mut world = FireflyWorld::new();
 
         world.create_swarm(15, 1);
         assert_eq!(world.num_entities(), 15);
     }
 
     #[test]
-    fn test_serialize() {
-        let mut world = FireflyWorld {
-            firefly_swarm: Vec::new(),
+    fn test_params() {
+        let params: FireflyParams = config::from_toml_str("sight_range = 8.0").unwrap();
+        assert_eq!(Firefly::from_params(vec![0_f32], &params).sight_range, 8_f32);
+        assert_eq!(params.lifetime, 50);
+
+        let error = config::from_toml_str::<FireflyParams>("flash_rate = 0").unwrap_err();
+        assert_eq!(error.key(), Some("flash_rate"));
+
+        // a rate which does not divide the cooldown flashes every 4 ticks
+        let params: FireflyParams = config::from_toml_str("flash_rate = 3").unwrap();
+        let mut firefly = Firefly::from_params(vec![0_f32], &params);
+        let world = FireflyWorld::new();
+        let mut flashes = Vec::new();
+        for tick in 1..=12 {
+            firefly.update(&world);
+            if firefly.cur_flash_cooldown == 0 {
+                flashes.push(tick);
+            }
+        }
+        assert_eq!(flashes, vec![4, 8, 12]);
+        assert_eq!(firefly.flash_interval(), 4);
+
+        let params: FireflyParams = config::from_toml_str("schedule = \"random_order\"").unwrap();
+        assert_eq!(params.schedule, Schedule::RandomOrder);
+        let params: FireflyParams =
+            config::from_toml_str("[schedule.poisson]\nrate = 0.5").unwrap();
+        assert_eq!(params.schedule, Schedule::Poisson { rate: 0.5 });
+        let error = config::from_toml_str::<FireflyParams>("[schedule.poisson]\nrate = 0.0")
+            .unwrap_err();
+        assert_eq!(error.key(), Some("schedule"));
+        let error = config::from_toml_str::<FireflyParams>("[schedule.poisson]\nrate = inf")
+            .unwrap_err();
+        assert_eq!(error.key(), Some("schedule.poisson.rate"));
+    }
+
+    #[test]
+    fn test_flash_events() {
+        let mut world = FireflyWorld::new();
+        world.add_entity(Firefly::new_at(vec![0_f32, 0_f32]));
+        world.add_entity(Firefly::new_at(vec![3_f32, 0_f32]));
+        world.firefly_swarm[1].cur_flash_cooldown = 5;
+        let mut sim = EventSimulation::new(world.clone());
+        world.schedule_flashes(sim.queue_mut());
+
+        // the second firefly flashes first, at 5, pulling the first one in
+        sim.step();
+        assert_eq!(sim.time(), 5.0);
+        assert_eq!(sim.world().firefly_swarm[0].pos, vec![1_f32, 0_f32]);
+
+        // flashes every 10 ticks until their 50 tick lifetimes run out
+        sim.run(100.0, 1.0, &mut []);
+        assert_eq!(sim.world().num_entities(), 0);
+        assert_eq!(sim.handled(), 11);
+
+        // the same flashes happen a tick at a time in the registered model
+        world.start_events();
+        for _ in 0..5 {
+            world.update();
+        }
+        assert_eq!(world.firefly_swarm[0].pos, vec![1_f32, 0_f32]);
+        for _ in 0..100 {
+            world.update();
+        }
+        assert_eq!(world.num_entities(), 0);
+    }
+
+    #[test]
+    fn test_delayed_messages() {
+        let mut params = FireflyParams::default();
+        params.channel.delay = Delay::Distance {
+            base: 0.0,
+            per_unit: 1.0,
         };
+        let mut world = FireflyWorld::from_params(&params, 0);
+        world.add_entity(Firefly::new_at(vec![1_f32, 0_f32]));
+        world.add_entity(Firefly::new_at(vec![3_f32, 0_f32]));
+        let mut flash = Color::new(2);
+        flash.pos = vec![0_f32, 0_f32];
+        world.receive_message(flash);
+
+        // the near firefly sees the flash after 1 tick, the far one after 3
+        world.update();
+        assert_eq!(world.firefly_swarm[0].pos, vec![0_f32, 0_f32]);
+        assert_eq!(world.firefly_swarm[1].pos, vec![3_f32, 0_f32]);
+        world.update();
+        world.update();
+        assert_eq!(world.firefly_swarm[1].pos, vec![2_f32, 0_f32]);
+        assert_eq!(world.channel.stats().delivered, 2);
+    }
+
+    #[test]
+    fn test_flashes_are_averaged() {
+        let mut world = FireflyWorld::new();
+        world.add_entity(Firefly::new_at(vec![0_f32, 0_f32]));
+        for pos in vec![vec![4_f32, 0_f32], vec![0_f32, 4_f32]] {
+            let mut flash = Color::new(2);
+            flash.pos = pos;
+            world.receive_message(flash);
+        }
+
+        // one step towards the middle of both flashes, instead of one
+        // towards each
+        world.update();
+        let step = 0.5_f32.sqrt();
+        let pos = &world.firefly_swarm[0].pos;
+        assert!((pos[0] - step).abs() < 1e-6 && (pos[1] - step).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut world = FireflyWorld::new();
         world.add_entity(Firefly::new_at(vec![5_f32, 12_f32]));
         world.add_entity(Firefly::new_at(vec![0_f32, 0_f32]));
         world.add_entity(Firefly::new_at(vec![0_f32, 1_f32]));
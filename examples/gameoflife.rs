@@ -2,9 +2,21 @@
  * Game of Life turing complete test
  */
 
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate sekai;
-use sekai::world::World;
+use sekai::cli;
+use sekai::config::{Config, ConfigError};
+use sekai::detect::{hash_state, CycleDetector, Detector, ExtinctionDetector};
+use sekai::world::{World, WorldView};
 use sekai::entity::Entity;
+use sekai::grid::{Grid, GridAutomaton, Life};
+use sekai::observer::{EntityCount, Metrics};
+use sekai::registry::{Definition, Registry};
+use sekai::render::{Bounds, Rgb, Scene};
+use sekai::simulation::Simulation;
+use sekai::terminal::LiveView;
 use std::cmp::{Eq, PartialEq};
 type Proximity = u32;
 
@@ -18,8 +30,24 @@ struct Board {
     // maybe proximity is the message?
 }
 impl World<Proximity> for Board {
+    // steps the living cells with Conway's rules on a wrapping board
     fn update(&mut self) {
-        // TODO: update the cells based on the rules
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut grid = Grid::new(width, height, false);
+        for cell in &self.cell_swarm {
+            grid.set(cell.x as usize, cell.y as usize, true);
+        }
+        let mut life = GridAutomaton::new(grid, Life::conway(), 0);
+        life.step();
+        let grid = life.grid();
+        self.cell_swarm = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| grid.get(x, y))
+            .map(|(x, y)| Cell {
+                x: x as u32,
+                y: y as u32,
+            })
+            .collect();
     }
     fn num_entities(&self) -> usize {
         self.cell_swarm.len()
@@ -27,20 +55,24 @@ impl World<Proximity> for Board {
     fn receive_message(&mut self, _message: Proximity) {
         // TODO: for each cell, calculate its location based on proximity
         // and make updates based on rules
-        // TODO: don't double borrow here
-        let mut cur_idx = 0;
-        for cell in &mut self.cell_swarm {
-            if cell != &mut self.cell_swarm[cur_idx] {
+        for (cur_idx, cell) in self.cell_swarm.iter().enumerate() {
+            if cell != &self.cell_swarm[cur_idx] {
                 println!("Found other cell!");
             }
-            cur_idx += 1;
         }
     }
 }
+impl WorldView for Board {
+    type Entity = Cell;
+    fn entities(&self) -> &[Cell] {
+        &self.cell_swarm
+    }
+}
 impl Board {
     fn add_entity(&mut self, cell: Cell) {
         self.cell_swarm.push(cell);
     }
+    #[cfg(test)]
     fn remove_entity(&mut self, index: usize) {
         self.cell_swarm.swap_remove(index);
     }
@@ -67,17 +99,136 @@ impl PartialEq for Cell {
 impl Eq for Cell {}
 
 impl Entity<Proximity> for Cell {
-    fn update(&mut self, _board: &World<Proximity>) {}
+    fn update(&mut self, _board: &dyn World<Proximity>) {}
     fn receive_message(&mut self, _message: Proximity) {}
 }
 impl Cell {
+    #[cfg(test)]
     fn new() -> Self {
         Cell { x: 0_u32, y: 0_u32 }
     }
 }
 
+// Parameters of the board, loaded from a TOML or JSON file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct LifeParams {
+    width: u32,
+    height: u32,
+    pattern: String, // "glider", "blinker" or "block", in the top left
+}
+
+impl Default for LifeParams {
+    fn default() -> Self {
+        LifeParams {
+            width: 10,
+            height: 10,
+            pattern: "glider".into(),
+        }
+    }
+}
+
+impl Config for LifeParams {
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.width < 3 {
+            return Err(ConfigError::invalid("width", "must be at least 3"));
+        }
+        if self.height < 3 {
+            return Err(ConfigError::invalid("height", "must be at least 3"));
+        }
+        if LifeParams::cells(&self.pattern).is_none() {
+            return Err(ConfigError::invalid(
+                "pattern",
+                "must be \"glider\", \"blinker\" or \"block\"",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl LifeParams {
+    // the living cells of a named pattern
+    fn cells(pattern: &str) -> Option<&'static [(u32, u32)]> {
+        match pattern {
+            "glider" => Some(&[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]),
+            "blinker" => Some(&[(0, 1), (1, 1), (2, 1)]),
+            "block" => Some(&[(0, 0), (1, 0), (0, 1), (1, 1)]),
+            _ => None,
+        }
+    }
+
+    fn board(&self) -> Board {
+        let mut board = Board::new();
+        board.width = self.width;
+        board.height = self.height;
+        for &(x, y) in LifeParams::cells(&self.pattern).unwrap_or(&[]) {
+            board.add_entity(Cell { x, y });
+        }
+        board
+    }
+}
+
+// makes the board runnable by name from the sekai command line
+fn registry() -> Registry {
+    let mut life = Definition::new("life", |params: &LifeParams, _seed| params.board());
+    life.set_description("Conway's Game of Life");
+    life.set_metrics(|_| {
+        let mut metrics = Metrics::new();
+        metrics.add_probe(EntityCount);
+        metrics
+    });
+    // stop once every cell has died, or the board repeats itself
+    life.stop_when(|_| {
+        let repeats = CycleDetector::new(|board: &Board| {
+            let cells: Vec<(u32, u32)> = board.cell_swarm.iter().map(|c| (c.x, c.y)).collect();
+            hash_state(&cells)
+        });
+        vec![
+            Box::new(ExtinctionDetector::entities()) as Box<dyn Detector<Board>>,
+            Box::new(repeats),
+        ]
+    });
+
+    let mut registry = Registry::new();
+    registry.add(life);
+    registry
+}
+
 fn main() {
-    println!("This is the main function");
+    // with arguments, e.g. `run life --set pattern=blinker`, act as the
+    // sekai command line
+    if std::env::args().len() > 1 {
+        cli::main(&registry());
+        return;
+    }
+    let board = LifeParams::default().board();
+
+    // watch the board in the terminal, living cells in white
+    let mut view = LiveView::new(|board: &Board| {
+        let (width, height) = (board.width as usize, board.height as usize);
+        let mut scene = Scene::new(
+            board.width * 4,
+            board.height * 4,
+            Bounds::new(0.0, 0.0, width as f32, height as f32),
+        );
+        scene.add_grid(width, height, |x, y| {
+            let alive = board
+                .entities()
+                .iter()
+                .any(|cell| cell.x as usize == x && cell.y as usize == y);
+            if alive {
+                Rgb::WHITE
+            } else {
+                Rgb::new(30, 30, 30)
+            }
+        });
+        scene
+    });
+
+    let mut sim = Simulation::new(board);
+    while sim.tick() < 100 && !view.quit_requested() {
+        sim.step(&mut [&mut view]);
+    }
 }
 
 #[test]
@@ -94,3 +245,33 @@ fn test_game_of_life() {
     let test_message: Proximity = 5;
     board.receive_message(test_message);
 }
+
+#[test]
+fn test_blinker_oscillates() {
+    let params = LifeParams {
+        pattern: "blinker".into(),
+        ..LifeParams::default()
+    };
+    let mut board = params.board();
+    board.update();
+    let mut cells: Vec<(u32, u32)> = board.cell_swarm.iter().map(|c| (c.x, c.y)).collect();
+    cells.sort();
+    assert_eq!(cells, vec![(1, 0), (1, 1), (1, 2)]);
+    board.update();
+    assert_eq!(board.cell_swarm, params.board().cell_swarm);
+}
+
+#[test]
+fn test_validate_names_the_short_side() {
+    let narrow = LifeParams {
+        width: 2,
+        ..LifeParams::default()
+    };
+    assert_eq!(narrow.validate().unwrap_err().key(), Some("width"));
+    let short = LifeParams {
+        height: 2,
+        ..LifeParams::default()
+    };
+    assert_eq!(short.validate().unwrap_err().key(), Some("height"));
+    assert!(LifeParams::default().validate().is_ok());
+}
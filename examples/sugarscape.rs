@@ -0,0 +1,50 @@
+/*
+ * Sugarscape: agents harvesting a regrowing sugar landscape
+ *
+ * Agents see along the four lattice directions, move to the richest free
+ * site in sight and pay for their metabolism out of what they harvest.
+ * Those who starve or grow old are replaced by newborns, and the Gini
+ * coefficient of their wealth shows inequality emerging from equal rules.
+ *
+ */
+extern crate sekai;
+
+use sekai::cli;
+use sekai::registry::Registry;
+use sekai::simulation::Simulation;
+use sekai::sugarscape::{self, Sugarscape, SugarscapeParams};
+
+// makes the society runnable by name from the sekai command line
+fn registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.add(sugarscape::definition());
+    registry
+}
+
+fn main() {
+    // with arguments, e.g. `run sugarscape --set replace=false`, act as the
+    // sekai command line
+    if std::env::args().len() > 1 {
+        cli::main(&registry());
+        return;
+    }
+    let params = SugarscapeParams::default();
+    let mut metrics = sugarscape::metrics(&params);
+    let mut sim = Simulation::new(Sugarscape::from_params(&params, 0));
+    sim.run(200, &mut [&mut metrics]);
+
+    println!("tick  agents  mean wealth  gini");
+    for (index, &tick) in metrics.ticks().iter().enumerate() {
+        if tick % 20 != 0 {
+            continue;
+        }
+        let value = |name: &str| metrics.series(name).unwrap().values[index];
+        println!(
+            "{:4}  {:6}  {:11.1}  {:.2}",
+            tick,
+            value("entities"),
+            value("wealth_mean"),
+            value("wealth_gini")
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! Measures of emergence and complexity, computed after the fact from
+//! recorded metrics or trajectories.
+//!
+//! Information measures work on sequences of discrete states and are in
+//! bits. Continuous series, like an `observer::TimeSeries` or a field pulled
+//! out of a trajectory with `field_series`, are first binned with
+//! `discretize`.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use trajectory::Trajectory;
+
+/// Shannon entropy of the distribution of states in a sequence, in bits
+/// # Arguments
+/// * `states` - One observed state per sample
+pub fn entropy<T: Hash + Eq>(states: &[T]) -> f64 {
+    let mut counts = HashMap::new();
+    for state in states {
+        *counts.entry(state).or_insert(0) += 1;
+    }
+    entropy_of_counts(counts.values().cloned())
+}
+
+/// Shannon entropy of a distribution given as counts, in bits
+/// # Arguments
+/// * `counts` - How often each state was observed
+pub fn entropy_of_counts<I: IntoIterator<Item = usize>>(counts: I) -> f64 {
+    let counts: Vec<usize> = counts.into_iter().filter(|&c| c > 0).collect();
+    let total = counts.iter().sum::<usize>() as f64;
+    -counts
+        .iter()
+        .map(|&c| {
+            let p = c as f64 / total;
+            p * p.log2()
+        })
+        .sum::<f64>()
+}
+
+/// Mutual information between two paired sequences of states, in bits,
+/// e.g. the states of two agents or two regions at the same ticks
+/// # Arguments
+/// * `a` - The first sequence
+/// * `b` - The second sequence, as long as the first
+pub fn mutual_information<A: Hash + Eq, B: Hash + Eq>(a: &[A], b: &[B]) -> f64 {
+    assert_eq!(a.len(), b.len(), "the sequences must be paired");
+    let joint: Vec<(&A, &B)> = a.iter().zip(b).collect();
+    (entropy(a) + entropy(b) - entropy(&joint)).max(0.0)
+}
+
+/// Local transfer entropy from a source to a target sequence at every tick
+/// where it is defined, in bits. The mean of the series is the transfer
+/// entropy: how much the source's past tells about the target's next state
+/// beyond what the target's own past does
+/// # Arguments
+/// * `source` - The sequence which may drive the target
+/// * `target` - The driven sequence, as long as the source
+/// * `history` - How many past states of each sequence are conditioned on
+/// # Returns
+/// One value per tick from `history` on, so `target.len() - history` values
+pub fn local_transfer_entropy<T: Hash + Eq>(
+    source: &[T],
+    target: &[T],
+    history: usize,
+) -> Vec<f64> {
+    assert_eq!(source.len(), target.len(), "the sequences must be paired");
+    assert!(history > 0, "the history must hold at least one state");
+    if target.len() <= history {
+        return Vec::new();
+    }
+    let samples: Vec<(&[T], &[T], &T)> = (history..target.len())
+        .map(|t| (&target[t - history..t], &source[t - history..t], &target[t]))
+        .collect();
+
+    let mut joint = HashMap::new();
+    let mut histories = HashMap::new();
+    let mut target_next = HashMap::new();
+    let mut target_past = HashMap::new();
+    for &(past, source_past, next) in &samples {
+        *joint.entry((past, source_past, next)).or_insert(0) += 1;
+        *histories.entry((past, source_past)).or_insert(0) += 1;
+        *target_next.entry((past, next)).or_insert(0) += 1;
+        *target_past.entry(past).or_insert(0) += 1;
+    }
+    samples
+        .iter()
+        .map(|&(past, source_past, next)| {
+            let with_source =
+                joint[&(past, source_past, next)] as f64 / histories[&(past, source_past)] as f64;
+            let without_source = target_next[&(past, next)] as f64 / target_past[past] as f64;
+            (with_source / without_source).log2()
+        })
+        .collect()
+}
+
+/// Transfer entropy from a source to a target sequence, in bits
+/// # Arguments
+/// * `source` - The sequence which may drive the target
+/// * `target` - The driven sequence, as long as the source
+/// * `history` - How many past states of each sequence are conditioned on
+pub fn transfer_entropy<T: Hash + Eq>(source: &[T], target: &[T], history: usize) -> f64 {
+    let local = local_transfer_entropy(source, target, history);
+    if local.is_empty() {
+        return 0.0;
+    }
+    local.iter().sum::<f64>() / local.len() as f64
+}
+
+/// Lempel-Ziv (1976) complexity: the number of distinct phrases found
+/// while parsing the sequence left to right, each phrase being the
+/// shortest which cannot be copied from earlier in the sequence
+/// # Arguments
+/// * `sequence` - E.g. one row of a cellular automaton
+pub fn lempel_ziv_complexity<T: Eq>(sequence: &[T]) -> usize {
+    let n = sequence.len();
+    if n < 2 {
+        return n;
+    }
+    // Kaspar and Schuster's algorithm
+    let (mut complexity, mut prefix, mut i, mut k, mut k_max) = (1, 1, 0, 1, 1);
+    loop {
+        if sequence[i + k - 1] == sequence[prefix + k - 1] {
+            k += 1;
+            if prefix + k > n {
+                complexity += 1;
+                break;
+            }
+        } else {
+            k_max = k_max.max(k);
+            i += 1;
+            if i == prefix {
+                complexity += 1;
+                prefix += k_max;
+                if prefix + 1 > n {
+                    break;
+                }
+                i = 0;
+                k = 1;
+                k_max = 1;
+            } else {
+                k = 1;
+            }
+        }
+    }
+    complexity
+}
+
+/// Lempel-Ziv complexity scaled by `log_b(n) / n`, where `b` is the number
+/// of distinct states, so a long random sequence scores close to 1 and a
+/// regular one close to 0
+/// # Arguments
+/// * `sequence` - E.g. one row of a cellular automaton
+pub fn normalized_lempel_ziv<T: Hash + Eq>(sequence: &[T]) -> f64 {
+    let n = sequence.len() as f64;
+    let mut alphabet: Vec<&T> = Vec::new();
+    for state in sequence {
+        if !alphabet.contains(&state) {
+            alphabet.push(state);
+        }
+    }
+    if sequence.len() < 2 {
+        return 0.0;
+    }
+    let base = (alphabet.len().max(2)) as f64;
+    lempel_ziv_complexity(sequence) as f64 * n.log(base) / n
+}
+
+/// Bins continuous values into equally wide bins between their minimum
+/// and maximum, so they can be used as discrete states. NaN values go to
+/// an extra bin numbered `bins`
+/// # Arguments
+/// * `values` - The values to bin
+/// * `bins` - The number of bins
+pub fn discretize(values: &[f64], bins: usize) -> Vec<usize> {
+    assert!(bins > 0, "there must be at least one bin");
+    let finite = values.iter().filter(|v| !v.is_nan());
+    let (min, max) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+        (lo.min(v), hi.max(v))
+    });
+    values
+        .iter()
+        .map(|&v| {
+            if v.is_nan() {
+                bins
+            } else if max > min {
+                (((v - min) / (max - min) * bins as f64) as usize).min(bins - 1)
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// Gini coefficient of some non-negative values, e.g. the wealth of every
+/// agent: 0 when all are equal, approaching 1 when one holds everything.
+/// NaN when there are no values, and 0 when they are all zero
+/// # Arguments
+/// * `values` - The values, in any order
+pub fn gini(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return f64::NAN;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let total: f64 = sorted.iter().sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    // with the values ascending, the mean difference weighs each by its rank
+    let n = sorted.len() as f64;
+    let ranked: f64 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| (i + 1) as f64 * v)
+        .sum();
+    2.0 * ranked / (n * total) - (n + 1.0) / n
+}
+
+/// Gets the values of one field of one entity at every recorded tick where
+/// the entity exists
+/// # Arguments
+/// * `trajectory` - The recorded run
+/// * `id` - The id of the entity
+/// * `field` - The recorded field
+pub fn field_series(trajectory: &Trajectory, id: u64, field: &str) -> Option<Vec<f64>> {
+    let index = trajectory.field_index(field)?;
+    Some(
+        trajectory
+            .frames
+            .iter()
+            .filter_map(|frame| frame.entity(id))
+            .map(|entity| entity.fields[index])
+            .collect(),
+    )
+}
+
+/// Mean distance from every point to its nearest neighbor, using the
+/// first two coordinates. NaN for fewer than two points
+/// # Arguments
+/// * `positions` - The positions of the agents
+pub fn mean_nearest_neighbor<P: AsRef<[f32]>>(positions: &[P]) -> f64 {
+    let points = xy_points(positions);
+    if points.len() < 2 {
+        return f64::NAN;
+    }
+    let total: f64 = points
+        .iter()
+        .enumerate()
+        .map(|(i, a)| {
+            points
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| i != j)
+                .map(|(_, b)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt())
+                .fold(f64::INFINITY, f64::min)
+        })
+        .sum();
+    total / points.len() as f64
+}
+
+/// Clark-Evans aggregation index: the mean nearest neighbor distance
+/// divided by its expectation for the same number of points placed
+/// uniformly at random over their bounding box. Below 1 the points are
+/// aggregated, near 1 random and above 1 evenly spread. NaN for fewer than
+/// two points or points on a line
+/// # Arguments
+/// * `positions` - The positions of the agents
+pub fn clark_evans<P: AsRef<[f32]>>(positions: &[P]) -> f64 {
+    let points = xy_points(positions);
+    let (min_x, min_y, max_x, max_y) = bounding_box(&points);
+    let area = (max_x - min_x) * (max_y - min_y);
+    if points.len() < 2 || area <= 0.0 {
+        return f64::NAN;
+    }
+    let density = points.len() as f64 / area;
+    mean_nearest_neighbor(positions) / (0.5 / density.sqrt())
+}
+
+/// Morisita's index of dispersion over a grid of equal quadrats covering
+/// the bounding box of the points. Near 1 the points are random, above 1
+/// clumped and below 1 evenly spread. NaN for fewer than two points
+/// # Arguments
+/// * `positions` - The positions of the agents
+/// * `cells` - The number of quadrats along each side of the grid
+pub fn morisita<P: AsRef<[f32]>>(positions: &[P], cells: usize) -> f64 {
+    assert!(cells > 0, "the grid must have at least one quadrat");
+    let points = xy_points(positions);
+    let n = points.len();
+    if n < 2 {
+        return f64::NAN;
+    }
+    let (min_x, min_y, max_x, max_y) = bounding_box(&points);
+    let cell = |v: f64, min: f64, max: f64| {
+        if max > min {
+            (((v - min) / (max - min) * cells as f64) as usize).min(cells - 1)
+        } else {
+            0
+        }
+    };
+    let mut counts = vec![0usize; cells * cells];
+    for &(x, y) in &points {
+        counts[cell(y, min_y, max_y) * cells + cell(x, min_x, max_x)] += 1;
+    }
+    let pairs: usize = counts.iter().map(|&c| c * c.saturating_sub(1)).sum();
+    (cells * cells) as f64 * pairs as f64 / (n * (n - 1)) as f64
+}
+
+fn xy_points<P: AsRef<[f32]>>(positions: &[P]) -> Vec<(f64, f64)> {
+    positions
+        .iter()
+        .map(|p| {
+            let p = p.as_ref();
+            (
+                p.first().cloned().unwrap_or(0.0) as f64,
+                p.get(1).cloned().unwrap_or(0.0) as f64,
+            )
+        })
+        .collect()
+}
+
+fn bounding_box(points: &[(f64, f64)]) -> (f64, f64, f64, f64) {
+    points.iter().fold(
+        (
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        ),
+        |(x0, y0, x1, y1), &(x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, StdRng};
+
+    fn close(a: f64, b: f64, tolerance: f64) -> bool {
+        (a - b).abs() < tolerance
+    }
+
+    #[test]
+    fn test_entropy_and_mutual_information() {
+        assert_eq!(entropy(&[0, 1, 2, 3]), 2.0);
+        assert_eq!(entropy(&["on", "on"]), 0.0);
+        assert_eq!(entropy_of_counts(vec![5, 5, 0]), 1.0);
+
+        let a = [0, 1, 0, 1, 1, 0, 0, 1];
+        let b = [0, 0, 1, 1, 0, 0, 1, 1];
+        assert_eq!(mutual_information(&a, &a), 1.0);
+        assert!(close(mutual_information(&a, &b), 0.0, 1e-12));
+    }
+
+    #[test]
+    fn test_transfer_entropy_finds_driver() {
+        let mut rng = StdRng::from_seed(&[1usize][..]);
+        let source: Vec<bool> = (0..5000).map(|_| rng.gen()).collect();
+        // the target copies the source one tick later
+        let mut target = vec![false];
+        target.extend_from_slice(&source[..source.len() - 1]);
+
+        assert!(close(transfer_entropy(&source, &target, 1), 1.0, 0.01));
+        assert!(close(transfer_entropy(&target, &source, 1), 0.0, 0.01));
+        assert_eq!(local_transfer_entropy(&source, &target, 2).len(), 4998);
+    }
+
+    #[test]
+    fn test_lempel_ziv() {
+        let sequence: Vec<u8> = "0001101001000101".bytes().collect();
+        assert_eq!(lempel_ziv_complexity(&sequence), 6);
+        assert_eq!(lempel_ziv_complexity(&[1; 100]), 2);
+        assert!(normalized_lempel_ziv(&[1; 1000]) < 0.1);
+
+        let mut rng = StdRng::from_seed(&[2usize][..]);
+        let random: Vec<bool> = (0..10000).map(|_| rng.gen()).collect();
+        assert!(close(normalized_lempel_ziv(&random), 1.0, 0.1));
+        assert_eq!(discretize(&[0.0, 0.5, 1.0, f64::NAN], 2), vec![0, 1, 1, 2]);
+    }
+
+    #[test]
+    fn test_gini() {
+        assert_eq!(gini(&[3.0, 3.0, 3.0]), 0.0);
+        assert_eq!(gini(&[0.0, 0.0]), 0.0);
+        assert!(close(gini(&[4.0, 1.0, 3.0, 2.0]), 0.25, 1e-12));
+        // one of four holding everything is as unequal as four can be
+        assert!(close(gini(&[0.0, 0.0, 7.0, 0.0]), 0.75, 1e-12));
+        assert!(gini(&[]).is_nan());
+    }
+
+    #[test]
+    fn test_aggregation_indices() {
+        let grid: Vec<Vec<f32>> = (0..100)
+            .map(|i| vec![(i % 10) as f32, (i / 10) as f32])
+            .collect();
+        // four tight clumps in the corners of the box
+        let clumped: Vec<Vec<f32>> = (0..100)
+            .map(|i| {
+                let (clump, k) = (i / 25, i % 25);
+                vec![
+                    (clump % 2) as f32 * 10.0 + (k % 5) as f32 * 0.01,
+                    (clump / 2) as f32 * 10.0 + (k / 5) as f32 * 0.01,
+                ]
+            })
+            .collect();
+        assert!(clark_evans(&grid) > 1.5);
+        assert!(clark_evans(&clumped) < 0.5);
+        assert!(morisita(&grid, 2) < 1.0);
+        assert!(morisita(&clumped, 5) > 1.0);
+        assert_eq!(mean_nearest_neighbor(&grid), 1.0);
+        assert!(clark_evans(&[vec![0.0f32, 0.0]]).is_nan());
+    }
+}
@@ -0,0 +1,359 @@
+//! One-dimensional cellular automata: a row of cells which are on or off,
+//! each updated every tick from the cells within a radius of it.
+//!
+//! Rules are either Wolfram's 256 elementary rules, numbered by the new
+//! states of the eight neighborhoods, or totalistic rules of any radius,
+//! which only count the live cells in a neighborhood. Rule 110 is Turing
+//! complete, making it the simplest target for computation experiments.
+//!
+//! ```rust
+//! # use sekai::automaton::{Automaton, Boundary, Rule};
+//! let mut rule90 = Automaton::single(9, Rule::Elementary(90), Boundary::Fixed(false));
+//! let history = rule90.run(3);
+//! assert_eq!(Automaton::row_to_string(&history[3]), ".#.#.#.#.");
+//! ```
+
+use std::fmt;
+
+use rand::Rng;
+
+use render::{Bounds, Rgb, Scene};
+use world::World;
+
+/// How a cell's next state follows from its neighborhood
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// An elementary rule of radius 1. Bit `4l + 2c + r` of the number is
+    /// the next state of a cell which is `c` with neighbors `l` and `r`
+    Elementary(u8),
+    /// A totalistic rule of any radius up to 31. Bit `k` of the code is the
+    /// next state of a cell with `k` live cells in its neighborhood,
+    /// itself included
+    Totalistic { radius: usize, code: u64 },
+}
+
+impl Rule {
+    /// Gets how many cells on each side a cell sees
+    pub fn radius(&self) -> usize {
+        match *self {
+            Rule::Elementary(_) => 1,
+            Rule::Totalistic { radius, .. } => radius,
+        }
+    }
+
+    /// Gets the next state of the cell in the middle of a neighborhood
+    /// # Arguments
+    /// * `neighborhood` - The `2 * radius + 1` cells around the cell, left
+    ///   to right
+    pub fn apply(&self, neighborhood: &[bool]) -> bool {
+        match *self {
+            Rule::Elementary(number) => {
+                let index = neighborhood
+                    .iter()
+                    .fold(0, |index, &cell| index << 1 | cell as u8);
+                number >> index & 1 == 1
+            }
+            Rule::Totalistic { code, .. } => {
+                let live = neighborhood.iter().filter(|&&cell| cell).count();
+                live < 64 && code >> live & 1 == 1
+            }
+        }
+    }
+}
+
+/// What lies beyond the ends of the row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    /// The row wraps around into a ring
+    Periodic,
+    /// Cells beyond the ends stay in one state forever
+    Fixed(bool),
+}
+
+/// A row of cells updated together by a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Automaton {
+    cells: Vec<bool>,
+    rule: Rule,
+    boundary: Boundary,
+    generation: u64,
+}
+
+impl Automaton {
+    /// Creates an automaton at generation 0
+    /// # Arguments
+    /// * `cells` - The starting row, `true` for live cells
+    /// * `rule` - How cells update
+    /// * `boundary` - What lies beyond the ends of the row
+    pub fn new(cells: Vec<bool>, rule: Rule, boundary: Boundary) -> Self {
+        assert!(
+            rule.radius() <= 31,
+            "rules may not see more than 31 cells on each side"
+        );
+        Automaton {
+            cells,
+            rule,
+            boundary,
+            generation: 0,
+        }
+    }
+
+    /// Creates an automaton whose only live cell is in the middle of the row
+    /// # Arguments
+    /// * `width` - The number of cells
+    /// * `rule` - How cells update
+    /// * `boundary` - What lies beyond the ends of the row
+    pub fn single(width: usize, rule: Rule, boundary: Boundary) -> Self {
+        let mut cells = vec![false; width];
+        if width > 0 {
+            cells[width / 2] = true;
+        }
+        Automaton::new(cells, rule, boundary)
+    }
+
+    /// Creates an automaton whose cells are each live with a probability
+    /// # Arguments
+    /// * `width` - The number of cells
+    /// * `density` - The chance of each cell being live
+    /// * `rule` - How cells update
+    /// * `boundary` - What lies beyond the ends of the row
+    /// * `rng` - The source of randomness
+    pub fn random<R: Rng>(
+        width: usize,
+        density: f64,
+        rule: Rule,
+        boundary: Boundary,
+        rng: &mut R,
+    ) -> Self {
+        let cells = (0..width).map(|_| rng.gen::<f64>() < density).collect();
+        Automaton::new(cells, rule, boundary)
+    }
+
+    /// Gets the current row
+    pub fn cells(&self) -> &[bool] {
+        &self.cells
+    }
+
+    /// Gets how the cells update
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// Gets the number of steps taken so far
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Gets the number of live cells
+    pub fn live(&self) -> usize {
+        self.cells.iter().filter(|&&cell| cell).count()
+    }
+
+    /// Updates every cell at once from the row before the step
+    pub fn step(&mut self) {
+        let radius = self.rule.radius() as isize;
+        let width = self.cells.len() as isize;
+        let mut neighborhood = Vec::with_capacity(2 * radius as usize + 1);
+        let next = (0..width)
+            .map(|i| {
+                neighborhood.clear();
+                for j in i - radius..=i + radius {
+                    neighborhood.push(self.cell(j, width));
+                }
+                self.rule.apply(&neighborhood)
+            })
+            .collect();
+        self.cells = next;
+        self.generation += 1;
+    }
+
+    /// Takes a number of steps, returning the space-time history: the row
+    /// before the first step followed by the row after each step
+    /// # Arguments
+    /// * `generations` - The number of steps to take
+    pub fn run(&mut self, generations: usize) -> Vec<Vec<bool>> {
+        let mut history = Vec::with_capacity(generations + 1);
+        history.push(self.cells.clone());
+        for _ in 0..generations {
+            self.step();
+            history.push(self.cells.clone());
+        }
+        history
+    }
+
+    /// Writes a row with `#` for live cells and `.` for dead ones
+    /// # Arguments
+    /// * `row` - The row
+    pub fn row_to_string(row: &[bool]) -> String {
+        row.iter()
+            .map(|&cell| if cell { '#' } else { '.' })
+            .collect()
+    }
+
+    /// Gets a cell of the row, or what the boundary holds beyond its ends
+    fn cell(&self, index: isize, width: isize) -> bool {
+        if index >= 0 && index < width {
+            return self.cells[index as usize];
+        }
+        match self.boundary {
+            Boundary::Periodic => self.cells[index.rem_euclid(width) as usize],
+            Boundary::Fixed(state) => state,
+        }
+    }
+}
+
+impl fmt::Display for Automaton {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&Automaton::row_to_string(&self.cells))
+    }
+}
+
+/// Lets a simulation step the automaton, one generation per tick. Cells
+/// exchange no messages
+impl World<()> for Automaton {
+    fn update(&mut self) {
+        self.step();
+    }
+    fn num_entities(&self) -> usize {
+        self.cells.len()
+    }
+    fn receive_message(&mut self, _message: ()) {}
+}
+
+/// Draws a space-time diagram: generation 0 as the top row of the image and
+/// every later generation below the one before, live cells in black on white
+/// # Arguments
+/// * `history` - The rows of every generation, e.g. from `Automaton::run`
+/// * `cell_size` - The width and height of each cell in pixels
+pub fn space_time_diagram(history: &[Vec<bool>], cell_size: u32) -> Scene {
+    let cols = history.iter().map(Vec::len).max().unwrap_or(0);
+    let rows = history.len();
+    let mut scene = Scene::new(
+        cols as u32 * cell_size,
+        rows as u32 * cell_size,
+        Bounds::new(0.0, 0.0, cols as f32, rows as f32),
+    );
+    scene.background = Rgb::WHITE;
+    scene.add_grid(cols, rows, |col, row| {
+        if history[row].get(col).cloned().unwrap_or(false) {
+            Rgb::BLACK
+        } else {
+            Rgb::WHITE
+        }
+    });
+    scene
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+    use render::Canvas;
+
+    fn rows(history: &[Vec<bool>]) -> Vec<String> {
+        history
+            .iter()
+            .map(|row| Automaton::row_to_string(row))
+            .collect()
+    }
+
+    #[test]
+    fn test_rule_30() {
+        let mut rule30 = Automaton::single(11, Rule::Elementary(30), Boundary::Fixed(false));
+        let history = rule30.run(4);
+        assert_eq!(
+            rows(&history),
+            vec![
+                ".....#.....",
+                "....###....",
+                "...##..#...",
+                "..##.####..",
+                ".##..#...#.",
+            ]
+        );
+
+        // the chaotic center column, as used for random numbers
+        let mut wide = Automaton::single(81, Rule::Elementary(30), Boundary::Fixed(false));
+        let center: Vec<u8> = wide.run(20).iter().map(|row| row[40] as u8).collect();
+        assert_eq!(
+            center,
+            vec![1, 1, 0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1, 0, 1, 1, 0, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn test_rule_90() {
+        // Sierpiński's triangle: generation n has 2^(ones in n) live cells
+        let mut rule90 = Automaton::single(65, Rule::Elementary(90), Boundary::Fixed(false));
+        let live: Vec<usize> = rule90
+            .run(16)
+            .iter()
+            .map(|row| row.iter().filter(|&&cell| cell).count())
+            .collect();
+        assert_eq!(
+            live,
+            vec![1, 2, 2, 4, 2, 4, 4, 8, 2, 4, 4, 8, 4, 8, 8, 16, 2]
+        );
+
+        // on a ring of 8 the two ends of generation 4 meet and cancel
+        let mut ring = Automaton::single(8, Rule::Elementary(90), Boundary::Periodic);
+        ring.run(4);
+        assert_eq!(ring.live(), 0);
+        assert_eq!(ring.generation(), 4);
+    }
+
+    #[test]
+    fn test_rule_110() {
+        let mut cells = vec![false; 12];
+        cells[11] = true;
+        let mut rule110 = Automaton::new(cells, Rule::Elementary(110), Boundary::Fixed(false));
+        assert_eq!(
+            rows(&rule110.run(7)),
+            vec![
+                "...........#",
+                "..........##",
+                ".........###",
+                "........##.#",
+                ".......#####",
+                "......##...#",
+                ".....###..##",
+                "....##.#.###",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_totalistic_and_rendering() {
+        // rule 150 is the odd parity of the neighborhood, so totalistic
+        let mut rng = StdRng::from_seed(&[4][..]);
+        let start = Automaton::random(30, 0.5, Rule::Elementary(150), Boundary::Periodic, &mut rng);
+        let parity = Rule::Totalistic {
+            radius: 1,
+            code: 0b1010,
+        };
+        let mut totalistic = Automaton::new(start.cells().to_vec(), parity, Boundary::Periodic);
+        assert_eq!(totalistic.run(10), start.clone().run(10));
+
+        // a radius 2 majority vote keeps wide blocks and erases lone cells
+        let majority = Rule::Totalistic {
+            radius: 2,
+            code: 0b111000,
+        };
+        let mut cells = vec![false; 12];
+        for &i in &[1, 5, 6, 7, 8] {
+            cells[i] = true;
+        }
+        let mut vote = Automaton::new(cells, majority, Boundary::Fixed(true));
+        vote.step();
+        assert_eq!(vote.to_string(), "#....####...");
+
+        let scene = space_time_diagram(&[vec![true, false], vec![false, true]], 3);
+        let canvas = Canvas::render(&scene);
+        assert_eq!((canvas.width(), canvas.height()), (6, 6));
+        assert_eq!(canvas.get(0, 0), Some(Rgb::BLACK));
+        assert_eq!(canvas.get(5, 0), Some(Rgb::WHITE));
+        assert_eq!(canvas.get(5, 5), Some(Rgb::BLACK));
+    }
+}
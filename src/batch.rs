@@ -0,0 +1,515 @@
+//! Parameter sweeps: running one model many times over a space of parameters
+//! and seeds, in parallel, and collecting the final metrics of every run
+//! into one table.
+//!
+//! Parameters are named by their key in the model's `config::Config`, with
+//! nested keys separated by `.`. Every point of a sweep only overrides the
+//! keys it names, so the rest keep their defaults.
+
+use std::io::{self, Write};
+
+use rand::{Rng, SeedableRng, StdRng};
+use rayon::prelude::*;
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+
+use config::{self, Config, ConfigError};
+use output::csv_field;
+
+/// One set of parameter values, as pairs of key and value
+pub type Point = Vec<(String, Value)>;
+
+/// The values one parameter can take
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dimension {
+    /// Exactly these values
+    Values(Vec<Value>),
+    /// Real numbers from `min` to `max`. A grid takes `steps` evenly spaced
+    /// values including both ends
+    Range { min: f64, max: f64, steps: usize },
+    /// Every integer from `min` to `max`, both included
+    Integers { min: i64, max: i64 },
+}
+
+impl Dimension {
+    /// Gets the values a grid visits
+    fn grid(&self) -> Vec<Value> {
+        match *self {
+            Dimension::Values(ref values) => values.clone(),
+            Dimension::Range { min, max, steps } => match steps {
+                0 => Vec::new(),
+                1 => vec![json!(min)],
+                _ => (0..steps)
+                    .map(|i| json!(min + (max - min) * i as f64 / (steps - 1) as f64))
+                    .collect(),
+            },
+            Dimension::Integers { min, max } => (min..=max).map(|i| json!(i)).collect(),
+        }
+    }
+
+    /// Maps a number in `[0, 1)` to a value of the dimension
+    fn at(&self, u: f64) -> Value {
+        match *self {
+            Dimension::Values(ref values) => {
+                values[((u * values.len() as f64) as usize).min(values.len() - 1)].clone()
+            }
+            Dimension::Range { min, max, .. } => json!(min + (max - min) * u),
+            Dimension::Integers { min, max } => {
+                // in i128, as the span of two i64 may not fit one
+                let span = i128::from(max) - i128::from(min);
+                let offset = ((u * (span + 1) as f64) as i128).min(span);
+                json!((i128::from(min) + offset) as i64)
+            }
+        }
+    }
+
+    /// Checks whether no value can be drawn. A range is continuous, so its
+    /// steps only matter to grids
+    fn is_empty(&self) -> bool {
+        match *self {
+            Dimension::Values(ref values) => values.is_empty(),
+            Dimension::Range { min, max, .. } => max < min,
+            Dimension::Integers { min, max } => max < min,
+        }
+    }
+}
+
+/// The parameters a sweep varies and the values each can take
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParameterSpace {
+    dimensions: Vec<(String, Dimension)>,
+}
+
+impl ParameterSpace {
+    pub fn new() -> Self {
+        ParameterSpace::default()
+    }
+
+    /// Adds a parameter to vary
+    /// # Arguments
+    /// * `key` - The config key, with nested keys separated by `.`
+    /// * `dimension` - The values the parameter can take
+    pub fn add<K: Into<String>>(&mut self, key: K, dimension: Dimension) {
+        self.dimensions.push((key.into(), dimension));
+    }
+
+    /// Adds a parameter which takes exactly the given values
+    /// # Arguments
+    /// * `key` - The config key, with nested keys separated by `.`
+    /// * `values` - Anything serializable into the parameter's type
+    pub fn add_values<K: Into<String>, V: Serialize>(
+        &mut self,
+        key: K,
+        values: &[V],
+    ) -> Result<(), serde_json::Error> {
+        let values = values
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<_, _>>()?;
+        self.add(key, Dimension::Values(values));
+        Ok(())
+    }
+
+    /// Gets the keys of the varied parameters
+    pub fn keys(&self) -> Vec<&str> {
+        self.dimensions.iter().map(|d| d.0.as_str()).collect()
+    }
+
+    /// Gets every combination of the values of every parameter. The last
+    /// parameter added varies fastest
+    pub fn grid(&self) -> Vec<Point> {
+        let mut points = vec![Point::new()];
+        for (key, dimension) in &self.dimensions {
+            let values = dimension.grid();
+            points = points
+                .iter()
+                .flat_map(|point| {
+                    values.iter().map(move |value| {
+                        let mut point = point.clone();
+                        point.push((key.clone(), value.clone()));
+                        point
+                    })
+                })
+                .collect();
+        }
+        points
+    }
+
+    /// Draws points independently and uniformly from the space
+    /// # Arguments
+    /// * `n` - How many points to draw
+    /// * `seed` - Seed of the draws, so a sweep can be repeated
+    pub fn random(&self, n: usize, seed: u64) -> Vec<Point> {
+        if self.has_empty_dimension() {
+            return Vec::new();
+        }
+        let mut rng = seeded_rng(seed);
+        (0..n)
+            .map(|_| {
+                self.dimensions
+                    .iter()
+                    .map(|(key, dimension)| (key.clone(), dimension.at(rng.gen_range(0.0, 1.0))))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Draws a Latin hypercube sample: every parameter's range is split
+    /// into `n` equal strata and every stratum is used by exactly one point,
+    /// which covers the space more evenly than independent draws
+    /// # Arguments
+    /// * `n` - How many points to draw
+    /// * `seed` - Seed of the draws, so a sweep can be repeated
+    pub fn latin_hypercube(&self, n: usize, seed: u64) -> Vec<Point> {
+        if self.has_empty_dimension() {
+            return Vec::new();
+        }
+        let mut rng = seeded_rng(seed);
+        let mut points = vec![Point::new(); n];
+        for (key, dimension) in &self.dimensions {
+            let mut strata: Vec<usize> = (0..n).collect();
+            rng.shuffle(&mut strata);
+            for (point, stratum) in points.iter_mut().zip(strata) {
+                let u = (stratum as f64 + rng.gen_range(0.0, 1.0)) / n as f64;
+                point.push((key.clone(), dimension.at(u)));
+            }
+        }
+        points
+    }
+
+    fn has_empty_dimension(&self) -> bool {
+        self.dimensions.iter().any(|d| d.1.is_empty())
+    }
+}
+
+/// Runs a model once per point and replicate, spread across all cores
+#[derive(Debug, Clone, PartialEq)]
+pub struct Batch {
+    points: Vec<Point>,
+    replicates: usize,
+    seed: u64,
+}
+
+impl Batch {
+    /// Creates a batch of `points.len() * replicates` runs. Replicate `r`
+    /// of every point is given the seed `seed + r`, so each point is run
+    /// with the same seeds
+    /// # Arguments
+    /// * `points` - The parameters of each run, e.g. from a `ParameterSpace`
+    /// * `replicates` - How many times each point is run
+    /// * `seed` - The seed of the first replicate. Later replicates count up
+    ///   from it, wrapping around after `u64::MAX`
+    pub fn new(points: Vec<Point>, replicates: usize, seed: u64) -> Self {
+        Batch {
+            points,
+            replicates,
+            seed,
+        }
+    }
+
+    /// Gets the parameters of each run
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// Gets the number of runs in the batch
+    pub fn len(&self) -> usize {
+        self.points.len() * self.replicates
+    }
+
+    /// Checks if the batch has no runs
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Runs every simulation in parallel. Every point is resolved against
+    /// the config's defaults before anything runs, so a bad point fails the
+    /// whole batch straight away
+    /// # Arguments
+    /// * `run` - Runs one simulation with the given parameters and seed,
+    ///   returning its final metrics, e.g. from `Metrics::final_values`
+    pub fn run<C, F>(&self, run: F) -> Result<BatchResults, ConfigError>
+    where
+        C: Config + Sync,
+        F: Fn(&C, u64) -> Vec<(String, f64)> + Sync,
+    {
+        self.run_with(config::resolve::<C>, run)
+    }
+
+    /// Runs every simulation in parallel, turning each point into
+    /// parameters with a custom resolver, e.g. to add overrides shared by
+    /// every point or for models whose parameter type is only known at run
+    /// time
+    /// # Arguments
+    /// * `resolve` - Turns the overrides of a point into parameters
+    /// * `run` - Runs one simulation with the given parameters and seed,
+    ///   returning its final metrics
+    pub fn run_with<T, R, F>(&self, resolve: R, run: F) -> Result<BatchResults, ConfigError>
+    where
+        T: Sync,
+        R: Fn(Value) -> Result<T, ConfigError>,
+        F: Fn(&T, u64) -> Vec<(String, f64)> + Sync,
+    {
+        let configs = self
+            .points
+            .iter()
+            .map(|point| {
+                let mut overrides = Value::Object(Map::new());
+                for (key, value) in point {
+                    config::set_key(&mut overrides, key, value.clone());
+                }
+                resolve(overrides)
+            })
+            .collect::<Result<Vec<T>, _>>()?;
+
+        let replicates = self.replicates;
+        let seed = self.seed;
+        let runs: Vec<Run> = (0..self.len())
+            .into_par_iter()
+            .map(|i| {
+                let (point, replicate) = (i / replicates, i % replicates);
+                let seed = seed.wrapping_add(replicate as u64);
+                (point, seed, run(&configs[point], seed))
+            })
+            .collect();
+
+        let mut results = BatchResults::default();
+        for point in &self.points {
+            for (key, _) in point {
+                if !results.parameters.contains(key) {
+                    results.parameters.push(key.clone());
+                }
+            }
+        }
+        for (_, _, values) in &runs {
+            for (name, _) in values {
+                if !results.metrics.contains(name) {
+                    results.metrics.push(name.clone());
+                }
+            }
+        }
+        for (point, seed, values) in runs {
+            let parameters = results
+                .parameters
+                .iter()
+                .map(|key| {
+                    self.points[point]
+                        .iter()
+                        .find(|p| p.0 == *key)
+                        .map_or(Value::Null, |p| p.1.clone())
+                })
+                .collect();
+            let values = results
+                .metrics
+                .iter()
+                .map(|name| {
+                    values
+                        .iter()
+                        .find(|v| v.0 == *name)
+                        .map_or(f64::NAN, |v| v.1)
+                })
+                .collect();
+            results.rows.push(BatchRow {
+                point,
+                seed,
+                parameters,
+                values,
+            });
+        }
+        Ok(results)
+    }
+}
+
+/// The final metrics of one run of a batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchRow {
+    /// Index of the run's point in the batch
+    pub point: usize,
+    pub seed: u64,
+    /// One value per parameter column, null if the point left it default
+    pub parameters: Vec<Value>,
+    /// One value per metric column, NaN if the run did not report it
+    pub values: Vec<f64>,
+}
+
+/// The final metrics of every run of a batch, ordered by point and then
+/// replicate
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchResults {
+    pub parameters: Vec<String>,
+    pub metrics: Vec<String>,
+    pub rows: Vec<BatchRow>,
+}
+
+impl BatchResults {
+    /// Gets every run's value of a metric
+    /// # Arguments
+    /// * `name` - The metric column
+    pub fn metric(&self, name: &str) -> Option<Vec<f64>> {
+        let column = self.metrics.iter().position(|m| m == name)?;
+        Some(self.rows.iter().map(|row| row.values[column]).collect())
+    }
+
+    /// Writes the table as CSV with one column per parameter, then `seed`,
+    /// then one column per metric
+    /// # Arguments
+    /// * `out` - Where to write the table
+    pub fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let header: Vec<String> = self
+            .parameters
+            .iter()
+            .map(String::as_str)
+            .chain(Some("seed"))
+            .chain(self.metrics.iter().map(String::as_str))
+            .map(csv_field)
+            .collect();
+        writeln!(out, "{}", header.join(","))?;
+        for row in &self.rows {
+            let fields: Vec<String> = row
+                .parameters
+                .iter()
+                .map(|value| match *value {
+                    Value::Null => String::new(),
+                    Value::String(ref s) => csv_field(s),
+                    ref value => csv_field(&value.to_string()),
+                })
+                .chain(Some(row.seed.to_string()))
+                .chain(row.values.iter().map(|v| v.to_string()))
+                .collect();
+            writeln!(out, "{}", fields.join(","))?;
+        }
+        out.flush()
+    }
+}
+
+/// The point index, seed and final metrics of one finished run
+type Run = (usize, u64, Vec<(String, f64)>);
+
+fn seeded_rng(seed: u64) -> StdRng {
+    StdRng::from_seed(&[seed as usize, (seed >> 32) as usize][..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Params {
+        sight_range: f64,
+        cooldown: u32,
+    }
+    impl Default for Params {
+        fn default() -> Self {
+            Params {
+                sight_range: 5.0,
+                cooldown: 10,
+            }
+        }
+    }
+    impl Config for Params {}
+
+    fn space() -> ParameterSpace {
+        let mut space = ParameterSpace::new();
+        space.add(
+            "sight_range",
+            Dimension::Range {
+                min: 0.0,
+                max: 1.0,
+                steps: 3,
+            },
+        );
+        space.add("cooldown", Dimension::Integers { min: 1, max: 2 });
+        space
+    }
+
+    #[test]
+    fn test_grid() {
+        let grid = space().grid();
+        assert_eq!(grid.len(), 6);
+        assert_eq!(
+            grid[1],
+            vec![
+                ("sight_range".to_string(), json!(0.0)),
+                ("cooldown".to_string(), json!(2)),
+            ]
+        );
+        assert_eq!(grid[5][0].1, json!(1.0));
+    }
+
+    #[test]
+    fn test_latin_hypercube_uses_every_stratum() {
+        let points = space().latin_hypercube(4, 7);
+        assert_eq!(points, space().latin_hypercube(4, 7));
+        let mut strata: Vec<usize> = points
+            .iter()
+            .map(|p| (p[0].1.as_f64().unwrap() * 4.0) as usize)
+            .collect();
+        strata.sort();
+        assert_eq!(strata, vec![0, 1, 2, 3]);
+        assert!(points
+            .iter()
+            .all(|p| p[1].1 == json!(1) || p[1].1 == json!(2)));
+
+        let random = space().random(5, 7);
+        assert_eq!(random.len(), 5);
+        assert!(random.iter().all(|p| p[0].1.as_f64().unwrap() < 1.0));
+    }
+
+    #[test]
+    fn test_run_collects_one_row_per_replicate() {
+        let mut space = ParameterSpace::new();
+        space.add_values("cooldown", &[3, 4]).unwrap();
+        let batch = Batch::new(space.grid(), 2, 100);
+        let results = batch
+            .run(|params: &Params, seed| {
+                vec![
+                    ("product".to_string(), params.cooldown as f64 * seed as f64),
+                    ("sight".to_string(), params.sight_range),
+                ]
+            })
+            .unwrap();
+
+        assert_eq!(results.parameters, vec!["cooldown"]);
+        assert_eq!(
+            results.metric("product").unwrap(),
+            vec![300.0, 303.0, 400.0, 404.0]
+        );
+        let mut csv = Vec::new();
+        results.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "cooldown,seed,product,sight\n3,100,300,5\n3,101,303,5\n4,100,400,5\n4,101,404,5\n"
+        );
+    }
+
+    #[test]
+    fn test_extreme_integers_and_seeds() {
+        let top = Dimension::Integers {
+            min: i64::MAX - 1,
+            max: i64::MAX,
+        };
+        assert_eq!(top.grid(), vec![json!(i64::MAX - 1), json!(i64::MAX)]);
+        let all = Dimension::Integers {
+            min: i64::MIN,
+            max: i64::MAX,
+        };
+        assert_eq!(all.at(0.0), json!(i64::MIN));
+        assert!(all.at(0.999).as_i64().unwrap() > i64::MAX / 2);
+        assert_eq!(top.at(0.99), json!(i64::MAX));
+
+        // seeds wrap around rather than overflow
+        let batch = Batch::new(vec![Vec::new()], 2, u64::MAX);
+        let results = batch
+            .run(|_: &Params, seed| vec![("low".to_string(), (seed % 2) as f64)])
+            .unwrap();
+        assert_eq!(results.metric("low").unwrap(), vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_bad_point_names_key() {
+        let batch = Batch::new(vec![vec![("cooldown".into(), json!("often"))]], 1, 0);
+        let error = batch.run(|_: &Params, _| Vec::new()).unwrap_err();
+        assert_eq!(error.key(), Some("cooldown"));
+    }
+}
@@ -0,0 +1,9 @@
+extern crate sekai;
+
+use sekai::registry::Registry;
+
+// runs the library's models. The firefly, ant and life examples run their
+// own the same way, e.g. `cargo run --example ant -- run ant`
+fn main() {
+    sekai::cli::main(&Registry::builtin());
+}
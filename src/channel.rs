@@ -0,0 +1,388 @@
+//! Imperfect message delivery, for studying how robust synchronization and
+//! stigmergy are to latency and loss.
+//!
+//! A `Channel` holds messages in flight until their delivery time. Each
+//! message may be dropped, duplicated or corrupted on the way, following
+//! its `ChannelParams`, and the channel counts what happened to them.
+//!
+//! ```rust
+//! # use sekai::channel::{Channel, ChannelParams, Delay};
+//! let params = ChannelParams {
+//!     delay: Delay::Fixed(2.0),
+//!     ..ChannelParams::default()
+//! };
+//! let mut channel = Channel::new(params, 0);
+//! channel.send("flash", 0.0);
+//! assert!(channel.advance(1.0).is_empty());
+//! assert_eq!(channel.advance(1.0), vec!["flash"]);
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use config::{Config, ConfigError};
+use event::EventQueue;
+use observer::Probe;
+
+/// How long messages take to arrive
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delay {
+    /// Every message takes the same time
+    Fixed(f64),
+    /// Messages take `base` plus `per_unit` for every unit of distance
+    /// between sender and receiver
+    Distance { base: f64, per_unit: f64 },
+    /// Messages take a time drawn uniformly between `min` and `max`
+    Uniform { min: f64, max: f64 },
+}
+
+/// How a channel delays and damages messages
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelParams {
+    pub delay: Delay,
+    /// Chance of a message being lost
+    pub drop: f64,
+    /// Chance of a message arriving twice, each copy delayed on its own
+    pub duplicate: f64,
+    /// Chance of a copy being passed to the corruption hook
+    pub corrupt: f64,
+}
+
+impl Default for ChannelParams {
+    /// A perfect channel, delivering every message on the next advance
+    fn default() -> Self {
+        ChannelParams {
+            delay: Delay::Fixed(0.0),
+            drop: 0.0,
+            duplicate: 0.0,
+            corrupt: 0.0,
+        }
+    }
+}
+
+impl Config for ChannelParams {
+    fn validate(&self) -> Result<(), ConfigError> {
+        let delays_valid = match self.delay {
+            Delay::Fixed(delay) => delay >= 0.0,
+            Delay::Distance { base, per_unit } => base >= 0.0 && per_unit >= 0.0,
+            Delay::Uniform { min, max } => min >= 0.0 && min <= max,
+        };
+        if !delays_valid {
+            return Err(ConfigError::invalid(
+                "delay",
+                "delays must not be negative, and min must not exceed max",
+            ));
+        }
+        let chances = [
+            ("drop", self.drop),
+            ("duplicate", self.duplicate),
+            ("corrupt", self.corrupt),
+        ];
+        for &(key, chance) in &chances {
+            if !(0.0..=1.0).contains(&chance) {
+                return Err(ConfigError::invalid(key, "must be between 0 and 1"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counts of what happened to the messages sent on a channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ChannelStats {
+    pub sent: u64,
+    pub delivered: u64,
+    pub dropped: u64,
+    /// Extra copies created
+    pub duplicated: u64,
+    /// Copies passed to the corruption hook
+    pub corrupted: u64,
+    /// Copies waiting to be delivered
+    pub in_flight: u64,
+}
+
+type Corrupt<M> = dyn Fn(&mut M, &mut StdRng) + Send + Sync;
+
+/// Messages in flight between entities
+/// # Arguments
+/// * `M` - The type of message carried
+#[derive(Clone)]
+pub struct Channel<M> {
+    params: ChannelParams,
+    queue: EventQueue<M>,
+    now: f64,
+    rng: StdRng,
+    corrupt: Option<Arc<Corrupt<M>>>,
+    stats: ChannelStats,
+}
+
+impl<M: Clone> Channel<M> {
+    /// Creates an empty channel at time 0
+    /// # Arguments
+    /// * `params` - How the channel delays and damages messages
+    /// * `seed` - Seeds the random delays and damage
+    pub fn new(params: ChannelParams, seed: u64) -> Self {
+        Channel {
+            params,
+            queue: EventQueue::new(),
+            now: 0.0,
+            rng: StdRng::from_seed(&[seed as usize][..]),
+            corrupt: None,
+            stats: ChannelStats::default(),
+        }
+    }
+
+    /// Sets how messages are corrupted, e.g. by adding noise to a value.
+    /// Without a hook, nothing is corrupted
+    /// # Arguments
+    /// * `corrupt` - Damages a message, with the channel's random numbers
+    pub fn set_corruption<F>(&mut self, corrupt: F)
+    where
+        F: Fn(&mut M, &mut StdRng) + Send + Sync + 'static,
+    {
+        self.corrupt = Some(Arc::new(corrupt));
+    }
+
+    /// Gets the current time of the channel
+    pub fn now(&self) -> f64 {
+        self.now
+    }
+
+    /// Gets how the channel delays and damages messages
+    pub fn params(&self) -> &ChannelParams {
+        &self.params
+    }
+
+    /// Gets the counts of what happened to messages so far
+    pub fn stats(&self) -> ChannelStats {
+        let mut stats = self.stats;
+        stats.in_flight = self.queue.len() as u64;
+        stats
+    }
+
+    /// Sends a message, which may be dropped, duplicated or corrupted
+    /// # Arguments
+    /// * `message` - The message
+    /// * `distance` - How far apart sender and receiver are, for delays
+    ///   which depend on it
+    pub fn send(&mut self, message: M, distance: f64) {
+        self.stats.sent += 1;
+        if self.chance(self.params.drop) {
+            self.stats.dropped += 1;
+            return;
+        }
+        if self.chance(self.params.duplicate) {
+            self.stats.duplicated += 1;
+            self.dispatch(message.clone(), distance);
+        }
+        self.dispatch(message, distance);
+    }
+
+    /// Moves time forward, returning the messages which arrived by then in
+    /// the order they arrived
+    /// # Arguments
+    /// * `time` - How far to move, e.g. 1 tick
+    pub fn advance(&mut self, time: f64) -> Vec<M> {
+        let until = self.now + time;
+        self.advance_to(until)
+    }
+
+    /// Moves time forward to a time, returning the messages which arrived
+    /// by then in the order they arrived
+    /// # Arguments
+    /// * `time` - The new time, no earlier than now
+    pub fn advance_to(&mut self, time: f64) -> Vec<M> {
+        let mut delivered = Vec::new();
+        while self.queue.next_time().is_some_and(|next| next <= time) {
+            delivered.extend(self.queue.pop().map(|(_, message)| message));
+        }
+        self.now = self.now.max(time);
+        self.stats.delivered += delivered.len() as u64;
+        delivered
+    }
+
+    /// Delays one copy of a message, corrupting it maybe
+    fn dispatch(&mut self, mut message: M, distance: f64) {
+        if let Some(corrupt) = self.corrupt.clone() {
+            if self.chance(self.params.corrupt) {
+                corrupt(&mut message, &mut self.rng);
+                self.stats.corrupted += 1;
+            }
+        }
+        let delay = match self.params.delay {
+            Delay::Fixed(delay) => delay,
+            Delay::Distance { base, per_unit } => base + per_unit * distance,
+            Delay::Uniform { min, max } if max > min => self.rng.gen_range(min, max),
+            Delay::Uniform { min, .. } => min,
+        };
+        self.queue.schedule(self.now + delay, message);
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.rng.gen::<f64>() < probability
+    }
+}
+
+impl<M> fmt::Debug for Channel<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Channel")
+            .field("params", &self.params)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+/// Probe reporting the statistics of a channel in a world, with the columns
+/// `<name>_sent`, `<name>_delivered`, `<name>_dropped`,
+/// `<name>_duplicated`, `<name>_corrupted` and `<name>_in_flight`
+pub struct ChannelProbe<F> {
+    name: String,
+    stats: F,
+}
+
+impl<F> ChannelProbe<F> {
+    /// Creates a probe
+    /// # Arguments
+    /// * `name` - Prefix of the column names, e.g. the channel's name
+    /// * `stats` - Gets the statistics of the channel from the world
+    pub fn new<S: Into<String>>(name: S, stats: F) -> Self {
+        ChannelProbe {
+            name: name.into(),
+            stats,
+        }
+    }
+}
+
+impl<W: ?Sized, F: FnMut(&W) -> ChannelStats> Probe<W> for ChannelProbe<F> {
+    fn columns(&self) -> Vec<String> {
+        [
+            "sent",
+            "delivered",
+            "dropped",
+            "duplicated",
+            "corrupted",
+            "in_flight",
+        ]
+        .iter()
+        .map(|column| format!("{}_{}", self.name, column))
+        .collect()
+    }
+    fn measure(&mut self, world: &W) -> Vec<f64> {
+        let stats = (self.stats)(world);
+        vec![
+            stats.sent as f64,
+            stats.delivered as f64,
+            stats.dropped as f64,
+            stats.duplicated as f64,
+            stats.corrupted as f64,
+            stats.in_flight as f64,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config;
+
+    fn channel(delay: Delay) -> Channel<u32> {
+        let params = ChannelParams {
+            delay,
+            ..ChannelParams::default()
+        };
+        Channel::new(params, 1)
+    }
+
+    #[test]
+    fn test_delays() {
+        let mut fixed = channel(Delay::Fixed(0.0));
+        fixed.send(1, 10.0);
+        fixed.send(2, 0.0);
+        assert_eq!(fixed.advance(1.0), vec![1, 2]);
+
+        let mut distance = channel(Delay::Distance {
+            base: 0.5,
+            per_unit: 1.0,
+        });
+        distance.send(1, 3.0);
+        distance.send(2, 1.0);
+        assert_eq!(distance.advance(2.0), vec![2]);
+        assert_eq!(distance.stats().in_flight, 1);
+        assert_eq!(distance.advance_to(3.5), vec![1]);
+        assert_eq!(distance.now(), 3.5);
+
+        let mut uniform = channel(Delay::Uniform { min: 2.0, max: 4.0 });
+        for message in 0..100 {
+            uniform.send(message, 0.0);
+        }
+        assert!(uniform.advance(2.0 - 1e-9).is_empty());
+        assert_eq!(uniform.advance(2.0).len(), 100);
+    }
+
+    #[test]
+    fn test_losses() {
+        let params = ChannelParams {
+            drop: 0.25,
+            duplicate: 0.5,
+            corrupt: 1.0,
+            ..ChannelParams::default()
+        };
+        let mut lossy = Channel::new(params, 5);
+        lossy.set_corruption(|message: &mut u32, _rng: &mut StdRng| *message += 100);
+        for message in 0..4000 {
+            lossy.send(message, 0.0);
+        }
+        let delivered = lossy.advance(1.0);
+        let stats = lossy.stats();
+        assert!((stats.dropped as f64 - 1000.0).abs() < 100.0);
+        assert!((stats.duplicated as f64 - 1500.0).abs() < 150.0);
+        assert_eq!(
+            stats.delivered,
+            stats.sent - stats.dropped + stats.duplicated
+        );
+        assert_eq!(stats.corrupted, stats.delivered);
+        assert!(delivered.iter().all(|&message| message >= 100));
+
+        let mut probe = ChannelProbe::new("flash", |channel: &Channel<u32>| channel.stats());
+        assert_eq!(probe.columns()[5], "flash_in_flight");
+        assert_eq!(probe.measure(&lossy)[0], 4000.0);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut params = ChannelParams {
+            delay: Delay::Uniform { min: 3.0, max: 1.0 },
+            ..ChannelParams::default()
+        };
+        assert_eq!(params.validate().unwrap_err().key(), Some("delay"));
+        params.delay = Delay::Fixed(1.0);
+        params.drop = 1.5;
+        let error = params.validate().unwrap_err().within("channel");
+        assert_eq!(error.key(), Some("channel.drop"));
+    }
+
+    #[test]
+    fn test_delays_from_toml() {
+        let delay = |text: &str| config::from_toml_str::<ChannelParams>(text).map(|p| p.delay);
+        assert_eq!(delay("[delay]\nfixed = 2.0").unwrap(), Delay::Fixed(2.0));
+        assert_eq!(
+            delay("[delay.distance]\nbase = 0.5\nper_unit = 2.0").unwrap(),
+            Delay::Distance {
+                base: 0.5,
+                per_unit: 2.0
+            }
+        );
+        assert_eq!(
+            delay("[delay.uniform]\nmin = 1.0\nmax = 3.0").unwrap(),
+            Delay::Uniform { min: 1.0, max: 3.0 }
+        );
+        let error = delay("[delay.gamma]\nshape = 2.0").unwrap_err();
+        assert_eq!(error.key(), Some("delay.gamma.shape"));
+        let error = delay("[delay.uniform]\nmin = 3.0\nmax = 1.0").unwrap_err();
+        assert_eq!(error.key(), Some("delay"));
+    }
+}
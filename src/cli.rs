@@ -0,0 +1,792 @@
+//! The `sekai` command line: running models by name, sweeping their
+//! parameters, and replaying or rendering recorded trajectories.
+//!
+//! ```text
+//! sekai run <model> [--config FILE] [--set KEY=VALUE]... [--seed N] [--ticks N] [--out DIR]
+//! sekai sweep <model> --sweep FILE [--config FILE] [--seed N] [--ticks N] [--out DIR]
+//! sekai replay <trajectory> [--out FILE]
+//! sekai render <trajectory> --out PATH [--format gif|png|ppm|svg] [--size N] [--color-by FIELD] [--delay MS]
+//! sekai models
+//! sekai describe <model>
+//! ```
+//!
+//! A sweep file names the parameters to vary and how to sample them:
+//!
+//! ```toml
+//! sampling = "latin_hypercube"  # or "grid" (the default) or "random"
+//! samples = 20                  # points drawn by "random" and "latin_hypercube"
+//! replicates = 3                # runs of every point, with seeds seed, seed + 1, ...
+//!
+//! [parameters]
+//! lifetime = [20, 50, 80]                               # exactly these values
+//! flash_cooldown = { min = 5, max = 15 }                # every integer in between
+//! sight_range = { min = 1.0, max = 10.0, steps = 4 }    # evenly spaced reals
+//! ```
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use serde_json::{self, Map, Value};
+use toml;
+
+use batch::{Batch, Dimension, ParameterSpace};
+use config::{self, Config, ConfigError};
+use entity::Observable;
+use observer::{EntityCount, Metrics, Observer, ScalarStats};
+use output::{CsvWriter, RunMetadata};
+use registry::{Model, Registry, RunOptions};
+use render::{colormap, Bounds, FrameRenderer, GifEncoder, GifRenderer, ImageFormat, Rgb, Scene};
+use replay::{RecordedEntity, RecordedWorld, Replay};
+use trajectory::Trajectory;
+
+const USAGE: &str = "usage:
+    sekai run <model> [--config FILE] [--set KEY=VALUE]... [--seed N] [--ticks N] [--out DIR]
+    sekai sweep <model> --sweep FILE [--config FILE] [--seed N] [--ticks N] [--out DIR]
+    sekai replay <trajectory> [--out FILE]
+    sekai render <trajectory> --out PATH [--format gif|png|ppm|svg] [--size N] [--color-by FIELD] [--delay MS]
+    sekai models
+    sekai describe <model>";
+
+/// Why a command failed
+#[derive(Debug)]
+pub enum CliError {
+    /// The command line itself is wrong
+    Usage(String),
+    /// A config or sweep file is wrong
+    Config(ConfigError),
+    Io(io::Error),
+    /// The model failed while running
+    Model(String),
+}
+
+impl CliError {
+    /// Gets the exit code for the error: 2 for usage errors, else 1
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            CliError::Usage(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CliError::Usage(ref message) => write!(f, "{}\n\n{}", message, USAGE),
+            CliError::Config(ref e) => write!(f, "config error: {}", e),
+            CliError::Io(ref e) => write!(f, "{}", e),
+            CliError::Model(ref message) => write!(f, "model error: {}", message),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+impl From<ConfigError> for CliError {
+    fn from(e: ConfigError) -> Self {
+        CliError::Config(e)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        CliError::Io(e)
+    }
+}
+
+/// Runs the command line with the process arguments, exiting with a
+/// message and a non-zero code if the command fails
+/// # Arguments
+/// * `registry` - The models which can be run by name
+pub fn main(registry: &Registry) {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = execute(&args, registry, &mut io::stdout()) {
+        eprintln!("sekai: {}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+/// Runs one command
+/// # Arguments
+/// * `args` - The arguments, without the program name
+/// * `registry` - The models which can be run by name
+/// * `out` - Where to print results meant for the user
+pub fn execute<W: Write>(
+    args: &[String],
+    registry: &Registry,
+    out: &mut W,
+) -> Result<(), CliError> {
+    let command = match args.first() {
+        Some(command) => command.as_str(),
+        None => return Err(CliError::Usage("no command given".into())),
+    };
+    let rest = &args[1..];
+    match command {
+        "run" => run(
+            &Args::new(rest, &["config", "set", "seed", "ticks", "out"])?,
+            registry,
+            out,
+        ),
+        "sweep" => sweep(
+            &Args::new(rest, &["sweep", "config", "seed", "ticks", "out"])?,
+            registry,
+            out,
+        ),
+        "replay" => replay(&Args::new(rest, &["out"])?, out),
+        "render" => render(
+            &Args::new(rest, &["out", "format", "size", "color-by", "delay"])?,
+            out,
+        ),
+        "models" => {
+            Args::new(rest, &[])?.positionals(0)?;
+            for model in registry.models() {
+                match model.description() {
+                    "" => writeln!(out, "{}", model.name())?,
+                    description => writeln!(out, "{} - {}", model.name(), description)?,
+                }
+            }
+            Ok(())
+        }
+        "describe" => describe(&Args::new(rest, &[])?, registry, out),
+        "help" | "--help" | "-h" => {
+            writeln!(out, "{}", USAGE)?;
+            Ok(())
+        }
+        _ => Err(CliError::Usage(format!("unknown command `{}`", command))),
+    }
+}
+
+fn describe<W: Write>(args: &Args, registry: &Registry, out: &mut W) -> Result<(), CliError> {
+    let positionals = args.positionals(1)?;
+    let model = find_model(registry, &positionals[0])?;
+    writeln!(out, "{}", model.name())?;
+    if !model.description().is_empty() {
+        writeln!(out, "{}", model.description())?;
+    }
+    writeln!(out, "\nparameters:")?;
+    let parameters =
+        toml::to_string_pretty(&model.parameters()).map_err(|e| CliError::Model(e.to_string()))?;
+    for line in parameters.lines() {
+        writeln!(out, "    {}", line)?;
+    }
+    writeln!(out, "\nmetrics:")?;
+    for metric in model.metrics() {
+        writeln!(out, "    {}", metric)?;
+    }
+    Ok(())
+}
+
+fn run<W: Write>(args: &Args, registry: &Registry, out: &mut W) -> Result<(), CliError> {
+    let positionals = args.positionals(1)?;
+    let model = find_model(registry, &positionals[0])?;
+    let mut overrides = base_overrides(args)?;
+    for assignment in args.all("set") {
+        let (key, value) = match assignment.find('=') {
+            Some(i) => (&assignment[..i], &assignment[i + 1..]),
+            None => {
+                return Err(CliError::Usage(format!(
+                    "`--set {}` must look like KEY=VALUE",
+                    assignment
+                )))
+            }
+        };
+        // values which are not valid JSON, like bare words, are strings
+        let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.into()));
+        config::set_key(&mut overrides, key, value);
+    }
+    let parameters = model.resolve(overrides)?;
+    let options = RunOptions {
+        seed: args.parse("seed", 0)?,
+        ticks: args.parse("ticks", 100)?,
+        out: args.get("out").map(PathBuf::from),
+    };
+    if let Some(ref dir) = options.out {
+        fs::create_dir_all(dir)?;
+        config::save(&parameters, dir.join("config.toml"))?;
+    }
+
+    let metrics = model
+        .run(&parameters, &options)
+        .map_err(|e| CliError::Model(e.to_string()))?;
+    for (name, value) in metrics {
+        writeln!(out, "{} = {}", name, value)?;
+    }
+    Ok(())
+}
+
+fn sweep<W: Write>(args: &Args, registry: &Registry, out: &mut W) -> Result<(), CliError> {
+    let positionals = args.positionals(1)?;
+    let model = find_model(registry, &positionals[0])?;
+    let spec_path = args
+        .get("sweep")
+        .ok_or_else(|| CliError::Usage("`sweep` needs a sweep file given with `--sweep`".into()))?;
+    let spec: SweepSpec = config::load(spec_path)?;
+    let base = base_overrides(args)?;
+    // the base config alone must be valid before any point is tried
+    model.resolve(base.clone())?;
+
+    let seed = args.parse("seed", 0)?;
+    let points = match spec.sampling.as_str() {
+        "grid" => spec.space()?.grid(),
+        "random" => spec.space()?.random(spec.samples, seed),
+        _ => spec.space()?.latin_hypercube(spec.samples, seed),
+    };
+    let batch = Batch::new(points, spec.replicates, seed);
+    let ticks = args.parse("ticks", 100)?;
+    let error = Mutex::new(None);
+    let results = batch.run_with(
+        |point| {
+            let mut overrides = base.clone();
+            merge_overrides(&mut overrides, point);
+            model.resolve(overrides)
+        },
+        |parameters, seed| {
+            let options = RunOptions {
+                seed,
+                ticks,
+                out: None,
+            };
+            model.run(parameters, &options).unwrap_or_else(|e| {
+                error
+                    .lock()
+                    .unwrap()
+                    .get_or_insert(CliError::Model(e.to_string()));
+                Vec::new()
+            })
+        },
+    )?;
+    if let Some(e) = error.into_inner().unwrap() {
+        return Err(e);
+    }
+
+    match args.get("out") {
+        Some(dir) => {
+            let dir = Path::new(dir);
+            fs::create_dir_all(dir)?;
+            config::save(&model.resolve(base)?, dir.join("config.toml"))?;
+            config::save(&spec, dir.join("sweep.toml"))?;
+            results.write_csv(BufWriter::new(File::create(dir.join("sweep.csv"))?))?;
+            writeln!(
+                out,
+                "{} runs written to {}",
+                results.rows.len(),
+                dir.join("sweep.csv").display()
+            )?;
+        }
+        None => results.write_csv(out)?,
+    }
+    Ok(())
+}
+
+fn replay<W: Write>(args: &Args, out: &mut W) -> Result<(), CliError> {
+    let positionals = args.positionals(1)?;
+    let replay = Replay::open(&positionals[0])?;
+
+    // summarize every recorded field of the entities, tick by tick
+    let mut metrics = Metrics::new();
+    metrics.add_probe(EntityCount);
+    for field in replay.fields() {
+        let name = field.clone();
+        metrics.add_probe(ScalarStats::new(
+            field.clone(),
+            move |entity: &RecordedEntity| entity.field(&name).unwrap_or(f64::NAN),
+        ));
+    }
+    let metadata = RunMetadata::new(positionals[0].clone());
+    match args.get("out") {
+        Some(path) => metrics.add_sink(CsvWriter::create(path, metadata)?),
+        None => metrics.add_sink(CsvWriter::new(io::stdout(), metadata)),
+    }
+    replay.run(&mut [&mut metrics]);
+    metrics.finish()?;
+    if let Some(path) = args.get("out") {
+        writeln!(out, "{} ticks written to {}", replay.len(), path)?;
+    }
+    Ok(())
+}
+
+fn render<W: Write>(args: &Args, out: &mut W) -> Result<(), CliError> {
+    let positionals = args.positionals(1)?;
+    let path = args
+        .get("out")
+        .ok_or_else(|| CliError::Usage("`render` needs an output given with `--out`".into()))?;
+    let size: u32 = args.parse("size", 400)?;
+    let trajectory = Trajectory::open(&positionals[0])?;
+
+    // color by a field scaled over the whole run, so colors are comparable
+    // between frames
+    let color_by = match args.get("color-by") {
+        Some(field) => match trajectory.field_index(field) {
+            Some(index) => {
+                let values = trajectory
+                    .frames
+                    .iter()
+                    .flat_map(|frame| frame.entities.iter().map(move |e| e.fields[index]))
+                    .filter(|v| !v.is_nan());
+                let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                    (lo.min(v), hi.max(v))
+                });
+                Some((field.to_string(), min, max))
+            }
+            None => {
+                return Err(CliError::Usage(format!(
+                    "the trajectory has no field `{}`; it has {:?}",
+                    field, trajectory.fields
+                )))
+            }
+        },
+        None => None,
+    };
+    let replay = Replay::new(trajectory);
+    let bounds = replay.frames().fold(None, |bounds: Option<Bounds>, world| {
+        let frame = Bounds::around(world, 0.0);
+        Some(match bounds {
+            Some(b) => Bounds::new(
+                b.min_x.min(frame.min_x),
+                b.min_y.min(frame.min_y),
+                b.max_x.max(frame.max_x),
+                b.max_y.max(frame.max_y),
+            ),
+            None => frame,
+        })
+    });
+    let bounds = bounds.map_or(Bounds::new(-1.0, -1.0, 1.0, 1.0), |b| {
+        let margin = 0.05 * b.width().max(b.height()).max(1.0);
+        Bounds::new(
+            b.min_x - margin,
+            b.min_y - margin,
+            b.max_x + margin,
+            b.max_y + margin,
+        )
+    });
+    let draw = |world: &RecordedWorld| {
+        let mut scene = Scene::new(size, size, bounds);
+        scene.add_points(world, 3.0, |entity: &RecordedEntity| match color_by {
+            Some((ref field, min, max)) => match entity.field(field) {
+                Some(value) if max > min => colormap((value - min) / (max - min)),
+                Some(_) => colormap(0.5),
+                None => Rgb::new(128, 128, 128),
+            },
+            None => Rgb::WHITE,
+        });
+        scene
+    };
+
+    let format = args
+        .get("format")
+        .unwrap_or(if path.ends_with(".gif") { "gif" } else { "png" });
+    let format = match format {
+        "gif" => None,
+        "png" => Some(ImageFormat::Png),
+        "ppm" => Some(ImageFormat::Ppm),
+        "svg" => Some(ImageFormat::Svg),
+        other => {
+            return Err(CliError::Usage(format!(
+                "unknown format `{}`; use gif, png, ppm or svg",
+                other
+            )))
+        }
+    };
+    match format {
+        None => {
+            let encoder = GifEncoder::create(path, size, size, args.parse("delay", 100)?)?;
+            let mut renderer = GifRenderer::new(encoder, draw);
+            replay.run(&mut [&mut renderer as &mut dyn Observer<RecordedWorld>]);
+            renderer.finish()?;
+        }
+        Some(format) => {
+            let mut renderer = FrameRenderer::new(path, format, draw)?;
+            replay.run(&mut [&mut renderer as &mut dyn Observer<RecordedWorld>]);
+            renderer.finish()?;
+        }
+    }
+    writeln!(out, "{} frames written to {}", replay.len(), path)?;
+    Ok(())
+}
+
+fn find_model<'a>(registry: &'a Registry, name: &str) -> Result<&'a dyn Model, CliError> {
+    let names = registry.names();
+    match registry.get(name) {
+        Some(model) => Ok(model),
+        None if names.is_empty() => Err(CliError::Usage(format!(
+            "unknown model `{}`: no models are registered",
+            name
+        ))),
+        None => Err(CliError::Usage(format!(
+            "unknown model `{}`; available models are {}",
+            name,
+            names.join(", ")
+        ))),
+    }
+}
+
+/// Reads the overrides given with `--config`, if any
+fn base_overrides(args: &Args) -> Result<Value, CliError> {
+    match args.get("config") {
+        Some(path) => {
+            let path = Path::new(path);
+            let text =
+                fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+            Ok(config::Format::of(path)?.parse(&text)?)
+        }
+        None => Ok(Value::Object(Map::new())),
+    }
+}
+
+/// Copies every leaf of the overrides onto the target
+fn merge_overrides(target: &mut Value, overrides: Value) {
+    match overrides {
+        Value::Object(object) => {
+            for (key, value) in object {
+                match target.get_mut(key.as_str()) {
+                    Some(existing) if existing.is_object() && value.is_object() => {
+                        merge_overrides(existing, value);
+                    }
+                    _ => config::set_key(target, &key, value),
+                }
+            }
+        }
+        value => *target = value,
+    }
+}
+
+/// The arguments of one command: positionals in order and `--name value`
+/// or `--name=value` options
+struct Args {
+    positionals: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    fn new(args: &[String], allowed: &[&str]) -> Result<Args, CliError> {
+        let mut parsed = Args {
+            positionals: Vec::new(),
+            options: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                parsed.positionals.push(arg.clone());
+                continue;
+            }
+            let (name, value) = match arg.find('=') {
+                Some(i) => (arg[2..i].to_string(), Some(arg[i + 1..].to_string())),
+                None => (arg[2..].to_string(), None),
+            };
+            if !allowed.contains(&name.as_str()) {
+                return Err(CliError::Usage(format!("unknown option `--{}`", name)));
+            }
+            let value = match value.or_else(|| args.next().cloned()) {
+                Some(value) => value,
+                None => return Err(CliError::Usage(format!("`--{}` needs a value", name))),
+            };
+            parsed.options.push((name, value));
+        }
+        Ok(parsed)
+    }
+
+    /// Gets exactly `n` positionals
+    fn positionals(&self, n: usize) -> Result<&[String], CliError> {
+        if self.positionals.len() == n {
+            Ok(&self.positionals)
+        } else {
+            Err(CliError::Usage(format!(
+                "expected {} argument(s) but got {}",
+                n,
+                self.positionals.len()
+            )))
+        }
+    }
+
+    /// Gets the last value of an option
+    fn get(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|o| o.0 == name)
+            .map(|o| o.1.as_str())
+    }
+
+    /// Gets every value of an option which may be repeated
+    fn all(&self, name: &str) -> Vec<&str> {
+        self.options
+            .iter()
+            .filter(|o| o.0 == name)
+            .map(|o| o.1.as_str())
+            .collect()
+    }
+
+    /// Parses the value of an option, or gives a default if it is missing
+    fn parse<T: FromStr>(&self, name: &str, default: T) -> Result<T, CliError> {
+        match self.get(name) {
+            Some(value) => value.parse().map_err(|_| {
+                CliError::Usage(format!("`--{}` has the invalid value `{}`", name, value))
+            }),
+            None => Ok(default),
+        }
+    }
+}
+
+/// A sweep file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct SweepSpec {
+    sampling: String,
+    samples: usize,
+    replicates: usize,
+    parameters: Map<String, Value>,
+}
+
+impl Default for SweepSpec {
+    fn default() -> Self {
+        SweepSpec {
+            sampling: "grid".into(),
+            samples: 10,
+            replicates: 1,
+            parameters: Map::new(),
+        }
+    }
+}
+
+impl Config for SweepSpec {
+    fn validate(&self) -> Result<(), ConfigError> {
+        match self.sampling.as_str() {
+            "grid" | "random" | "latin_hypercube" => {}
+            _ => {
+                return Err(ConfigError::invalid(
+                    "sampling",
+                    "must be \"grid\", \"random\" or \"latin_hypercube\"",
+                ))
+            }
+        }
+        if self.replicates == 0 {
+            return Err(ConfigError::invalid("replicates", "must be at least 1"));
+        }
+        self.space().map(|_| ())
+    }
+}
+
+impl SweepSpec {
+    /// Builds the parameter space, naming the parameter whose description
+    /// is wrong
+    fn space(&self) -> Result<ParameterSpace, ConfigError> {
+        let mut space = ParameterSpace::new();
+        for (key, value) in &self.parameters {
+            let invalid =
+                |message: &str| ConfigError::invalid(format!("parameters.{}", key), message);
+            let dimension = match *value {
+                Value::Array(ref values) if !values.is_empty() => Dimension::Values(values.clone()),
+                Value::Object(ref range) => {
+                    let (min, max) = match (range.get("min"), range.get("max")) {
+                        (Some(min), Some(max)) => (min, max),
+                        _ => return Err(invalid("a range needs both `min` and `max`")),
+                    };
+                    let steps = range.get("steps").and_then(Value::as_u64);
+                    match (min.as_i64(), max.as_i64(), steps) {
+                        (Some(min), Some(max), None) if min <= max => {
+                            Dimension::Integers { min, max }
+                        }
+                        _ => match (min.as_f64(), max.as_f64()) {
+                            (Some(min), Some(max)) if min <= max => Dimension::Range {
+                                min,
+                                max,
+                                steps: match steps {
+                                    Some(steps) => steps as usize,
+                                    None if self.sampling == "grid" => {
+                                        return Err(invalid("a real range needs `steps` in a grid"))
+                                    }
+                                    None => 0,
+                                },
+                            },
+                            _ => {
+                                return Err(invalid(
+                                    "`min` and `max` must be numbers with min <= max",
+                                ))
+                            }
+                        },
+                    }
+                }
+                _ => {
+                    return Err(invalid(
+                        "must be a list of values or a table with `min` and `max`",
+                    ))
+                }
+            };
+            space.add(key.clone(), dimension);
+        }
+        Ok(space)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use registry::RunResult;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Params {
+        rate: f64,
+    }
+    impl Default for Params {
+        fn default() -> Self {
+            Params { rate: 1.0 }
+        }
+    }
+    impl Config for Params {
+        fn validate(&self) -> Result<(), ConfigError> {
+            if self.rate < 0.0 {
+                return Err(ConfigError::invalid("rate", "must not be negative"));
+            }
+            Ok(())
+        }
+    }
+
+    /// Grows linearly, reporting its size after the last tick
+    struct Growth;
+    impl Model for Growth {
+        fn name(&self) -> &str {
+            "growth"
+        }
+        fn parameters(&self) -> Value {
+            config::to_value(&Params::default()).unwrap()
+        }
+        fn metrics(&self) -> Vec<String> {
+            vec!["size".into()]
+        }
+        fn resolve(&self, overrides: Value) -> Result<Value, ConfigError> {
+            config::to_value(&config::resolve::<Params>(overrides)?)
+        }
+        fn run(&self, parameters: &Value, options: &RunOptions) -> RunResult {
+            let params: Params = serde_json::from_value(parameters.clone())?;
+            let size = params.rate * options.ticks as f64 + options.seed as f64;
+            Ok(vec![("size".into(), size)])
+        }
+    }
+
+    fn execute_args(args: &[&str]) -> Result<String, CliError> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let mut registry = Registry::new();
+        registry.add(Growth);
+        let mut out = Vec::new();
+        execute(&args, &registry, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = env::temp_dir().join("sekai_test_cli_run");
+        let dir_arg = dir.to_str().unwrap();
+        let out = execute_args(&[
+            "run",
+            "growth",
+            "--set",
+            "rate=2",
+            "--ticks=10",
+            "--seed",
+            "1",
+            "--out",
+            dir_arg,
+        ])
+        .unwrap();
+        assert_eq!(out, "size = 21\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("config.toml")).unwrap(),
+            "rate = 2.0\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let usage = execute_args(&["run", "decay"]).unwrap_err();
+        assert_eq!(usage.exit_code(), 2);
+        assert!(usage
+            .to_string()
+            .starts_with("unknown model `decay`; available models are growth"));
+        assert!(execute_args(&["run", "growth", "--speed", "2"]).is_err());
+
+        match execute_args(&["run", "growth", "--set", "rate=-1"]).unwrap_err() {
+            CliError::Config(e) => assert_eq!(e.key(), Some("rate")),
+            e => panic!("expected a config error, got {}", e),
+        }
+    }
+
+    #[test]
+    fn test_sweep() {
+        let spec = env::temp_dir().join("sekai_test_cli_sweep.toml");
+        fs::write(&spec, "replicates = 2\n[parameters]\nrate = [1, 3]\n").unwrap();
+        let out = execute_args(&[
+            "sweep",
+            "growth",
+            "--sweep",
+            spec.to_str().unwrap(),
+            "--ticks",
+            "10",
+        ])
+        .unwrap();
+        assert_eq!(out, "rate,seed,size\n1,0,10\n1,1,11\n3,0,30\n3,1,31\n");
+
+        fs::write(&spec, "[parameters]\nrate = { min = 0.5, max = 1.0 }\n").unwrap();
+        match execute_args(&["sweep", "growth", "--sweep", spec.to_str().unwrap()]) {
+            Err(CliError::Config(e)) => assert_eq!(e.key(), Some("parameters.rate")),
+            other => panic!("expected a config error, got {:?}", other),
+        }
+
+        // random sampling needs no steps to draw from a real range
+        fs::write(
+            &spec,
+            "sampling = \"random\"\nsamples = 3\n[parameters]\nrate = { min = 0.5, max = 1.0 }\n",
+        )
+        .unwrap();
+        let out = execute_args(&["sweep", "growth", "--sweep", spec.to_str().unwrap()]).unwrap();
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(rows[0], "rate,seed,size");
+        assert_eq!(rows.len(), 4);
+        fs::remove_file(&spec).unwrap();
+    }
+
+    #[test]
+    fn test_render_to_gif() {
+        use trajectory::{EntityState, Frame};
+
+        let path = env::temp_dir().join("sekai_test_cli_render.trj");
+        let gif = env::temp_dir().join("sekai_test_cli_render.gif");
+        let trajectory = Trajectory {
+            fields: vec!["energy".into()],
+            frames: (1..4)
+                .map(|tick| Frame {
+                    tick,
+                    entities: vec![EntityState {
+                        id: 0,
+                        position: vec![tick as f32, 0.0],
+                        fields: vec![tick as f64],
+                    }],
+                })
+                .collect(),
+        };
+        trajectory.write(File::create(&path).unwrap()).unwrap();
+        let out = execute_args(&[
+            "render",
+            path.to_str().unwrap(),
+            "--out",
+            gif.to_str().unwrap(),
+            "--size",
+            "16",
+            "--color-by",
+            "energy",
+        ])
+        .unwrap();
+        assert!(out.starts_with("3 frames written to"));
+        assert!(fs::read(&gif).unwrap().starts_with(b"GIF89a"));
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&gif).unwrap();
+    }
+}
@@ -0,0 +1,420 @@
+//! Finding groups of agents from their positions, e.g. whether fireflies
+//! gather into swarms.
+//!
+//! Distances are Euclidean over every coordinate two positions share.
+//! Clustering compares every pair of agents, so it is quadratic in the
+//! number of agents.
+
+use entity::Observable;
+use observer::{Observer, Probe};
+use world::WorldView;
+
+/// How agents are grouped into clusters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    /// DBSCAN: agents with at least `min_points` agents (themselves
+    /// included) within `eps` are cores, clusters are cores linked within
+    /// `eps` plus the agents they reach, and everything else is noise
+    Dbscan { eps: f64, min_points: usize },
+    /// Agents closer than `threshold` are linked, and every set of linked
+    /// agents is a cluster, so there is no noise
+    Components { threshold: f64 },
+}
+
+impl Method {
+    /// Labels every position with its cluster, or `None` for noise.
+    /// Clusters are numbered from 0 in order of their first member
+    /// # Arguments
+    /// * `positions` - The positions of the agents
+    pub fn label<P: AsRef<[f32]>>(&self, positions: &[P]) -> Vec<Option<usize>> {
+        match *self {
+            Method::Dbscan { eps, min_points } => dbscan(positions, eps, min_points),
+            Method::Components { threshold } => connected_components(positions, threshold)
+                .into_iter()
+                .map(Some)
+                .collect(),
+        }
+    }
+
+    /// Finds the clusters among the positions
+    /// # Arguments
+    /// * `positions` - The positions of the agents
+    pub fn clusters<P: AsRef<[f32]>>(&self, positions: &[P]) -> Vec<Cluster> {
+        clusters(positions, &self.label(positions))
+    }
+}
+
+/// A group of agents
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    /// Indices of the member agents among the clustered positions
+    pub members: Vec<usize>,
+    /// Mean position of the members
+    pub centroid: Vec<f64>,
+    /// Root mean square distance of the members from the centroid
+    pub radius_of_gyration: f64,
+}
+
+impl Cluster {
+    /// Gets the number of member agents
+    pub fn size(&self) -> usize {
+        self.members.len()
+    }
+}
+
+/// Labels positions with DBSCAN, returning `None` for noise
+/// # Arguments
+/// * `positions` - The positions of the agents
+/// * `eps` - How close agents must be to be neighbors
+/// * `min_points` - How many neighbors, itself included, make an agent a
+///   core of a cluster
+pub fn dbscan<P: AsRef<[f32]>>(positions: &[P], eps: f64, min_points: usize) -> Vec<Option<usize>> {
+    let neighbors = neighbors(positions, eps);
+    let mut labels = vec![None; positions.len()];
+    let mut visited = vec![false; positions.len()];
+    let mut next_label = 0;
+    for start in 0..positions.len() {
+        if visited[start] || neighbors[start].len() + 1 < min_points {
+            continue;
+        }
+        // grow a new cluster from this core
+        let mut frontier = vec![start];
+        visited[start] = true;
+        while let Some(i) = frontier.pop() {
+            labels[i] = Some(next_label);
+            if neighbors[i].len() + 1 < min_points {
+                continue;
+            }
+            for &j in &neighbors[i] {
+                if !visited[j] {
+                    visited[j] = true;
+                    frontier.push(j);
+                } else if labels[j].is_none() {
+                    // a border point first seen from a core elsewhere
+                    labels[j] = Some(next_label);
+                }
+            }
+        }
+        next_label += 1;
+    }
+    labels
+}
+
+/// Labels positions by connected component, linking agents closer than a
+/// threshold
+/// # Arguments
+/// * `positions` - The positions of the agents
+/// * `threshold` - How close agents must be to be linked
+pub fn connected_components<P: AsRef<[f32]>>(positions: &[P], threshold: f64) -> Vec<usize> {
+    let neighbors = neighbors(positions, threshold);
+    let mut labels = vec![usize::MAX; positions.len()];
+    let mut next_label = 0;
+    for start in 0..positions.len() {
+        if labels[start] != usize::MAX {
+            continue;
+        }
+        labels[start] = next_label;
+        let mut frontier = vec![start];
+        while let Some(i) = frontier.pop() {
+            for &j in &neighbors[i] {
+                if labels[j] == usize::MAX {
+                    labels[j] = next_label;
+                    frontier.push(j);
+                }
+            }
+        }
+        next_label += 1;
+    }
+    labels
+}
+
+/// Groups labelled positions into clusters, in order of label
+/// # Arguments
+/// * `positions` - The positions of the agents
+/// * `labels` - The cluster of every position, or `None` for noise
+pub fn clusters<P: AsRef<[f32]>>(positions: &[P], labels: &[Option<usize>]) -> Vec<Cluster> {
+    let count = labels.iter().filter_map(|&l| l).max().map_or(0, |l| l + 1);
+    let mut members = vec![Vec::new(); count];
+    for (i, label) in labels.iter().enumerate() {
+        if let Some(label) = *label {
+            members[label].push(i);
+        }
+    }
+    members
+        .into_iter()
+        .filter(|m| !m.is_empty())
+        .map(|members| {
+            let dims = members
+                .iter()
+                .map(|&i| positions[i].as_ref().len())
+                .min()
+                .unwrap_or(0);
+            let mut centroid = vec![0.0; dims];
+            for &i in &members {
+                for (c, &x) in centroid.iter_mut().zip(positions[i].as_ref()) {
+                    *c += x as f64;
+                }
+            }
+            for c in &mut centroid {
+                *c /= members.len() as f64;
+            }
+            let spread: f64 = members
+                .iter()
+                .map(|&i| squared_distance_to(positions[i].as_ref(), &centroid))
+                .sum();
+            Cluster {
+                radius_of_gyration: (spread / members.len() as f64).sqrt(),
+                centroid,
+                members,
+            }
+        })
+        .collect()
+}
+
+/// Probe summarizing the clusters of a world's entities every tick, with
+/// the columns `<name>_count`, `<name>_largest`, `<name>_mean_size`,
+/// `<name>_noise` and `<name>_gyration`, the mean radius of gyration of the
+/// clusters. Means are NaN when there are no clusters
+pub struct ClusterProbe {
+    name: String,
+    method: Method,
+}
+
+impl ClusterProbe {
+    /// Creates a probe
+    /// # Arguments
+    /// * `name` - Prefix of the column names
+    /// * `method` - How entities are grouped
+    pub fn new<S: Into<String>>(name: S, method: Method) -> Self {
+        ClusterProbe {
+            name: name.into(),
+            method,
+        }
+    }
+}
+
+impl<W> Probe<W> for ClusterProbe
+where
+    W: WorldView + ?Sized,
+    W::Entity: Observable,
+{
+    fn columns(&self) -> Vec<String> {
+        ["count", "largest", "mean_size", "noise", "gyration"]
+            .iter()
+            .map(|column| format!("{}_{}", self.name, column))
+            .collect()
+    }
+    fn measure(&mut self, world: &W) -> Vec<f64> {
+        let positions: Vec<&[f32]> = world.entities().iter().map(|e| e.position()).collect();
+        let clusters = self.method.clusters(&positions);
+        let clustered: usize = clusters.iter().map(Cluster::size).sum();
+        let count = clusters.len() as f64;
+        vec![
+            count,
+            clusters.iter().map(Cluster::size).max().unwrap_or(0) as f64,
+            if clusters.is_empty() {
+                f64::NAN
+            } else {
+                clustered as f64 / count
+            },
+            (positions.len() - clustered) as f64,
+            if clusters.is_empty() {
+                f64::NAN
+            } else {
+                clusters.iter().map(|c| c.radius_of_gyration).sum::<f64>() / count
+            },
+        ]
+    }
+}
+
+/// The clusters found at one tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterFrame {
+    pub tick: u64,
+    /// Ids of the entities, so `ids[member]` identifies a cluster member
+    pub ids: Vec<u64>,
+    pub clusters: Vec<Cluster>,
+}
+
+/// Observer keeping every cluster found at every tick, for when the summary
+/// of a `ClusterProbe` is not enough, e.g. to follow centroids over time
+pub struct ClusterHistory {
+    method: Method,
+    frames: Vec<ClusterFrame>,
+}
+
+impl ClusterHistory {
+    /// Creates an empty history
+    /// # Arguments
+    /// * `method` - How entities are grouped
+    pub fn new(method: Method) -> Self {
+        ClusterHistory {
+            method,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Gets the clusters of every observed tick
+    pub fn frames(&self) -> &[ClusterFrame] {
+        &self.frames
+    }
+}
+
+impl<W> Observer<W> for ClusterHistory
+where
+    W: WorldView + ?Sized,
+    W::Entity: Observable,
+{
+    fn observe(&mut self, tick: u64, world: &W) {
+        let entities = world.entities();
+        let positions: Vec<&[f32]> = entities.iter().map(|e| e.position()).collect();
+        self.frames.push(ClusterFrame {
+            tick,
+            ids: entities.iter().map(|e| e.id()).collect(),
+            clusters: self.method.clusters(&positions),
+        });
+    }
+}
+
+/// Lists, for every position, the other positions within a distance
+fn neighbors<P: AsRef<[f32]>>(positions: &[P], distance: f64) -> Vec<Vec<usize>> {
+    let limit = distance * distance;
+    let mut neighbors = vec![Vec::new(); positions.len()];
+    for i in 0..positions.len() {
+        for j in i + 1..positions.len() {
+            if squared_distance(positions[i].as_ref(), positions[j].as_ref()) <= limit {
+                neighbors[i].push(j);
+                neighbors[j].push(i);
+            }
+        }
+    }
+    neighbors
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(&x, &y)| (x as f64 - y as f64).powi(2))
+        .sum()
+}
+
+fn squared_distance_to(a: &[f32], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(&x, &y)| (x as f64 - y).powi(2)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two groups of three, and one straggler far away
+    fn positions() -> Vec<Vec<f32>> {
+        vec![
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![0.0, 1.0],
+            vec![10.0, 10.0],
+            vec![11.0, 10.0],
+            vec![10.0, 11.0],
+            vec![50.0, 50.0],
+        ]
+    }
+
+    #[test]
+    fn test_dbscan_and_components() {
+        let method = Method::Dbscan {
+            eps: 1.5,
+            min_points: 3,
+        };
+        assert_eq!(
+            method.label(&positions()),
+            vec![Some(0), Some(0), Some(0), Some(1), Some(1), Some(1), None]
+        );
+        let components = Method::Components { threshold: 1.5 };
+        assert_eq!(
+            components.label(&positions()),
+            vec![
+                Some(0),
+                Some(0),
+                Some(0),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(2)
+            ]
+        );
+        // a chain links through its middle point but DBSCAN needs cores
+        let chain = vec![vec![0.0f32], vec![1.0], vec![2.0]];
+        assert_eq!(connected_components(&chain, 1.0), vec![0, 0, 0]);
+        assert_eq!(dbscan(&chain, 1.0, 3), vec![Some(0), Some(0), Some(0)]);
+        assert_eq!(dbscan(&chain, 1.0, 4), vec![None, None, None]);
+    }
+
+    #[test]
+    fn test_cluster_shape() {
+        let clusters = Method::Components { threshold: 1.5 }.clusters(&positions());
+        assert_eq!(clusters.len(), 3);
+        assert_eq!(clusters[1].members, vec![3, 4, 5]);
+        assert_eq!(clusters[1].centroid, vec![31.0 / 3.0, 31.0 / 3.0]);
+        assert_eq!(clusters[2].radius_of_gyration, 0.0);
+        // the points of the first group are sqrt(2)/3, sqrt(5)/3 and sqrt(5)/3
+        // from the centroid, so the mean squared distance is 4/9
+        let expected = 2.0 / 3.0;
+        assert!((clusters[0].radius_of_gyration - expected).abs() < 1e-12);
+    }
+
+    struct Agent(u64, Vec<f32>);
+    impl Observable for Agent {
+        fn id(&self) -> u64 {
+            self.0
+        }
+        fn position(&self) -> &[f32] {
+            &self.1
+        }
+    }
+    struct Swarm(Vec<Agent>);
+    impl WorldView for Swarm {
+        type Entity = Agent;
+        fn entities(&self) -> &[Agent] {
+            &self.0
+        }
+    }
+
+    #[test]
+    fn test_probe_and_history() {
+        let swarm = Swarm(
+            positions()
+                .into_iter()
+                .enumerate()
+                .map(|(i, p)| Agent(100 + i as u64, p))
+                .collect(),
+        );
+        let method = Method::Dbscan {
+            eps: 1.5,
+            min_points: 3,
+        };
+        let mut probe = ClusterProbe::new("swarms", method);
+        assert_eq!(
+            Probe::<Swarm>::columns(&probe),
+            vec![
+                "swarms_count",
+                "swarms_largest",
+                "swarms_mean_size",
+                "swarms_noise",
+                "swarms_gyration"
+            ]
+        );
+        let row = probe.measure(&swarm);
+        assert_eq!(row[..4].to_vec(), vec![2.0, 3.0, 3.0, 1.0]);
+
+        let mut history = ClusterHistory::new(method);
+        history.observe(4, &swarm);
+        let frame = &history.frames()[0];
+        assert_eq!(frame.tick, 4);
+        let ids: Vec<u64> = frame.clusters[1]
+            .members
+            .iter()
+            .map(|&m| frame.ids[m])
+            .collect();
+        assert_eq!(ids, vec![103, 104, 105]);
+    }
+}
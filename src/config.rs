@@ -0,0 +1,470 @@
+//! Loading model parameters from TOML or JSON files.
+//!
+//! A model declares its parameters as a struct implementing `Config`. Every
+//! key missing from a file keeps its default value, keys the struct does not
+//! have are rejected, and every error names the offending key, e.g.
+//! `dynamics.flash_rate`.
+//!
+//! ```rust
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate sekai;
+//! # use sekai::config::{self, Config, ConfigError};
+//! #[derive(Debug, Serialize, Deserialize)]
+//! #[serde(default)]
+//! struct Params {
+//!     sight_range: f32,
+//!     lifetime: u32,
+//! }
+//! impl Default for Params {
+//!     fn default() -> Self {
+//!         Params { sight_range: 5.0, lifetime: 50 }
+//!     }
+//! }
+//! impl Config for Params {
+//!     fn validate(&self) -> Result<(), ConfigError> {
+//!         if self.sight_range <= 0.0 {
+//!             return Err(ConfigError::invalid("sight_range", "must be positive"));
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//! # fn main() {
+//! let params: Params = config::from_toml_str("lifetime = 80").unwrap();
+//! assert_eq!(params.lifetime, 80);
+//! assert_eq!(params.sight_range, 5.0);
+//!
+//! let error = config::from_toml_str::<Params>("sight_range = -1.0").unwrap_err();
+//! assert_eq!(error.key(), Some("sight_range"));
+//! # }
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+use toml;
+
+/// Parameters of a model which can be loaded from a file
+pub trait Config: Serialize + DeserializeOwned + Default {
+    /// Checks that the parameters make sense together. Errors should be
+    /// made with `ConfigError::invalid` so they name the offending key
+    fn validate(&self) -> Result<(), ConfigError> {
+        Ok(())
+    }
+}
+
+/// Why a config could not be loaded or saved
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read or written
+    Io(PathBuf, io::Error),
+    /// The file extension is neither `.toml` nor `.json`
+    UnknownFormat(PathBuf),
+    /// The file is not valid TOML or JSON
+    Syntax(String),
+    /// The file has a key the parameters do not
+    UnknownKey(String),
+    /// A key has a value of the wrong type or which failed validation
+    Invalid { key: String, message: String },
+}
+
+impl ConfigError {
+    /// Creates an error for a value which failed validation
+    /// # Arguments
+    /// * `key` - The offending key, with nested keys separated by `.`
+    /// * `message` - What is wrong with the value
+    pub fn invalid<K: Into<String>, M: Into<String>>(key: K, message: M) -> Self {
+        ConfigError::Invalid {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Gets the offending key, if the error is about one
+    pub fn key(&self) -> Option<&str> {
+        match *self {
+            ConfigError::UnknownKey(ref key) | ConfigError::Invalid { ref key, .. } => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Moves the offending key, if any, under a parent key, e.g. to report
+    /// errors from validating nested parameters
+    /// # Arguments
+    /// * `parent` - The key the nested parameters are under
+    pub fn within(self, parent: &str) -> Self {
+        match self {
+            ConfigError::UnknownKey(key) => ConfigError::UnknownKey(format!("{}.{}", parent, key)),
+            ConfigError::Invalid { key, message } => ConfigError::Invalid {
+                key: format!("{}.{}", parent, key),
+                message,
+            },
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref path, ref e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::UnknownFormat(ref path) => write!(
+                f,
+                "{}: config files must end in .toml or .json",
+                path.display()
+            ),
+            ConfigError::Syntax(ref message) => write!(f, "syntax error: {}", message),
+            ConfigError::UnknownKey(ref key) => write!(f, "unknown key `{}`", key),
+            ConfigError::Invalid {
+                ref key,
+                ref message,
+            } => write!(f, "invalid value for `{}`: {}", key, message),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// File formats configs can be read from and written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// Picks the format from a file extension
+    /// # Arguments
+    /// * `path` - A path ending in `.toml` or `.json`
+    pub fn of<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Ok(Format::Toml),
+            Some("json") => Ok(Format::Json),
+            _ => Err(ConfigError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Parses text in this format into a JSON value
+    /// # Arguments
+    /// * `text` - The text to parse
+    pub fn parse(self, text: &str) -> Result<Value, ConfigError> {
+        match self {
+            Format::Toml => {
+                let value: toml::Value =
+                    toml::from_str(text).map_err(|e| ConfigError::Syntax(e.to_string()))?;
+                serde_json::to_value(value).map_err(|e| ConfigError::Syntax(e.to_string()))
+            }
+            Format::Json => {
+                serde_json::from_str(text).map_err(|e| ConfigError::Syntax(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Loads parameters from a `.toml` or `.json` file
+/// # Arguments
+/// * `path` - The config file
+pub fn load<C: Config, P: AsRef<Path>>(path: P) -> Result<C, ConfigError> {
+    let path = path.as_ref();
+    let format = Format::of(path)?;
+    let text = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+    resolve(format.parse(&text)?)
+}
+
+/// Loads parameters from TOML text
+/// # Arguments
+/// * `text` - The TOML document
+pub fn from_toml_str<C: Config>(text: &str) -> Result<C, ConfigError> {
+    resolve(Format::Toml.parse(text)?)
+}
+
+/// Loads parameters from JSON text
+/// # Arguments
+/// * `text` - The JSON document
+pub fn from_json_str<C: Config>(text: &str) -> Result<C, ConfigError> {
+    resolve(Format::Json.parse(text)?)
+}
+
+/// Builds parameters by applying overrides to the defaults, then validates
+/// them
+/// # Arguments
+/// * `overrides` - A JSON object of the keys to change
+pub fn resolve<C: Config>(overrides: Value) -> Result<C, ConfigError> {
+    let defaults = to_value(&C::default())?;
+    let mut merged = defaults.clone();
+    let mut switched = Vec::new();
+    merge(&mut merged, &overrides, "", &mut switched)?;
+    let config = match serde_json::from_value::<C>(merged) {
+        Ok(config) => config,
+        Err(e) => {
+            return Err(find_invalid_key::<C>(&defaults, &overrides)
+                .unwrap_or_else(|| ConfigError::Syntax(e.to_string())))
+        }
+    };
+    // a variant the parameters do not have may have been read as a struct
+    // with every field left default, so check it survived
+    let resolved = to_value(&config)?;
+    for key in switched {
+        if resolved
+            .pointer(&format!("/{}", key.replace('.', "/")))
+            .is_none()
+        {
+            return Err(ConfigError::UnknownKey(key));
+        }
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+/// Converts parameters to a JSON value, e.g. to record them with results
+/// # Arguments
+/// * `config` - The parameters
+pub fn to_value<C: Serialize>(config: &C) -> Result<Value, ConfigError> {
+    serde_json::to_value(config).map_err(|e| ConfigError::Syntax(e.to_string()))
+}
+
+/// Writes parameters to a `.toml` or `.json` file, so the config a run
+/// actually used can be kept alongside its results
+/// # Arguments
+/// * `config` - The parameters, usually after loading and resolving
+/// * `path` - Where to write them
+pub fn save<C: Serialize, P: AsRef<Path>>(config: &C, path: P) -> Result<(), ConfigError> {
+    let path = path.as_ref();
+    let text = match Format::of(path)? {
+        Format::Toml => toml::Value::try_from(config)
+            .and_then(|value| toml::to_string_pretty(&value))
+            .map_err(|e| ConfigError::Syntax(e.to_string()))?,
+        Format::Json => {
+            serde_json::to_string_pretty(config).map_err(|e| ConfigError::Syntax(e.to_string()))?
+        }
+    };
+    fs::write(path, text).map_err(|e| ConfigError::Io(path.to_path_buf(), e))
+}
+
+/// Sets a key within a JSON object, creating nested objects as needed
+/// # Arguments
+/// * `target` - The object to change
+/// * `key` - The key, with nested keys separated by `.`
+/// * `value` - The new value
+pub fn set_key(target: &mut Value, key: &str, value: Value) {
+    let mut current = target;
+    let mut parts = key.split('.').peekable();
+    while let Some(part) = parts.next() {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        let object = current.as_object_mut().expect("just made an object");
+        if parts.peek().is_none() {
+            object.insert(part.into(), value);
+            return;
+        }
+        current = object
+            .entry(part)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+/// Recursively copies the overrides onto the defaults, rejecting keys the
+/// defaults do not have. An enum variant holding data is a table of one
+/// key, so a table of one other key replaces it as a different variant,
+/// and its key is added to `switched` to be checked once deserialized
+fn merge(
+    target: &mut Value,
+    overrides: &Value,
+    prefix: &str,
+    switched: &mut Vec<String>,
+) -> Result<(), ConfigError> {
+    let overrides = match *overrides {
+        Value::Object(ref overrides) => overrides,
+        _ if prefix.is_empty() => {
+            return Err(ConfigError::Syntax(
+                "a config must be a table of keys".into(),
+            ))
+        }
+        ref value => {
+            *target = value.clone();
+            return Ok(());
+        }
+    };
+    for (key, value) in overrides {
+        let path = join(prefix, key);
+        match target.get_mut(key.as_str()) {
+            // nested tables are merged key by key, except maps which are
+            // empty by default and so take any keys
+            Some(existing) => {
+                let nested = existing.as_object().is_some_and(|o| !o.is_empty());
+                if nested && is_other_variant(existing, value) {
+                    let variant = value.as_object().and_then(|o| o.keys().next());
+                    switched.extend(variant.map(|variant| join(&path, variant)));
+                    *existing = value.clone();
+                } else if nested && value.is_object() {
+                    merge(existing, value, &path, switched)?;
+                } else {
+                    *existing = value.clone();
+                }
+            }
+            None => return Err(ConfigError::UnknownKey(path)),
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether two tables each have one key, and not the same one, as
+/// two variants of an externally tagged enum do
+fn is_other_variant(existing: &Value, value: &Value) -> bool {
+    match (existing.as_object(), value.as_object()) {
+        (Some(existing), Some(value)) => {
+            existing.len() == 1 && value.len() == 1 && existing.keys().ne(value.keys())
+        }
+        _ => false,
+    }
+}
+
+/// Applies the overrides one leaf at a time to find the first key whose
+/// value the parameters cannot hold
+fn find_invalid_key<C: Config>(defaults: &Value, overrides: &Value) -> Option<ConfigError> {
+    let mut leaves = Vec::new();
+    collect_leaves(overrides, "", &mut leaves);
+    for (key, value) in leaves {
+        let mut single = defaults.clone();
+        set_key(&mut single, &key, value.clone());
+        if let Err(e) = serde_json::from_value::<C>(single) {
+            return Some(ConfigError::invalid(key, e.to_string()));
+        }
+    }
+    None
+}
+
+fn collect_leaves<'a>(value: &'a Value, prefix: &str, leaves: &mut Vec<(String, &'a Value)>) {
+    match *value {
+        Value::Object(ref object) if !object.is_empty() => {
+            for (key, value) in object {
+                collect_leaves(value, &join(prefix, key), leaves);
+            }
+        }
+        _ => leaves.push((prefix.into(), value)),
+    }
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.into()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Dynamics {
+        flash_rate: u32,
+        spread: f64,
+    }
+    impl Default for Dynamics {
+        fn default() -> Self {
+            Dynamics {
+                flash_rate: 1,
+                spread: 100.0,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Params {
+        sight_range: f32,
+        name: String,
+        dynamics: Dynamics,
+    }
+    impl Default for Params {
+        fn default() -> Self {
+            Params {
+                sight_range: 5.0,
+                name: "swarm".into(),
+                dynamics: Dynamics::default(),
+            }
+        }
+    }
+    impl Config for Params {
+        fn validate(&self) -> Result<(), ConfigError> {
+            if self.dynamics.flash_rate == 0 {
+                return Err(ConfigError::invalid(
+                    "dynamics.flash_rate",
+                    "must be at least 1",
+                ));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_missing_keys_keep_defaults() {
+        let params: Params = from_toml_str("sight_range = 2.5\n[dynamics]\nspread = 3.0").unwrap();
+        assert_eq!(params.sight_range, 2.5);
+        assert_eq!(params.name, "swarm");
+        assert_eq!(params.dynamics.flash_rate, 1);
+        assert_eq!(params.dynamics.spread, 3.0);
+
+        let json: Params = from_json_str(r#"{"dynamics": {"flash_rate": 4}}"#).unwrap();
+        assert_eq!(json.dynamics.flash_rate, 4);
+        assert_eq!(json.sight_range, 5.0);
+    }
+
+    #[test]
+    fn test_errors_name_the_key() {
+        let unknown = from_toml_str::<Params>("[dynamics]\nflash_rat = 2").unwrap_err();
+        assert_eq!(unknown.key(), Some("dynamics.flash_rat"));
+        assert_eq!(unknown.to_string(), "unknown key `dynamics.flash_rat`");
+
+        let wrong_type =
+            from_json_str::<Params>(r#"{"name": "a", "dynamics": {"spread": "wide"}}"#)
+                .unwrap_err();
+        assert_eq!(wrong_type.key(), Some("dynamics.spread"));
+
+        let invalid = from_toml_str::<Params>("[dynamics]\nflash_rate = 0").unwrap_err();
+        assert_eq!(invalid.key(), Some("dynamics.flash_rate"));
+
+        assert!(from_toml_str::<Params>("sight_range = ")
+            .unwrap_err()
+            .key()
+            .is_none());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = ::std::env::temp_dir();
+        let mut params = Params::default();
+        params.dynamics.spread = 7.0;
+        for name in &[
+            "sekai_test_save_and_load.toml",
+            "sekai_test_save_and_load.json",
+        ] {
+            let path = dir.join(name);
+            save(&params, &path).unwrap();
+            assert_eq!(load::<Params, _>(&path).unwrap(), params);
+            fs::remove_file(&path).unwrap();
+        }
+        match save(&params, dir.join("params.yaml")) {
+            Err(ConfigError::UnknownFormat(_)) => {}
+            other => panic!("expected an unknown format, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_set_key() {
+        let mut value = json!({ "a": 1 });
+        set_key(&mut value, "b.c", json!(2));
+        set_key(&mut value, "a", json!(3));
+        assert_eq!(value, json!({ "a": 3, "b": { "c": 2 } }));
+    }
+}
@@ -0,0 +1,284 @@
+//! Detecting when a run has nothing more to show: the world has settled
+//! into a fixed point or a cycle, or its population has died out.
+//!
+//! Detectors are observers, so they can watch a run passively, or be given
+//! to `Simulation::run_until` to stop the run as soon as one fires.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use entity::Observable;
+use observer::Observer;
+use world::WorldView;
+
+/// Why a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// The run went for all the ticks it was given
+    TickLimit { tick: u64 },
+    /// The world stopped changing at `tick - 1`, after `transient` ticks
+    FixedPoint { tick: u64, transient: u64 },
+    /// The world repeats every `period` ticks, after `transient` ticks
+    Cycle {
+        tick: u64,
+        period: u64,
+        transient: u64,
+    },
+    /// The population died out
+    Extinction { tick: u64 },
+}
+
+impl Termination {
+    /// Gets the tick at which the run ended
+    pub fn tick(&self) -> u64 {
+        match *self {
+            Termination::TickLimit { tick }
+            | Termination::FixedPoint { tick, .. }
+            | Termination::Cycle { tick, .. }
+            | Termination::Extinction { tick } => tick,
+        }
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Termination::TickLimit { tick } => write!(f, "reached the tick limit of {}", tick),
+            Termination::FixedPoint { transient, .. } => {
+                write!(f, "fixed point after {} ticks", transient)
+            }
+            Termination::Cycle {
+                period, transient, ..
+            } => write!(f, "cycle of period {} after {} ticks", period, transient),
+            Termination::Extinction { tick } => write!(f, "extinction at tick {}", tick),
+        }
+    }
+}
+
+/// An observer that can tell when a run should end
+pub trait Detector<W: ?Sized>: Observer<W> {
+    /// Gets what has been detected so far, if anything
+    fn detected(&self) -> Option<Termination>;
+}
+
+/// Hashes any hashable state, for use by a `CycleDetector`
+/// # Arguments
+/// * `state` - The state to hash
+pub fn hash_state<T: Hash + ?Sized>(state: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hashes the ids and positions of a world's entities
+/// # Arguments
+/// * `world` - The world to hash
+pub fn hash_entities<W>(world: &W) -> u64
+where
+    W: WorldView + ?Sized,
+    W::Entity: Observable,
+{
+    let mut hasher = DefaultHasher::new();
+    for entity in world.entities() {
+        entity.id().hash(&mut hasher);
+        for x in entity.position() {
+            x.to_bits().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// Finds fixed points and cycles by remembering a hash of the world at
+/// every observed tick, and reporting the first hash seen twice. Two
+/// different states with the same hash would be mistaken for a repeat,
+/// which is unlikely but possible with 64 bit hashes
+pub struct CycleDetector<F> {
+    hash: F,
+    seen: HashMap<u64, u64>,
+    latest: Option<u64>,
+    detected: Option<Termination>,
+}
+
+impl<F> CycleDetector<F> {
+    /// Creates a detector
+    /// # Arguments
+    /// * `hash` - Hashes the state of a world, e.g. with `hash_state`
+    pub fn new(hash: F) -> Self {
+        CycleDetector {
+            hash,
+            seen: HashMap::new(),
+            latest: None,
+            detected: None,
+        }
+    }
+}
+
+impl<W> CycleDetector<fn(&W) -> u64>
+where
+    W: WorldView,
+    W::Entity: Observable,
+{
+    /// Creates a detector comparing the ids and positions of entities
+    pub fn entities() -> Self {
+        CycleDetector::new(hash_entities::<W> as fn(&W) -> u64)
+    }
+}
+
+impl<W: ?Sized, F: FnMut(&W) -> u64> Observer<W> for CycleDetector<F> {
+    fn observe(&mut self, tick: u64, world: &W) {
+        if self.detected.is_some() {
+            return;
+        }
+        // a run which went back, e.g. to a restored state, forgets the
+        // states it had from then on
+        if self.latest.is_some_and(|latest| tick <= latest) {
+            self.seen.retain(|_, &mut seen_at| seen_at < tick);
+        }
+        self.latest = Some(tick);
+        let hash = (self.hash)(world);
+        if let Some(&first) = self.seen.get(&hash) {
+            let period = tick - first;
+            self.detected = Some(if period == 1 {
+                Termination::FixedPoint {
+                    tick,
+                    transient: first,
+                }
+            } else {
+                Termination::Cycle {
+                    tick,
+                    period,
+                    transient: first,
+                }
+            });
+        } else {
+            self.seen.insert(hash, tick);
+        }
+    }
+}
+
+impl<W: ?Sized, F: FnMut(&W) -> u64> Detector<W> for CycleDetector<F> {
+    fn detected(&self) -> Option<Termination> {
+        self.detected
+    }
+}
+
+/// Reports extinction the first time a population count reaches zero
+pub struct ExtinctionDetector<F> {
+    population: F,
+    detected: Option<Termination>,
+}
+
+impl<F> ExtinctionDetector<F> {
+    /// Creates a detector
+    /// # Arguments
+    /// * `population` - Counts the living members of a world, e.g. the
+    ///   fireflies whose lifetime has not run out
+    pub fn new(population: F) -> Self {
+        ExtinctionDetector {
+            population,
+            detected: None,
+        }
+    }
+}
+
+impl<W: WorldView> ExtinctionDetector<fn(&W) -> usize> {
+    /// Creates a detector counting every entity of a world
+    pub fn entities() -> Self {
+        ExtinctionDetector::new(count_entities::<W> as fn(&W) -> usize)
+    }
+}
+
+impl<W: ?Sized, F: FnMut(&W) -> usize> Observer<W> for ExtinctionDetector<F> {
+    fn observe(&mut self, tick: u64, world: &W) {
+        if self.detected.is_none() && (self.population)(world) == 0 {
+            self.detected = Some(Termination::Extinction { tick });
+        }
+    }
+}
+
+impl<W: ?Sized, F: FnMut(&W) -> usize> Detector<W> for ExtinctionDetector<F> {
+    fn detected(&self) -> Option<Termination> {
+        self.detected
+    }
+}
+
+fn count_entities<W: WorldView>(world: &W) -> usize {
+    world.entities().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycles_and_fixed_points() {
+        // 5, 6, 7, 8, 6, 7, 8, ... enters a cycle of 3 at tick 1
+        let states = [5, 6, 7, 8, 6, 7, 8];
+        let mut cycle = CycleDetector::new(|state: &i32| hash_state(state));
+        for (tick, state) in states.iter().enumerate() {
+            cycle.observe(tick as u64, state);
+        }
+        assert_eq!(
+            cycle.detected(),
+            Some(Termination::Cycle {
+                tick: 4,
+                period: 3,
+                transient: 1
+            })
+        );
+
+        let mut fixed = CycleDetector::new(|state: &i32| hash_state(state));
+        for (tick, state) in [3, 2, 1, 1].iter().enumerate() {
+            fixed.observe(tick as u64, state);
+        }
+        let detected = fixed.detected().unwrap();
+        assert_eq!(
+            detected,
+            Termination::FixedPoint {
+                tick: 3,
+                transient: 2
+            }
+        );
+        assert_eq!(detected.to_string(), "fixed point after 2 ticks");
+
+        // observing a tick again, after going back to it, is no repeat
+        let mut rerun = CycleDetector::new(|state: &i32| hash_state(state));
+        for &(tick, state) in &[(0, 5), (1, 6), (2, 7), (1, 6), (2, 8), (3, 6)] {
+            rerun.observe(tick, &state);
+            if tick < 3 {
+                assert_eq!(rerun.detected(), None);
+            }
+        }
+        assert_eq!(
+            rerun.detected(),
+            Some(Termination::Cycle {
+                tick: 3,
+                period: 2,
+                transient: 1
+            })
+        );
+    }
+
+    struct Colony(Vec<u32>);
+    impl WorldView for Colony {
+        type Entity = u32;
+        fn entities(&self) -> &[u32] {
+            &self.0
+        }
+    }
+
+    #[test]
+    fn test_extinction() {
+        let mut extinction = ExtinctionDetector::entities();
+        extinction.observe(1, &Colony(vec![1, 2]));
+        assert_eq!(extinction.detected(), None);
+        extinction.observe(2, &Colony(vec![]));
+        extinction.observe(3, &Colony(vec![]));
+        assert_eq!(
+            extinction.detected(),
+            Some(Termination::Extinction { tick: 2 })
+        );
+    }
+}
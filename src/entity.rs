@@ -5,33 +5,31 @@ use world::World;
 /// * `M` - The type to use for messages between entities
 /// # Example
 /// ```rust
+/// # use sekai::entity::Entity;
+/// # use sekai::world::World;
 /// struct Cat {
 ///     hunger: i64,
 /// }
 /// impl Entity<String> for Cat {
 ///     /// Every tick, the cat gets more hungry
-///     /// If the cat gets too hungry, it meows
-///     fn update(&mut self, world: &World) {
+///     fn update(&mut self, _world: &dyn World<String>) {
 ///         self.hunger += 1;
-///         if self.hunger >= 100 {
-///             self.send_message("MEOW".into());
-///         }
 ///     }
 ///     /// Cats ignore any incoming messages
-///     fn receive_message(&mut  self, message: T) {}
+///     fn receive_message(&mut self, _message: String) {}
 /// }
 /// ```
 pub trait Entity<M> {
     /// Updates the entity based on the world around it
     /// # Arguments
     /// * `world` - the world the entity exists in
-    fn update(&mut self, world: &World<M>);
+    fn update(&mut self, world: &dyn World<M>);
     /// Sends a message to the world. The world will choose which entities to
     /// forward the message to
     /// # Arguments
     /// * `message` - The message being sent to all other entities
     /// * `world` - The world the entity exists in
-    fn send_message(&self, message: M, world: &mut World<M>) {
+    fn send_message(&self, message: M, world: &mut dyn World<M>) {
         world.receive_message(message)
     }
     /// Handler for receiving a message
@@ -39,3 +37,17 @@ pub trait Entity<M> {
     /// * `message` - The message to receive
     fn receive_message(&mut self, message: M);
 }
+
+/// Exposes the state of an entity to observers and recorders
+pub trait Observable {
+    /// Gets an identifier which stays the same for the entity's whole life
+    fn id(&self) -> u64;
+    /// Gets the position of the entity, empty if it has none
+    fn position(&self) -> &[f32];
+    /// Gets a named scalar field of the entity, if it has one
+    /// # Arguments
+    /// * `name` - The name of the field
+    fn field(&self, _name: &str) -> Option<f64> {
+        None
+    }
+}
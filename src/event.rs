@@ -0,0 +1,322 @@
+//! Discrete-event simulation, an alternative to updating every entity
+//! every tick for worlds where little happens most of the time.
+//!
+//! Events are kept in an `EventQueue` keyed by continuous time. Handling an
+//! event may schedule more events, e.g. a firefly flashing schedules its
+//! next flash. Events are handled in time order, and events at the same
+//! time in the order they were scheduled, so runs repeat exactly.
+//!
+//! ```rust
+//! # use sekai::event::{EventQueue, EventSimulation, EventWorld};
+//! // a lamp that blinks every 2.5 time units
+//! struct Lamp {
+//!     blinks: u32,
+//! }
+//! impl EventWorld for Lamp {
+//!     type Event = ();
+//!     fn handle(&mut self, _event: (), queue: &mut EventQueue<()>) {
+//!         self.blinks += 1;
+//!         queue.schedule_in(2.5, ());
+//!     }
+//! }
+//!
+//! let mut sim = EventSimulation::new(Lamp { blinks: 0 });
+//! sim.queue_mut().schedule(0.0, ());
+//! sim.run(10.0, 1.0, &mut []);
+//! // blinks at 0, 2.5, 5, 7.5 and 10
+//! assert_eq!(sim.world().blinks, 5);
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use observer::Observer;
+
+/// Events waiting to happen, in time order
+#[derive(Debug, Clone)]
+pub struct EventQueue<E> {
+    heap: BinaryHeap<Pending<E>>,
+    now: f64,
+    scheduled: u64,
+}
+
+impl<E> EventQueue<E> {
+    /// Creates an empty queue at time 0
+    pub fn new() -> Self {
+        EventQueue {
+            heap: BinaryHeap::new(),
+            now: 0.0,
+            scheduled: 0,
+        }
+    }
+
+    /// Gets the time of the last event taken from the queue
+    pub fn now(&self) -> f64 {
+        self.now
+    }
+
+    /// Schedules an event at a time
+    /// # Arguments
+    /// * `time` - When the event happens, no earlier than now
+    /// * `event` - The event
+    /// # Panics
+    /// If the time is before now, or NaN
+    pub fn schedule(&mut self, time: f64, event: E) {
+        assert!(
+            time >= self.now,
+            "cannot schedule an event at {} before the current time {}",
+            time,
+            self.now
+        );
+        self.heap.push(Pending {
+            time,
+            order: self.scheduled,
+            event,
+        });
+        self.scheduled += 1;
+    }
+
+    /// Schedules an event some time from now
+    /// # Arguments
+    /// * `delay` - How long from now the event happens, not negative
+    /// * `event` - The event
+    pub fn schedule_in(&mut self, delay: f64, event: E) {
+        let time = self.now + delay;
+        self.schedule(time, event);
+    }
+
+    /// Gets the time of the next event, if any
+    pub fn next_time(&self) -> Option<f64> {
+        self.heap.peek().map(|pending| pending.time)
+    }
+
+    /// Takes the next event, moving the current time to it
+    pub fn pop(&mut self) -> Option<(f64, E)> {
+        self.heap.pop().map(|pending| {
+            self.now = pending.time;
+            (pending.time, pending.event)
+        })
+    }
+
+    /// Gets the number of events waiting
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Checks whether no events are waiting
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+impl<E> Default for EventQueue<E> {
+    fn default() -> Self {
+        EventQueue::new()
+    }
+}
+
+/// An event in the queue, ordered so the heap pops the earliest first, and
+/// the first scheduled among those at the same time
+#[derive(Debug, Clone)]
+struct Pending<E> {
+    time: f64,
+    order: u64,
+    event: E,
+}
+
+impl<E> Ord for Pending<E> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .time
+            .partial_cmp(&self.time)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+impl<E> PartialOrd for Pending<E> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<E> PartialEq for Pending<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<E> Eq for Pending<E> {}
+
+/// A world which changes only when events happen
+pub trait EventWorld {
+    /// The type of event happening in the world
+    type Event;
+    /// Handles an event, scheduling any events it causes. The queue's
+    /// current time is the time of the event
+    /// # Arguments
+    /// * `event` - The event happening
+    /// * `queue` - The queue to schedule further events in
+    fn handle(&mut self, event: Self::Event, queue: &mut EventQueue<Self::Event>);
+}
+
+/// Drives an event world forward by handling its events in time order
+/// # Arguments
+/// * `W` - The type of world being simulated
+pub struct EventSimulation<W: EventWorld> {
+    world: W,
+    queue: EventQueue<W::Event>,
+    handled: u64,
+    samples: u64,
+}
+
+impl<W: EventWorld> EventSimulation<W> {
+    /// Creates a simulation at time 0 with no events
+    /// # Arguments
+    /// * `world` - The world to simulate
+    pub fn new(world: W) -> Self {
+        EventSimulation {
+            world,
+            queue: EventQueue::new(),
+            handled: 0,
+            samples: 0,
+        }
+    }
+
+    /// Gets the current time, that of the last event handled
+    pub fn time(&self) -> f64 {
+        self.queue.now()
+    }
+
+    /// Gets the number of events handled so far
+    pub fn handled(&self) -> u64 {
+        self.handled
+    }
+
+    /// Gets the world being simulated
+    pub fn world(&self) -> &W {
+        &self.world
+    }
+
+    /// Gets the world being simulated, mutably
+    pub fn world_mut(&mut self) -> &mut W {
+        &mut self.world
+    }
+
+    /// Gets the queue of events, e.g. to schedule the first ones
+    pub fn queue_mut(&mut self) -> &mut EventQueue<W::Event> {
+        &mut self.queue
+    }
+
+    /// Handles the next event, if there is one
+    pub fn step(&mut self) -> bool {
+        match self.queue.pop() {
+            Some((_, event)) => {
+                self.world.handle(event, &mut self.queue);
+                self.handled += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Handles every event up to and including a time, calling observers
+    /// at regular sample times along the way. Sample `k` is taken at
+    /// `k * interval` after every event up to that time, and observers see
+    /// it as tick `k`. Later runs carry on from the last sample, so should
+    /// use the same interval
+    /// # Arguments
+    /// * `until` - The time to run up to
+    /// * `interval` - The time between samples, positive
+    /// * `observers` - The observers to call at every sample
+    pub fn run(&mut self, until: f64, interval: f64, observers: &mut [&mut dyn Observer<W>]) {
+        assert!(interval > 0.0, "the sampling interval must be positive");
+        loop {
+            let sample = self.samples + 1;
+            let sample_time = sample as f64 * interval;
+            let next = self.queue.next_time().filter(|&time| time <= until);
+            match next {
+                Some(time) if time <= sample_time || sample_time > until => {
+                    self.step();
+                }
+                _ if sample_time <= until => {
+                    for observer in observers.iter_mut() {
+                        observer.observe(sample, &self.world);
+                    }
+                    self.samples = sample;
+                }
+                _ => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_order() {
+        let mut queue = EventQueue::new();
+        queue.schedule(2.0, "c");
+        queue.schedule(1.0, "a");
+        queue.schedule(2.0, "d");
+        queue.schedule(1.0, "b");
+        let order: Vec<_> = (0..4).map(|_| queue.pop().unwrap()).collect();
+        assert_eq!(order, vec![(1.0, "a"), (1.0, "b"), (2.0, "c"), (2.0, "d")]);
+        assert_eq!(queue.now(), 2.0);
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "before the current time")]
+    fn test_no_scheduling_in_the_past() {
+        let mut queue = EventQueue::new();
+        queue.schedule(5.0, ());
+        queue.pop();
+        queue.schedule(4.0, ());
+    }
+
+    // a countdown which ticks at a slowing rate
+    struct Countdown {
+        left: u32,
+        log: Vec<(f64, u32)>,
+    }
+    impl EventWorld for Countdown {
+        type Event = u32;
+        fn handle(&mut self, step: u32, queue: &mut EventQueue<u32>) {
+            self.left -= 1;
+            self.log.push((queue.now(), step));
+            if self.left > 0 {
+                queue.schedule_in(step as f64, step + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_samples_between_events() {
+        let mut sim = EventSimulation::new(Countdown {
+            left: 4,
+            log: Vec::new(),
+        });
+        sim.queue_mut().schedule(0.5, 1);
+        let mut samples = Vec::new();
+        {
+            let mut sample = |tick: u64, world: &Countdown| samples.push((tick, world.left));
+            sim.run(5.0, 1.0, &mut [&mut sample]);
+        }
+        // events at 0.5, 1.5, 3.5 and 6.5, the last after the end
+        assert_eq!(sim.world().log, vec![(0.5, 1), (1.5, 2), (3.5, 3)]);
+        assert_eq!(sim.handled(), 3);
+        assert_eq!(samples, vec![(1, 3), (2, 2), (3, 2), (4, 1), (5, 1)]);
+
+        samples.clear();
+        {
+            let mut sample = |tick: u64, world: &Countdown| samples.push((tick, world.left));
+            sim.run(7.0, 1.0, &mut [&mut sample]);
+        }
+        assert_eq!(samples, vec![(6, 1), (7, 0)]);
+        assert_eq!(sim.world().left, 0);
+        assert_eq!(sim.time(), 6.5);
+    }
+}
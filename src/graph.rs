@@ -0,0 +1,827 @@
+//! Networks for worlds whose entities interact along edges rather than by
+//! distance, e.g. opinions spreading between friends.
+//!
+//! Node `i` of a `Graph` is meant to be entity `i` of a world, so messages
+//! can be routed along edges straight into a `Mailbox`. Entities which
+//! implement `Rewire` choose their own links, and `Graph::rewire` applies
+//! what they asked for between ticks.
+//!
+//! ```rust
+//! # extern crate rand;
+//! # extern crate sekai;
+//! # use rand::{SeedableRng, StdRng};
+//! # use sekai::graph;
+//! # use sekai::mailbox::Mailbox;
+//! # fn main() {
+//! let mut rng = StdRng::from_seed(&[1][..]);
+//! let network = graph::watts_strogatz(100, 2, 0.1, &mut rng);
+//! assert_eq!(network.edge_count(), 200);
+//!
+//! // node 0 tells all of its friends
+//! let mut mailbox = Mailbox::new();
+//! network.broadcast(0, "hello", &mut mailbox);
+//! assert_eq!(mailbox.len(), network.degree(0));
+//! # }
+//! ```
+
+use std::cmp::Reverse;
+use std::io::{self, BufRead, Write};
+
+use rand::Rng;
+
+use entity::Entity;
+use mailbox::Mailbox;
+use observer::{Observer, Probe};
+
+/// The most nodes an edge list may have, so a corrupt file cannot make
+/// `Graph::read_edge_list` allocate without bound
+pub const MAX_NODES: usize = 1 << 24;
+
+/// Nodes linked by edges, without self loops or repeated edges
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Graph {
+    directed: bool,
+    adjacency: Vec<Vec<usize>>,
+    edges: usize,
+}
+
+impl Graph {
+    /// Creates an undirected graph without edges
+    /// # Arguments
+    /// * `nodes` - The number of nodes
+    pub fn new(nodes: usize) -> Self {
+        Graph {
+            directed: false,
+            adjacency: vec![Vec::new(); nodes],
+            edges: 0,
+        }
+    }
+
+    /// Creates a directed graph without edges, where the neighbors of a
+    /// node are those its edges point to
+    /// # Arguments
+    /// * `nodes` - The number of nodes
+    pub fn directed(nodes: usize) -> Self {
+        Graph {
+            directed: true,
+            ..Graph::new(nodes)
+        }
+    }
+
+    /// Checks whether edges have a direction
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    /// Gets the number of nodes
+    pub fn node_count(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    /// Gets the number of edges
+    pub fn edge_count(&self) -> usize {
+        self.edges
+    }
+
+    /// Adds a node without edges, returning its index
+    pub fn add_node(&mut self) -> usize {
+        self.adjacency.push(Vec::new());
+        self.adjacency.len() - 1
+    }
+
+    /// Adds an edge, unless it is a self loop or already exists
+    /// # Arguments
+    /// * `from` - The node the edge starts at
+    /// * `to` - The node the edge ends at
+    /// # Panics
+    /// If either node does not exist
+    pub fn add_edge(&mut self, from: usize, to: usize) -> bool {
+        assert!(
+            from < self.node_count() && to < self.node_count(),
+            "edge {}-{} is outside a graph of {} nodes",
+            from,
+            to,
+            self.node_count()
+        );
+        if from == to || self.has_edge(from, to) {
+            return false;
+        }
+        self.adjacency[from].push(to);
+        if !self.directed {
+            self.adjacency[to].push(from);
+        }
+        self.edges += 1;
+        true
+    }
+
+    /// Removes an edge, if it exists
+    /// # Arguments
+    /// * `from` - The node the edge starts at
+    /// * `to` - The node the edge ends at
+    pub fn remove_edge(&mut self, from: usize, to: usize) -> bool {
+        if !self.has_edge(from, to) {
+            return false;
+        }
+        self.adjacency[from].retain(|&n| n != to);
+        if !self.directed {
+            self.adjacency[to].retain(|&n| n != from);
+        }
+        self.edges -= 1;
+        true
+    }
+
+    /// Checks whether there is an edge between two nodes
+    /// # Arguments
+    /// * `from` - The node the edge starts at
+    /// * `to` - The node the edge ends at
+    pub fn has_edge(&self, from: usize, to: usize) -> bool {
+        self.adjacency
+            .get(from)
+            .is_some_and(|neighbors| neighbors.contains(&to))
+    }
+
+    /// Gets the neighbors of a node, in the order their edges were added
+    /// # Arguments
+    /// * `node` - The node
+    pub fn neighbors(&self, node: usize) -> &[usize] {
+        &self.adjacency[node]
+    }
+
+    /// Gets the number of neighbors of a node
+    /// # Arguments
+    /// * `node` - The node
+    pub fn degree(&self, node: usize) -> usize {
+        self.adjacency[node].len()
+    }
+
+    /// Gets every edge, each undirected edge once with its lower node first
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        let mut edges = Vec::with_capacity(self.edges);
+        for (from, neighbors) in self.adjacency.iter().enumerate() {
+            for &to in neighbors {
+                if self.directed || from < to {
+                    edges.push((from, to));
+                }
+            }
+        }
+        edges
+    }
+
+    /// Sends a copy of a message to every neighbor of a node
+    /// # Arguments
+    /// * `from` - The sending node
+    /// * `message` - The message
+    /// * `mailbox` - The inboxes of the world's entities
+    pub fn broadcast<M: Clone>(&self, from: usize, message: M, mailbox: &mut Mailbox<M>) {
+        for &to in self.neighbors(from) {
+            mailbox.post(to, message.clone());
+        }
+    }
+
+    /// Sends a message to a neighbor of a node, returning whether there
+    /// was an edge to send it along
+    /// # Arguments
+    /// * `from` - The sending node
+    /// * `to` - The receiving node
+    /// * `message` - The message
+    /// * `mailbox` - The inboxes of the world's entities
+    pub fn send<M>(&self, from: usize, to: usize, message: M, mailbox: &mut Mailbox<M>) -> bool {
+        if !self.has_edge(from, to) {
+            return false;
+        }
+        mailbox.post(to, message);
+        true
+    }
+
+    /// Changes the links of a node, returning whether the graph changed
+    /// # Arguments
+    /// * `node` - The node asking for the change
+    /// * `change` - The change
+    pub fn apply(&mut self, node: usize, change: EdgeChange) -> bool {
+        match change {
+            EdgeChange::Link(to) if node < self.node_count() && to < self.node_count() => {
+                self.add_edge(node, to)
+            }
+            EdgeChange::Link(_) => false,
+            EdgeChange::Unlink(to) => self.remove_edge(node, to),
+        }
+    }
+
+    /// Asks every entity which links it wants, then makes the changes, so
+    /// every entity decides on the network as it was before the tick.
+    /// Changes are made in the order of the entities, and those which do
+    /// nothing, e.g. linking nodes already linked, are skipped
+    /// # Arguments
+    /// * `entities` - The entities, entity `i` being node `i`
+    pub fn rewire<M, E: Rewire<M>>(&mut self, entities: &mut [E]) -> RewireStats {
+        let mut requests = Vec::new();
+        for (node, entity) in entities.iter_mut().enumerate().take(self.node_count()) {
+            for change in entity.rewire(node, self) {
+                requests.push((node, change));
+            }
+        }
+        let mut stats = RewireStats {
+            requested: requests.len(),
+            ..RewireStats::default()
+        };
+        for (node, change) in requests {
+            if self.apply(node, change) {
+                match change {
+                    EdgeChange::Link(_) => stats.added += 1,
+                    EdgeChange::Unlink(_) => stats.removed += 1,
+                }
+            }
+        }
+        stats
+    }
+
+    /// Counts the nodes of every degree, so entry `k` is the number of
+    /// nodes with `k` neighbors
+    pub fn degree_distribution(&self) -> Vec<usize> {
+        let mut counts = Vec::new();
+        for node in 0..self.node_count() {
+            let degree = self.degree(node);
+            if counts.len() <= degree {
+                counts.resize(degree + 1, 0);
+            }
+            counts[degree] += 1;
+        }
+        counts
+    }
+
+    /// Gets the fraction of pairs of neighbors of a node which are linked
+    /// themselves, 0 for nodes with fewer than two neighbors
+    /// # Arguments
+    /// * `node` - The node
+    pub fn local_clustering(&self, node: usize) -> f64 {
+        let neighbors = self.neighbors(node);
+        let k = neighbors.len();
+        if k < 2 {
+            return 0.0;
+        }
+        // ordered pairs, so undirected links count twice, as do the pairs
+        let linked = neighbors
+            .iter()
+            .flat_map(|&a| neighbors.iter().map(move |&b| (a, b)))
+            .filter(|&(a, b)| a != b && self.has_edge(a, b))
+            .count();
+        linked as f64 / (k * (k - 1)) as f64
+    }
+
+    /// Gets the mean local clustering of every node
+    pub fn clustering_coefficient(&self) -> f64 {
+        if self.node_count() == 0 {
+            return 0.0;
+        }
+        let total: f64 = (0..self.node_count())
+            .map(|node| self.local_clustering(node))
+            .sum();
+        total / self.node_count() as f64
+    }
+
+    /// Groups nodes linked by paths, ignoring the direction of edges,
+    /// largest group first and each in node order
+    pub fn components(&self) -> Vec<Vec<usize>> {
+        let mut linked = self.adjacency.clone();
+        if self.directed {
+            for (from, to) in self.edges() {
+                linked[to].push(from);
+            }
+        }
+        let mut seen = vec![false; self.node_count()];
+        let mut components = Vec::new();
+        for start in 0..self.node_count() {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut component = vec![start];
+            let mut next = 0;
+            while next < component.len() {
+                for &neighbor in &linked[component[next]] {
+                    if !seen[neighbor] {
+                        seen[neighbor] = true;
+                        component.push(neighbor);
+                    }
+                }
+                next += 1;
+            }
+            component.sort();
+            components.push(component);
+        }
+        components.sort_by_key(|component| Reverse(component.len()));
+        components
+    }
+
+    /// Reads a graph from an edge list with one `from to` pair of node
+    /// indices per line. Further columns, e.g. weights, are ignored, as are
+    /// blank lines and lines starting with `#`. The graph has as many nodes
+    /// as a `# nodes: <count>` line says, which every index must be below,
+    /// or else as many as the highest index needs, up to `MAX_NODES`
+    /// # Arguments
+    /// * `reader` - Where to read the edge list from
+    /// * `directed` - Whether the edges have a direction
+    pub fn read_edge_list<R: BufRead>(reader: R, directed: bool) -> io::Result<Graph> {
+        let invalid = |number: usize, message: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", number + 1, message),
+            )
+        };
+        let mut edges = Vec::new();
+        let mut declared = None;
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if let Some(count) = line.strip_prefix("# nodes:") {
+                match count.trim().parse::<usize>() {
+                    Ok(count) if count <= MAX_NODES => declared = Some(count),
+                    Ok(_) => return Err(invalid(number, "too many nodes")),
+                    Err(_) => return Err(invalid(number, "expected a node count")),
+                }
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut columns = line.split_whitespace().map(str::parse::<usize>);
+            let (from, to) = match (columns.next(), columns.next()) {
+                (Some(Ok(from)), Some(Ok(to))) => (from, to),
+                _ => return Err(invalid(number, "expected two node indices")),
+            };
+            let limit = declared.unwrap_or(MAX_NODES);
+            if from >= limit || to >= limit {
+                return Err(invalid(
+                    number,
+                    &format!("node indices must be below {}", limit),
+                ));
+            }
+            edges.push((from, to));
+        }
+        // indices are below the limit, so adding 1 cannot overflow
+        let needed = edges.iter().map(|&(a, b)| a.max(b) + 1).max().unwrap_or(0);
+        if declared.is_some_and(|declared| needed > declared) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "an edge names a node beyond the declared count",
+            ));
+        }
+        let nodes = declared.unwrap_or(needed);
+        let mut graph = if directed {
+            Graph::directed(nodes)
+        } else {
+            Graph::new(nodes)
+        };
+        for (from, to) in edges {
+            graph.add_edge(from, to);
+        }
+        Ok(graph)
+    }
+
+    /// Writes the edges as an edge list that `read_edge_list` reads back
+    /// # Arguments
+    /// * `writer` - Where to write the edge list
+    pub fn write_edge_list<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "# nodes: {}", self.node_count())?;
+        for (from, to) in self.edges() {
+            writeln!(writer, "{} {}", from, to)?;
+        }
+        Ok(())
+    }
+}
+
+/// A change an entity asks for to the links of its own node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeChange {
+    /// Link to a node
+    Link(usize),
+    /// Remove the link to a node
+    Unlink(usize),
+}
+
+/// What became of the changes asked for in one round of rewiring
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RewireStats {
+    pub requested: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// An entity which chooses its own links, e.g. a firefly only watching the
+/// partners it flashes in time with
+pub trait Rewire<M>: Entity<M> {
+    /// Asks for changes to the links of the entity's node
+    /// # Arguments
+    /// * `node` - The entity's node
+    /// * `graph` - The network as it was before the tick
+    fn rewire(&mut self, node: usize, graph: &Graph) -> Vec<EdgeChange>;
+}
+
+/// A world whose entities interact along the edges of a network
+pub trait Networked {
+    /// Gets the network, node `i` being entity `i`
+    fn network(&self) -> &Graph;
+}
+
+impl Networked for Graph {
+    fn network(&self) -> &Graph {
+        self
+    }
+}
+
+/// Probe reporting the shape of a world's network, with the columns
+/// `<name>_edges`, `<name>_mean_degree`, `<name>_max_degree`,
+/// `<name>_clustering`, `<name>_components` and `<name>_largest_component`
+pub struct NetworkProbe {
+    name: String,
+}
+
+impl NetworkProbe {
+    /// Creates a probe
+    /// # Arguments
+    /// * `name` - Prefix of the column names
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        NetworkProbe { name: name.into() }
+    }
+}
+
+impl<W: Networked + ?Sized> Probe<W> for NetworkProbe {
+    fn columns(&self) -> Vec<String> {
+        [
+            "edges",
+            "mean_degree",
+            "max_degree",
+            "clustering",
+            "components",
+            "largest_component",
+        ]
+        .iter()
+        .map(|column| format!("{}_{}", self.name, column))
+        .collect()
+    }
+    fn measure(&mut self, world: &W) -> Vec<f64> {
+        let graph = world.network();
+        let nodes = graph.node_count();
+        let degrees: Vec<usize> = (0..nodes).map(|node| graph.degree(node)).collect();
+        let mean_degree = if nodes == 0 {
+            0.0
+        } else {
+            degrees.iter().sum::<usize>() as f64 / nodes as f64
+        };
+        let components = graph.components();
+        vec![
+            graph.edge_count() as f64,
+            mean_degree,
+            degrees.iter().cloned().max().unwrap_or(0) as f64,
+            graph.clustering_coefficient(),
+            components.len() as f64,
+            components.first().map_or(0, Vec::len) as f64,
+        ]
+    }
+}
+
+/// The shape of a network at one tick
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NetworkFrame {
+    pub tick: u64,
+    /// Entry `k` is the number of nodes with `k` neighbors
+    pub degrees: Vec<usize>,
+    pub clustering: f64,
+    /// Sizes of the components, largest first
+    pub components: Vec<usize>,
+}
+
+/// Observer recording the degree distribution, clustering and components
+/// of a world's network at every tick
+#[derive(Debug, Clone, Default)]
+pub struct NetworkHistory {
+    frames: Vec<NetworkFrame>,
+}
+
+impl NetworkHistory {
+    /// Creates an empty history
+    pub fn new() -> Self {
+        NetworkHistory::default()
+    }
+
+    /// Gets the shape of the network at every observed tick
+    pub fn frames(&self) -> &[NetworkFrame] {
+        &self.frames
+    }
+}
+
+impl<W: Networked + ?Sized> Observer<W> for NetworkHistory {
+    fn observe(&mut self, tick: u64, world: &W) {
+        let graph = world.network();
+        self.frames.push(NetworkFrame {
+            tick,
+            degrees: graph.degree_distribution(),
+            clustering: graph.clustering_coefficient(),
+            components: graph.components().iter().map(Vec::len).collect(),
+        });
+    }
+}
+
+/// Creates a grid where every node is linked to the nodes above, below, left
+/// and right of it. Node `(x, y)` is `y * width + x`
+/// # Arguments
+/// * `width` - The number of columns
+/// * `height` - The number of rows
+/// * `periodic` - Whether the edges of the grid wrap around
+pub fn lattice(width: usize, height: usize, periodic: bool) -> Graph {
+    let mut graph = Graph::new(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let node = y * width + x;
+            if x + 1 < width {
+                graph.add_edge(node, node + 1);
+            } else if periodic {
+                graph.add_edge(node, y * width);
+            }
+            if y + 1 < height {
+                graph.add_edge(node, node + width);
+            } else if periodic {
+                graph.add_edge(node, x);
+            }
+        }
+    }
+    graph
+}
+
+/// Creates a ring where every node is linked to its `k` nearest nodes on
+/// each side
+/// # Arguments
+/// * `nodes` - The number of nodes
+/// * `k` - The number of neighbors on each side
+pub fn ring(nodes: usize, k: usize) -> Graph {
+    let mut graph = Graph::new(nodes);
+    for node in 0..nodes {
+        for step in 1..=k {
+            graph.add_edge(node, (node + step) % nodes);
+        }
+    }
+    graph
+}
+
+/// Creates an Erdős–Rényi random graph, where every pair of nodes is linked
+/// with the same probability
+/// # Arguments
+/// * `nodes` - The number of nodes
+/// * `p` - The probability of each edge
+/// * `rng` - The source of randomness
+pub fn erdos_renyi<R: Rng>(nodes: usize, p: f64, rng: &mut R) -> Graph {
+    let mut graph = Graph::new(nodes);
+    for from in 0..nodes {
+        for to in from + 1..nodes {
+            if rng.gen::<f64>() < p {
+                graph.add_edge(from, to);
+            }
+        }
+    }
+    graph
+}
+
+/// Creates a Watts–Strogatz small world: a `ring` whose edges each have
+/// their far end moved to a random node with probability `beta`
+/// # Arguments
+/// * `nodes` - The number of nodes
+/// * `k` - The number of neighbors on each side in the ring
+/// * `beta` - The probability of rewiring each edge
+/// * `rng` - The source of randomness
+pub fn watts_strogatz<R: Rng>(nodes: usize, k: usize, beta: f64, rng: &mut R) -> Graph {
+    let mut graph = ring(nodes, k);
+    for step in 1..=k {
+        for node in 0..nodes {
+            let old = (node + step) % nodes;
+            // a node linked to all others has nowhere to rewire to
+            if rng.gen::<f64>() >= beta || graph.degree(node) + 1 >= nodes {
+                continue;
+            }
+            let mut new = rng.gen_range(0, nodes);
+            while new == node || graph.has_edge(node, new) {
+                new = rng.gen_range(0, nodes);
+            }
+            graph.remove_edge(node, old);
+            graph.add_edge(node, new);
+        }
+    }
+    graph
+}
+
+/// Creates a Barabási–Albert scale-free network: starting from `m + 1`
+/// nodes all linked together, every new node links to `m` existing nodes
+/// chosen with probability proportional to their degree
+/// # Arguments
+/// * `nodes` - The number of nodes, more than `m`
+/// * `m` - The number of edges every new node brings, at least 1
+/// * `rng` - The source of randomness
+pub fn barabasi_albert<R: Rng>(nodes: usize, m: usize, rng: &mut R) -> Graph {
+    assert!(
+        m >= 1 && nodes > m,
+        "a Barabási–Albert network needs m >= 1 and more than m nodes"
+    );
+    let mut graph = Graph::new(nodes);
+    // every node appears once per edge end, so picking uniformly from it
+    // picks nodes in proportion to their degree
+    let mut ends = Vec::new();
+    for from in 0..=m {
+        for to in from + 1..=m {
+            graph.add_edge(from, to);
+            ends.push(from);
+            ends.push(to);
+        }
+    }
+    for node in m + 1..nodes {
+        let mut targets = Vec::with_capacity(m);
+        while targets.len() < m {
+            let target = ends[rng.gen_range(0, ends.len())];
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        for target in targets {
+            graph.add_edge(node, target);
+            ends.push(node);
+            ends.push(target);
+        }
+    }
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+    use world::World;
+
+    #[test]
+    fn test_edges() {
+        let mut graph = Graph::new(3);
+        assert!(graph.add_edge(0, 1));
+        assert!(!graph.add_edge(1, 0));
+        assert!(!graph.add_edge(2, 2));
+        assert!(graph.add_edge(2, 1));
+        assert_eq!(graph.neighbors(1), &[0, 2]);
+        assert_eq!(graph.edges(), vec![(0, 1), (1, 2)]);
+        assert!(graph.remove_edge(1, 0));
+        assert_eq!(graph.edge_count(), 1);
+
+        let mut directed = Graph::directed(2);
+        directed.add_edge(0, 1);
+        assert!(directed.add_edge(1, 0));
+        assert_eq!(directed.edge_count(), 2);
+    }
+
+    #[test]
+    fn test_generators() {
+        assert_eq!(lattice(3, 3, false).edge_count(), 12);
+        assert_eq!(lattice(3, 3, true).edge_count(), 18);
+        assert!(lattice(4, 4, true).edges().iter().all(|&(a, b)| a != b));
+        assert_eq!(ring(10, 2).edge_count(), 20);
+
+        let mut rng = StdRng::from_seed(&[2][..]);
+        assert_eq!(watts_strogatz(10, 2, 0.0, &mut rng), ring(10, 2));
+        let small_world = watts_strogatz(50, 2, 1.0, &mut rng);
+        assert_eq!(small_world.edge_count(), 100);
+        assert_ne!(small_world, ring(50, 2));
+
+        let random = erdos_renyi(200, 0.1, &mut rng);
+        // 19900 pairs at 10%
+        assert!((random.edge_count() as f64 - 1990.0).abs() < 150.0);
+
+        let scale_free = barabasi_albert(300, 2, &mut rng);
+        assert_eq!(scale_free.edge_count(), 3 + 2 * 297);
+        assert!((0..300).all(|n| scale_free.degree(n) >= 2));
+        // early nodes become hubs
+        let hub = (0..300).map(|n| scale_free.degree(n)).max().unwrap();
+        assert!(hub > 15, "largest degree {}", hub);
+    }
+
+    #[test]
+    fn test_edge_list() {
+        let text = "# a triangle and a tail\n0 1\n1 2 0.5\n\n2 0\n2 4\n";
+        let mut graph = Graph::read_edge_list(text.as_bytes(), false).unwrap();
+        assert_eq!(graph.node_count(), 5);
+        assert_eq!(graph.edge_count(), 4);
+        assert_eq!(graph.degree(3), 0);
+
+        // a lonely node survives the round trip
+        graph.add_node();
+        let mut written = Vec::new();
+        graph.write_edge_list(&mut written).unwrap();
+        let read = Graph::read_edge_list(&written[..], false).unwrap();
+        assert_eq!(read.node_count(), 6);
+        let mut edges = read.edges();
+        edges.sort();
+        assert_eq!(edges, vec![(0, 1), (0, 2), (1, 2), (2, 4)]);
+
+        let error = Graph::read_edge_list("0 1\n1 x\n".as_bytes(), false).unwrap_err();
+        assert_eq!(error.to_string(), "line 2: expected two node indices");
+
+        // corrupt indices and counts are errors, not panics or huge graphs
+        let read = |text: &str| Graph::read_edge_list(text.as_bytes(), false).unwrap_err();
+        assert_eq!(
+            read("0 18446744073709551615\n").kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            read("0 100000000000\n").to_string(),
+            "line 1: node indices must be below 16777216"
+        );
+        assert_eq!(
+            read("# nodes: 3\n0 3\n").to_string(),
+            "line 2: node indices must be below 3"
+        );
+        assert_eq!(
+            read("# nodes: three\n").to_string(),
+            "line 1: expected a node count"
+        );
+        assert_eq!(read("0 5\n# nodes: 3\n").kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_network_metrics() {
+        // a triangle with a tail, and a lonely node
+        let mut graph = Graph::new(5);
+        for &(a, b) in &[(0, 1), (1, 2), (2, 0), (2, 3)] {
+            graph.add_edge(a, b);
+        }
+        assert_eq!(graph.degree_distribution(), vec![1, 1, 2, 1]);
+        assert_eq!(graph.local_clustering(0), 1.0);
+        assert!((graph.local_clustering(2) - 1.0 / 3.0).abs() < 1e-12);
+        assert!((graph.clustering_coefficient() - (2.0 + 1.0 / 3.0) / 5.0).abs() < 1e-12);
+        assert_eq!(graph.components(), vec![vec![0, 1, 2, 3], vec![4]]);
+
+        let mut chain = Graph::directed(3);
+        chain.add_edge(2, 1);
+        chain.add_edge(0, 1);
+        assert_eq!(chain.components(), vec![vec![0, 1, 2]]);
+
+        let mut probe = NetworkProbe::new("net");
+        assert_eq!(Probe::<Graph>::columns(&probe)[3], "net_clustering");
+        assert_eq!(
+            probe.measure(&graph),
+            vec![4.0, 1.6, 3.0, graph.clustering_coefficient(), 2.0, 4.0]
+        );
+    }
+
+    // links to the next node and drops every other link
+    #[derive(Clone)]
+    struct Follower;
+    impl Entity<()> for Follower {
+        fn update(&mut self, _world: &dyn World<()>) {}
+        fn receive_message(&mut self, _message: ()) {}
+    }
+    impl Rewire<()> for Follower {
+        fn rewire(&mut self, node: usize, graph: &Graph) -> Vec<EdgeChange> {
+            let next = (node + 1) % graph.node_count();
+            let mut changes: Vec<EdgeChange> = graph
+                .neighbors(node)
+                .iter()
+                .filter(|&&n| n != next)
+                .map(|&n| EdgeChange::Unlink(n))
+                .collect();
+            changes.push(EdgeChange::Link(next));
+            changes
+        }
+    }
+
+    #[test]
+    fn test_rewire_between_ticks() {
+        let mut graph = Graph::directed(4);
+        graph.add_edge(0, 2);
+        graph.add_edge(3, 1);
+        let mut followers = vec![Follower; 4];
+        let mut history = NetworkHistory::new();
+        history.observe(0, &graph);
+
+        let stats = graph.rewire(&mut followers);
+        assert_eq!(
+            stats,
+            RewireStats {
+                requested: 6,
+                added: 4,
+                removed: 2,
+            }
+        );
+        assert_eq!(graph.edges(), vec![(0, 1), (1, 2), (2, 3), (3, 0)]);
+        history.observe(1, &graph);
+
+        assert_eq!(graph.rewire(&mut followers).added, 0);
+        let components: Vec<&[usize]> = history
+            .frames()
+            .iter()
+            .map(|frame| &frame.components[..])
+            .collect();
+        assert_eq!(components, vec![&[2, 2][..], &[4]]);
+    }
+
+    #[test]
+    fn test_routing() {
+        let graph = ring(5, 1);
+        let mut mailbox = Mailbox::new();
+        graph.broadcast(0, 'a', &mut mailbox);
+        assert!(graph.send(2, 3, 'b', &mut mailbox));
+        assert!(!graph.send(2, 4, 'c', &mut mailbox));
+        assert_eq!(mailbox.len(), 3);
+    }
+}
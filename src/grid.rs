@@ -0,0 +1,686 @@
+//! Two-dimensional cellular automata whose cells may hold any state: on or
+//! off, one of several states, or a real number.
+//!
+//! A `GridRule` gives the next state of a cell from the cells around it,
+//! and a `GridAutomaton` steps a whole `Grid` with any rule, so life-like,
+//! multi-state, probabilistic and continuous rules all run the same way.
+//!
+//! ```rust
+//! # use sekai::grid::{Grid, GridAutomaton, Life};
+//! let mut grid = Grid::new(5, 5, false);
+//! for x in 1..4 {
+//!     grid.set(x, 2, true);
+//! }
+//! let mut blinker = GridAutomaton::new(grid, Life::conway(), 0);
+//! blinker.step();
+//! assert!(blinker.grid().get(2, 1) && blinker.grid().get(2, 3));
+//! assert_eq!(blinker.grid().count(|&cell| cell), 3);
+//! ```
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use render::{Bounds, Rgb, Scene};
+use world::World;
+
+/// What lies beyond the edges of a grid
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edge<S> {
+    /// The grid wraps around into a torus
+    Wrap,
+    /// Cells beyond the edges stay in one state forever
+    Fixed(S),
+}
+
+/// A rectangle of cells, stored row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid<S> {
+    width: usize,
+    height: usize,
+    cells: Vec<S>,
+    edge: Edge<S>,
+}
+
+impl<S: Clone> Grid<S> {
+    /// Creates a grid which wraps around, every cell in the same state
+    /// # Arguments
+    /// * `width` - The number of columns
+    /// * `height` - The number of rows
+    /// * `fill` - The state of every cell
+    pub fn new(width: usize, height: usize, fill: S) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+            edge: Edge::Wrap,
+        }
+    }
+
+    /// Creates a grid which wraps around from its cells
+    /// # Arguments
+    /// * `width` - The number of columns
+    /// * `cells` - The cells, row by row
+    pub fn from_cells(width: usize, cells: Vec<S>) -> Self {
+        assert!(
+            width > 0 && cells.len().is_multiple_of(width),
+            "{} cells do not fill rows of {}",
+            cells.len(),
+            width
+        );
+        Grid {
+            width,
+            height: cells.len() / width,
+            cells,
+            edge: Edge::Wrap,
+        }
+    }
+}
+
+impl<S> Grid<S> {
+    /// Gets the number of columns
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Gets the number of rows
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Gets every cell, row by row
+    pub fn cells(&self) -> &[S] {
+        &self.cells
+    }
+
+    /// Gets what lies beyond the edges
+    pub fn edge(&self) -> &Edge<S> {
+        &self.edge
+    }
+
+    /// Sets what lies beyond the edges
+    /// # Arguments
+    /// * `edge` - Whether the grid wraps around, or the fixed state beyond it
+    pub fn set_edge(&mut self, edge: Edge<S>) {
+        self.edge = edge;
+    }
+
+    /// Gets the state of a cell
+    /// # Arguments
+    /// * `x` - The column
+    /// * `y` - The row
+    pub fn get(&self, x: usize, y: usize) -> S
+    where
+        S: Clone,
+    {
+        self.cells[y * self.width + x].clone()
+    }
+
+    /// Sets the state of a cell
+    /// # Arguments
+    /// * `x` - The column
+    /// * `y` - The row
+    /// * `state` - The new state
+    pub fn set(&mut self, x: usize, y: usize, state: S) {
+        self.cells[y * self.width + x] = state;
+    }
+
+    /// Counts the cells matching a predicate, e.g. the live ones
+    /// # Arguments
+    /// * `matches` - Checks the state of a cell
+    pub fn count<F: Fn(&S) -> bool>(&self, matches: F) -> usize {
+        self.cells.iter().filter(|&cell| matches(cell)).count()
+    }
+
+    /// Gets a cell relative to another, following the edges beyond the grid
+    /// # Arguments
+    /// * `x` - The column of the cell to start from
+    /// * `y` - The row of the cell to start from
+    /// * `dx` - Columns to the right
+    /// * `dy` - Rows down
+    pub fn offset(&self, x: usize, y: usize, dx: isize, dy: isize) -> &S {
+        let (x, y) = (x as isize + dx, y as isize + dy);
+        let (width, height) = (self.width as isize, self.height as isize);
+        if x >= 0 && x < width && y >= 0 && y < height {
+            return &self.cells[(y * width + x) as usize];
+        }
+        match self.edge {
+            Edge::Wrap => {
+                &self.cells[(y.rem_euclid(height) * width + x.rem_euclid(width)) as usize]
+            }
+            Edge::Fixed(ref state) => state,
+        }
+    }
+
+    /// Draws the grid, one square per cell with row 0 at the top
+    /// # Arguments
+    /// * `cell_size` - The width and height of each cell in pixels
+    /// * `color` - Chooses the color of a state
+    pub fn draw<F: FnMut(&S) -> Rgb>(&self, cell_size: u32, mut color: F) -> Scene {
+        let mut scene = Scene::new(
+            self.width as u32 * cell_size,
+            self.height as u32 * cell_size,
+            Bounds::new(0.0, 0.0, self.width as f32, self.height as f32),
+        );
+        scene.add_grid(self.width, self.height, |x, y| {
+            color(&self.cells[y * self.width + x])
+        });
+        scene
+    }
+}
+
+/// The surroundings of the cell being updated
+#[derive(Debug, Clone, Copy)]
+pub struct Neighborhood<'a, S: 'a> {
+    grid: &'a Grid<S>,
+    x: usize,
+    y: usize,
+}
+
+/// The offsets of the eight cells around a cell
+const MOORE: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+impl<'a, S> Neighborhood<'a, S> {
+    /// Gets the column of the cell
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    /// Gets the row of the cell
+    pub fn y(&self) -> usize {
+        self.y
+    }
+
+    /// Gets the state of the cell
+    pub fn state(&self) -> &'a S {
+        self.grid.offset(self.x, self.y, 0, 0)
+    }
+
+    /// Gets a cell relative to this one
+    /// # Arguments
+    /// * `dx` - Columns to the right
+    /// * `dy` - Rows down
+    pub fn at(&self, dx: isize, dy: isize) -> &'a S {
+        self.grid.offset(self.x, self.y, dx, dy)
+    }
+
+    /// Gets the eight cells around this one
+    pub fn moore(&self) -> Vec<&'a S> {
+        MOORE.iter().map(|&(dx, dy)| self.at(dx, dy)).collect()
+    }
+
+    /// Counts the cells around this one matching a predicate
+    /// # Arguments
+    /// * `matches` - Checks the state of a neighbor
+    pub fn count<F: Fn(&S) -> bool>(&self, matches: F) -> usize {
+        MOORE
+            .iter()
+            .filter(|&&(dx, dy)| matches(self.at(dx, dy)))
+            .count()
+    }
+}
+
+/// How the cells of a grid update
+pub trait GridRule {
+    /// The state of a cell
+    type State: Clone;
+    /// Gets the next state of a cell, all cells reading the grid from before
+    /// the step
+    /// # Arguments
+    /// * `cell` - The cell and its surroundings
+    /// * `rng` - Random numbers for probabilistic rules
+    fn next(&self, cell: Neighborhood<Self::State>, rng: &mut StdRng) -> Self::State;
+}
+
+/// Steps a grid with a rule, one generation per tick
+#[derive(Debug, Clone)]
+pub struct GridAutomaton<R: GridRule> {
+    grid: Grid<R::State>,
+    rule: R,
+    rng: StdRng,
+    generation: u64,
+}
+
+impl<R: GridRule> GridAutomaton<R> {
+    /// Creates an automaton at generation 0
+    /// # Arguments
+    /// * `grid` - The starting grid
+    /// * `rule` - How cells update
+    /// * `seed` - Seeds probabilistic rules
+    pub fn new(grid: Grid<R::State>, rule: R, seed: u64) -> Self {
+        GridAutomaton {
+            grid,
+            rule,
+            rng: StdRng::from_seed(&[seed as usize][..]),
+            generation: 0,
+        }
+    }
+
+    /// Gets the current grid
+    pub fn grid(&self) -> &Grid<R::State> {
+        &self.grid
+    }
+
+    /// Gets the current grid mutably, e.g. to draw in new cells
+    pub fn grid_mut(&mut self) -> &mut Grid<R::State> {
+        &mut self.grid
+    }
+
+    /// Gets how the cells update
+    pub fn rule(&self) -> &R {
+        &self.rule
+    }
+
+    /// Gets the number of steps taken so far
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Updates every cell at once from the grid before the step
+    pub fn step(&mut self) {
+        let mut cells = Vec::with_capacity(self.grid.cells.len());
+        for y in 0..self.grid.height {
+            for x in 0..self.grid.width {
+                let cell = Neighborhood {
+                    grid: &self.grid,
+                    x,
+                    y,
+                };
+                cells.push(self.rule.next(cell, &mut self.rng));
+            }
+        }
+        self.grid.cells = cells;
+        self.generation += 1;
+    }
+
+    /// Takes a number of steps
+    /// # Arguments
+    /// * `generations` - The number of steps to take
+    pub fn run(&mut self, generations: usize) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+}
+
+/// Lets a simulation step the automaton, one generation per tick. Cells
+/// exchange no messages
+impl<R: GridRule> World<()> for GridAutomaton<R> {
+    fn update(&mut self) {
+        self.step();
+    }
+    fn num_entities(&self) -> usize {
+        self.grid.cells.len()
+    }
+    fn receive_message(&mut self, _message: ()) {}
+}
+
+/// A life-like rule on cells which are on or off: a dead cell comes alive
+/// with a number of live neighbors in `birth`, and a live cell stays alive
+/// with a number in `survive`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Life {
+    pub birth: Vec<u8>,
+    pub survive: Vec<u8>,
+}
+
+impl Life {
+    /// Conway's Game of Life, B3/S23
+    pub fn conway() -> Self {
+        Life {
+            birth: vec![3],
+            survive: vec![2, 3],
+        }
+    }
+
+    /// Reads a rule in `B3/S23` notation, e.g. `B36/S23` for HighLife
+    /// # Arguments
+    /// * `notation` - The rule
+    pub fn parse(notation: &str) -> Option<Life> {
+        let mut parts = notation.split('/');
+        let digits = |part: Option<&str>, prefix: char| -> Option<Vec<u8>> {
+            let part = part?.trim();
+            let rest = part
+                .strip_prefix(prefix)
+                .or_else(|| part.strip_prefix(prefix.to_ascii_lowercase()))?;
+            rest.chars()
+                .map(|c| c.to_digit(10).filter(|&d| d <= 8).map(|d| d as u8))
+                .collect()
+        };
+        let birth = digits(parts.next(), 'B')?;
+        let survive = digits(parts.next(), 'S')?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Life { birth, survive })
+    }
+}
+
+impl GridRule for Life {
+    type State = bool;
+    fn next(&self, cell: Neighborhood<bool>, _rng: &mut StdRng) -> bool {
+        let live = cell.count(|&alive| alive) as u8;
+        if *cell.state() {
+            self.survive.contains(&live)
+        } else {
+            self.birth.contains(&live)
+        }
+    }
+}
+
+/// A probabilistic rule on cells which are on or off: entry `k` of `birth`
+/// is the chance of a dead cell with `k` live neighbors coming alive, and of
+/// `survive` the chance of a live one staying alive
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Probabilistic {
+    pub birth: [f64; 9],
+    pub survive: [f64; 9],
+}
+
+impl Probabilistic {
+    /// Follows a life-like rule, except that every cell ends up in the
+    /// other state with a chance
+    /// # Arguments
+    /// * `rule` - The rule to follow
+    /// * `noise` - The chance of each cell doing the opposite
+    pub fn noisy(rule: &Life, noise: f64) -> Self {
+        let mut probabilistic = Probabilistic {
+            birth: [noise; 9],
+            survive: [noise; 9],
+        };
+        for &k in &rule.birth {
+            probabilistic.birth[k as usize] = 1.0 - noise;
+        }
+        for &k in &rule.survive {
+            probabilistic.survive[k as usize] = 1.0 - noise;
+        }
+        probabilistic
+    }
+}
+
+impl GridRule for Probabilistic {
+    type State = bool;
+    fn next(&self, cell: Neighborhood<bool>, rng: &mut StdRng) -> bool {
+        let live = cell.count(|&alive| alive);
+        let chance = if *cell.state() {
+            self.survive[live]
+        } else {
+            self.birth[live]
+        };
+        rng.gen::<f64>() < chance
+    }
+}
+
+/// The states of a cell in Brian's Brain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Brain {
+    Off,
+    Firing,
+    Refractory,
+}
+
+/// Brian's Brain: an off cell fires when exactly two neighbors are firing,
+/// then it rests for a step before it can fire again
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BriansBrain;
+
+impl GridRule for BriansBrain {
+    type State = Brain;
+    fn next(&self, cell: Neighborhood<Brain>, _rng: &mut StdRng) -> Brain {
+        match *cell.state() {
+            Brain::Off if cell.count(|&state| state == Brain::Firing) == 2 => Brain::Firing,
+            Brain::Off => Brain::Off,
+            Brain::Firing => Brain::Refractory,
+            Brain::Refractory => Brain::Off,
+        }
+    }
+}
+
+/// The states of a cell in Wireworld
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Wire {
+    Empty,
+    /// The front of an electron
+    Head,
+    /// The back of an electron
+    Tail,
+    Conductor,
+}
+
+/// Wireworld: electrons travel along conductors, a conductor becoming an
+/// electron head when one or two of its neighbors are heads
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Wireworld;
+
+impl GridRule for Wireworld {
+    type State = Wire;
+    fn next(&self, cell: Neighborhood<Wire>, _rng: &mut StdRng) -> Wire {
+        match *cell.state() {
+            Wire::Empty => Wire::Empty,
+            Wire::Head => Wire::Tail,
+            Wire::Tail => Wire::Conductor,
+            Wire::Conductor => match cell.count(|&state| state == Wire::Head) {
+                1 | 2 => Wire::Head,
+                _ => Wire::Conductor,
+            },
+        }
+    }
+}
+
+/// Lenia: cells hold a level between 0 and 1, which grows or shrinks with
+/// how well the weighted average of the levels within a radius, the
+/// potential, matches a target
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lenia {
+    radius: usize,
+    /// The potential at which cells grow fastest
+    pub mu: f64,
+    /// How far from `mu` the potential may be for cells to grow
+    pub sigma: f64,
+    /// The fraction of the growth applied every step
+    pub dt: f64,
+    /// Weights of the cells within the radius, row by row, summing to 1
+    kernel: Vec<f64>,
+}
+
+impl Lenia {
+    /// Creates a rule with a smooth ring-shaped kernel
+    /// # Arguments
+    /// * `radius` - How far cells see, at least 2 as the ring vanishes at
+    ///   radius 1
+    /// * `mu` - The potential at which cells grow fastest
+    /// * `sigma` - How far from `mu` the potential may be for cells to grow
+    /// * `dt` - The fraction of the growth applied every step
+    pub fn new(radius: usize, mu: f64, sigma: f64, dt: f64) -> Self {
+        assert!(radius >= 2, "the kernel radius must be at least 2");
+        let span = 2 * radius as isize + 1;
+        let mut kernel = Vec::with_capacity((span * span) as usize);
+        for dy in -(radius as isize)..=radius as isize {
+            for dx in -(radius as isize)..=radius as isize {
+                let r = ((dx * dx + dy * dy) as f64).sqrt() / radius as f64;
+                kernel.push(if r > 0.0 && r < 1.0 {
+                    (4.0 - 1.0 / (r * (1.0 - r))).exp()
+                } else {
+                    0.0
+                });
+            }
+        }
+        let total: f64 = kernel.iter().sum();
+        for weight in &mut kernel {
+            *weight /= total;
+        }
+        Lenia {
+            radius,
+            mu,
+            sigma,
+            dt,
+            kernel,
+        }
+    }
+
+    /// The parameters of the Orbium, Lenia's best known glider
+    pub fn orbium() -> Self {
+        Lenia::new(13, 0.15, 0.015, 0.1)
+    }
+
+    /// Gets how far cells see
+    pub fn radius(&self) -> usize {
+        self.radius
+    }
+
+    /// Gets how fast a cell grows at a potential, between -1 and 1
+    /// # Arguments
+    /// * `potential` - The weighted average of the levels around the cell
+    pub fn growth(&self, potential: f64) -> f64 {
+        2.0 * (-(potential - self.mu).powi(2) / (2.0 * self.sigma * self.sigma)).exp() - 1.0
+    }
+
+    /// Gets the weighted average of the levels within the radius of a cell
+    /// # Arguments
+    /// * `cell` - The cell and its surroundings
+    pub fn potential(&self, cell: &Neighborhood<f64>) -> f64 {
+        let radius = self.radius as isize;
+        let span = 2 * radius + 1;
+        let mut potential = 0.0;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let weight = self.kernel[((dy + radius) * span + dx + radius) as usize];
+                if weight > 0.0 {
+                    potential += weight * cell.at(dx, dy);
+                }
+            }
+        }
+        potential
+    }
+}
+
+impl GridRule for Lenia {
+    type State = f64;
+    fn next(&self, cell: Neighborhood<f64>, _rng: &mut StdRng) -> f64 {
+        let growth = self.growth(self.potential(&cell));
+        (cell.state() + self.dt * growth).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_life() {
+        // a glider crosses a wrapping 6x6 grid and comes back in 24 steps
+        let mut grid = Grid::new(6, 6, false);
+        for &(x, y) in &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            grid.set(x, y, true);
+        }
+        let mut life = GridAutomaton::new(grid.clone(), Life::conway(), 0);
+        life.run(4);
+        assert_eq!(life.grid().count(|&cell| cell), 5);
+        assert!(life.grid().get(2, 1) && life.grid().get(3, 3));
+        life.run(20);
+        assert_eq!(life.grid(), &grid);
+
+        // with dead edges the glider hits the corner and becomes a block
+        grid.set_edge(Edge::Fixed(false));
+        let mut walled = GridAutomaton::new(grid, Life::conway(), 0);
+        walled.run(24);
+        assert_eq!(walled.grid().count(|&cell| cell), 4);
+
+        assert_eq!(Life::parse("B3/S23"), Some(Life::conway()));
+        assert_eq!(Life::parse("b36/s23").unwrap().birth, vec![3, 6]);
+        assert_eq!(Life::parse("B9/S23"), None);
+    }
+
+    #[test]
+    fn test_multi_state() {
+        // two firing cells light the two cells beside them
+        let mut grid = Grid::new(6, 6, Brain::Off);
+        grid.set(2, 2, Brain::Firing);
+        grid.set(3, 2, Brain::Firing);
+        let mut brain = GridAutomaton::new(grid, BriansBrain, 0);
+        brain.step();
+        let grid = brain.grid();
+        assert_eq!(grid.get(2, 2), Brain::Refractory);
+        assert_eq!(grid.count(|&state| state == Brain::Firing), 4);
+        assert_eq!(grid.get(2, 1), Brain::Firing);
+
+        // an electron runs along a wire
+        let mut wire = Grid::new(5, 1, Wire::Conductor);
+        wire.set_edge(Edge::Fixed(Wire::Empty));
+        wire.set(1, 0, Wire::Head);
+        wire.set(0, 0, Wire::Tail);
+        let mut world = GridAutomaton::new(wire, Wireworld, 0);
+        world.run(2);
+        assert_eq!(
+            world.grid().cells(),
+            &[
+                Wire::Conductor,
+                Wire::Conductor,
+                Wire::Tail,
+                Wire::Head,
+                Wire::Conductor
+            ]
+        );
+    }
+
+    #[test]
+    fn test_probabilistic() {
+        let mut grid = Grid::new(40, 40, false);
+        grid.set(5, 5, true);
+        // without noise the rule is the deterministic one
+        let exact = Probabilistic::noisy(&Life::conway(), 0.0);
+        let mut quiet = GridAutomaton::new(grid.clone(), exact, 1);
+        quiet.step();
+        assert_eq!(quiet.grid().count(|&cell| cell), 0);
+
+        // noise switches on about that fraction of an empty grid
+        let mut noisy = GridAutomaton::new(grid, Probabilistic::noisy(&Life::conway(), 0.1), 1);
+        noisy.step();
+        let live = noisy.grid().count(|&cell| cell) as f64;
+        assert!((live - 160.0).abs() < 40.0, "{} live cells", live);
+    }
+
+    #[test]
+    fn test_lenia() {
+        let lenia = Lenia::orbium();
+        assert_eq!(lenia.growth(0.15), 1.0);
+        assert!(lenia.growth(0.0) < -0.99);
+        let total: f64 = lenia.kernel.iter().sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        // a uniform field has the field's level as its potential everywhere
+        let lenia = Lenia::new(3, 0.3, 0.05, 0.5);
+        let mut field = GridAutomaton::new(Grid::new(8, 8, 0.3), lenia.clone(), 0);
+        field.step();
+        assert!(field.grid().cells().iter().all(|&level| level > 0.79));
+
+        // a lone speck has too little around it and fades
+        let mut grid = Grid::new(16, 16, 0.0);
+        grid.set(8, 8, 1.0);
+        let mut speck = GridAutomaton::new(grid, lenia, 0);
+        speck.run(2);
+        assert!(speck.grid().get(8, 8) < 1e-6);
+
+        // the smallest kernel still weighs the cells next door
+        let small = Lenia::new(2, 0.3, 0.05, 0.5);
+        assert!(small.kernel.iter().all(|weight| weight.is_finite()));
+        let total: f64 = small.kernel.iter().sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        let mut field = GridAutomaton::new(Grid::new(8, 8, 0.3), small, 0);
+        field.step();
+        assert!(field.grid().cells().iter().all(|&level| level > 0.79));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 2")]
+    fn test_lenia_radius_1() {
+        Lenia::new(1, 0.15, 0.015, 0.1);
+    }
+}
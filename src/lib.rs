@@ -1,4 +1,38 @@
+extern crate gif;
+extern crate png;
+extern crate rand;
+extern crate rayon;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+extern crate toml;
+
+pub mod analysis;
+pub mod automaton;
+pub mod batch;
+pub mod channel;
+pub mod cli;
+pub mod cluster;
+pub mod config;
+pub mod detect;
 pub mod entity;
+pub mod event;
+pub mod graph;
+pub mod grid;
+pub mod mailbox;
+pub mod observer;
+pub mod output;
+pub mod registry;
+pub mod render;
+pub mod replay;
+pub mod schedule;
+pub mod simulation;
+pub mod sugarscape;
+pub mod terminal;
+pub mod trajectory;
+pub mod wireworld;
 pub mod world;
 
 #[cfg(test)]
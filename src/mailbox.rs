@@ -0,0 +1,261 @@
+//! Combining the messages sent to an entity within a tick into one, so the
+//! entity reacts to e.g. the average of the flashes it saw rather than to
+//! each flash in turn.
+//!
+//! An entity declares how its inbox is combined by implementing
+//! `Aggregate`, and its world collects messages in a `Mailbox` during the
+//! tick, then delivers them all at once.
+
+use std::cmp::Ordering;
+use std::mem;
+
+use rayon::prelude::*;
+
+use entity::Entity;
+
+/// Combines the messages an entity received in one tick
+pub trait Reducer<M>: Sync {
+    /// Combines messages into one, or none if there are no messages
+    /// # Arguments
+    /// * `messages` - The messages, in the order they were sent
+    fn reduce(&self, messages: Vec<M>) -> Option<M>;
+}
+
+/// Messages which can be added together and scaled, so they can be summed
+/// and averaged
+pub trait Combine: Sized {
+    /// Adds two messages
+    /// # Arguments
+    /// * `other` - The message to add to this one
+    fn combine(&self, other: &Self) -> Self;
+    /// Scales a message
+    /// # Arguments
+    /// * `factor` - What to multiply the message by
+    fn scale(&self, factor: f64) -> Self;
+}
+
+impl Combine for f64 {
+    fn combine(&self, other: &f64) -> f64 {
+        self + other
+    }
+    fn scale(&self, factor: f64) -> f64 {
+        self * factor
+    }
+}
+
+impl Combine for f32 {
+    fn combine(&self, other: &f32) -> f32 {
+        self + other
+    }
+    fn scale(&self, factor: f64) -> f32 {
+        (*self as f64 * factor) as f32
+    }
+}
+
+impl<T: Combine + Clone> Combine for Vec<T> {
+    /// Adds element by element, keeping the extra elements of the longer
+    fn combine(&self, other: &Vec<T>) -> Vec<T> {
+        let (long, short) = if self.len() >= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        long.iter()
+            .enumerate()
+            .map(|(i, x)| match short.get(i) {
+                Some(y) => x.combine(y),
+                None => x.clone(),
+            })
+            .collect()
+    }
+    fn scale(&self, factor: f64) -> Vec<T> {
+        self.iter().map(|x| x.scale(factor)).collect()
+    }
+}
+
+/// Adds every message together
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sum;
+
+impl<M: Combine> Reducer<M> for Sum {
+    fn reduce(&self, messages: Vec<M>) -> Option<M> {
+        let mut messages = messages.into_iter();
+        let first = messages.next()?;
+        Some(messages.fold(first, |sum, m| sum.combine(&m)))
+    }
+}
+
+/// Averages the messages
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mean;
+
+impl<M: Combine> Reducer<M> for Mean {
+    fn reduce(&self, messages: Vec<M>) -> Option<M> {
+        let count = messages.len();
+        Sum.reduce(messages)
+            .map(|sum| sum.scale(1.0 / count as f64))
+    }
+}
+
+/// Keeps the greatest message, the first of equals
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Max;
+
+impl<M: PartialOrd> Reducer<M> for Max {
+    fn reduce(&self, messages: Vec<M>) -> Option<M> {
+        messages.into_iter().fold(None, |max, m| match max {
+            Some(max) if m.partial_cmp(&max) != Some(Ordering::Greater) => Some(max),
+            _ => Some(m),
+        })
+    }
+}
+
+/// Keeps the least message, the first of equals
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Min;
+
+impl<M: PartialOrd> Reducer<M> for Min {
+    fn reduce(&self, messages: Vec<M>) -> Option<M> {
+        messages.into_iter().fold(None, |min, m| match min {
+            Some(min) if m.partial_cmp(&min) != Some(Ordering::Less) => Some(min),
+            _ => Some(m),
+        })
+    }
+}
+
+/// Folds the messages together with a function, starting from the first
+#[derive(Debug, Clone, Copy)]
+pub struct Fold<F>(pub F);
+
+impl<M, F: Fn(M, M) -> M + Sync> Reducer<M> for Fold<F> {
+    fn reduce(&self, messages: Vec<M>) -> Option<M> {
+        let mut messages = messages.into_iter();
+        let first = messages.next()?;
+        Some(messages.fold(first, &self.0))
+    }
+}
+
+/// An entity which receives one combined message per tick
+pub trait Aggregate<M>: Entity<M> {
+    /// Gets how the entity's inbox is combined, e.g. `&Mean`
+    fn reducer(&self) -> &dyn Reducer<M>;
+}
+
+/// The inboxes of a world's entities, indexed like the entities
+#[derive(Debug, Clone)]
+pub struct Mailbox<M> {
+    inboxes: Vec<Vec<M>>,
+}
+
+impl<M> Mailbox<M> {
+    /// Creates empty inboxes
+    pub fn new() -> Self {
+        Mailbox {
+            inboxes: Vec::new(),
+        }
+    }
+
+    /// Puts a message in the inbox of an entity
+    /// # Arguments
+    /// * `recipient` - The index of the entity
+    /// * `message` - The message
+    pub fn post(&mut self, recipient: usize, message: M) {
+        if self.inboxes.len() <= recipient {
+            self.inboxes.resize_with(recipient + 1, Vec::new);
+        }
+        self.inboxes[recipient].push(message);
+    }
+
+    /// Gets the number of messages waiting
+    pub fn len(&self) -> usize {
+        self.inboxes.iter().map(Vec::len).sum()
+    }
+
+    /// Checks whether no messages are waiting
+    pub fn is_empty(&self) -> bool {
+        self.inboxes.iter().all(Vec::is_empty)
+    }
+
+    /// Empties every inbox, giving each entity with mail one message
+    /// combined by its reducer. Entities are handled in parallel, and
+    /// messages for indices past the last entity are dropped
+    /// # Arguments
+    /// * `entities` - The entities, indexed as when their mail was posted
+    pub fn deliver<E>(&mut self, entities: &mut [E])
+    where
+        E: Aggregate<M> + Send,
+        M: Send,
+    {
+        let mut inboxes = mem::take(&mut self.inboxes);
+        inboxes.truncate(entities.len());
+        entities
+            .par_iter_mut()
+            .zip(inboxes.into_par_iter())
+            .for_each(|(entity, inbox)| {
+                if let Some(message) = entity.reducer().reduce(inbox) {
+                    entity.receive_message(message);
+                }
+            });
+    }
+}
+
+impl<M> Default for Mailbox<M> {
+    fn default() -> Self {
+        Mailbox::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::World;
+
+    #[test]
+    fn test_reducers() {
+        assert_eq!(Sum.reduce(vec![1.0, 2.0, 4.0]), Some(7.0));
+        assert_eq!(Mean.reduce(vec![1.0, 2.0, 6.0]), Some(3.0));
+        assert_eq!(Max.reduce(vec![3, 9, 2]), Some(9));
+        assert_eq!(Min.reduce(vec![3, 9, 2]), Some(2));
+        assert_eq!(
+            Fold(|a: u32, b: u32| a * 10 + b).reduce(vec![1, 2, 3]),
+            Some(123)
+        );
+        assert_eq!(Mean.reduce(Vec::<f64>::new()), None);
+        assert_eq!(
+            Mean.reduce(vec![vec![1.0f32, 2.0], vec![3.0, 4.0]]),
+            Some(vec![2.0, 3.0])
+        );
+    }
+
+    // counts everything it hears, but only hears the loudest message
+    struct Listener {
+        heard: Vec<u32>,
+    }
+    impl Entity<u32> for Listener {
+        fn update(&mut self, _world: &dyn World<u32>) {}
+        fn receive_message(&mut self, message: u32) {
+            self.heard.push(message);
+        }
+    }
+    impl Aggregate<u32> for Listener {
+        fn reducer(&self) -> &dyn Reducer<u32> {
+            &Max
+        }
+    }
+
+    #[test]
+    fn test_deliver_one_message_per_entity() {
+        let mut listeners: Vec<Listener> = (0..3).map(|_| Listener { heard: vec![] }).collect();
+        let mut mailbox = Mailbox::new();
+        mailbox.post(0, 4);
+        mailbox.post(0, 7);
+        mailbox.post(2, 1);
+        mailbox.post(5, 1);
+        assert_eq!(mailbox.len(), 4);
+        mailbox.deliver(&mut listeners);
+
+        assert!(mailbox.is_empty());
+        let heard: Vec<&[u32]> = listeners.iter().map(|l| &l.heard[..]).collect();
+        assert_eq!(heard, vec![&[7][..], &[], &[1]]);
+    }
+}
@@ -0,0 +1,373 @@
+use std::io;
+
+use analysis::gini;
+use output::MetricSink;
+use world::WorldView;
+
+/// Watches a world as it is simulated
+/// # Arguments
+/// * `W` - The type of world being observed
+/// # Example
+/// ```rust
+/// # use sekai::observer::Observer;
+/// struct Printer;
+/// impl Observer<Vec<u32>> for Printer {
+///     fn observe(&mut self, tick: u64, world: &Vec<u32>) {
+///         println!("tick {}: {:?}", tick, world);
+///     }
+/// }
+/// ```
+pub trait Observer<W: ?Sized> {
+    /// Called by the simulation after every tick
+    /// # Arguments
+    /// * `tick` - The number of ticks completed so far
+    /// * `world` - The world after the tick
+    fn observe(&mut self, tick: u64, world: &W);
+}
+
+impl<W: ?Sized, F: FnMut(u64, &W)> Observer<W> for F {
+    fn observe(&mut self, tick: u64, world: &W) {
+        self(tick, world)
+    }
+}
+
+/// Measures one or more named values from a world
+pub trait Probe<W: ?Sized> {
+    /// Names of the values produced by this probe, in order
+    fn columns(&self) -> Vec<String>;
+    /// Measures the world, producing one value per column
+    /// # Arguments
+    /// * `world` - The world to measure
+    fn measure(&mut self, world: &W) -> Vec<f64>;
+}
+
+/// Counts the entities in a world
+#[derive(Debug, Clone, Default)]
+pub struct EntityCount;
+
+impl<W: WorldView + ?Sized> Probe<W> for EntityCount {
+    fn columns(&self) -> Vec<String> {
+        vec!["entities".into()]
+    }
+    fn measure(&mut self, world: &W) -> Vec<f64> {
+        vec![world.entities().len() as f64]
+    }
+}
+
+/// Mean and population variance of a scalar extracted from every entity.
+/// Both values are NaN when the world has no entities
+pub struct ScalarStats<F> {
+    name: String,
+    extract: F,
+}
+
+impl<F> ScalarStats<F> {
+    /// Creates a probe producing `<name>_mean` and `<name>_variance`
+    /// # Arguments
+    /// * `name` - Prefix of the column names
+    /// * `extract` - Extracts the scalar from an entity
+    pub fn new<S: Into<String>>(name: S, extract: F) -> Self {
+        ScalarStats {
+            name: name.into(),
+            extract,
+        }
+    }
+}
+
+impl<W, F> Probe<W> for ScalarStats<F>
+where
+    W: WorldView + ?Sized,
+    F: FnMut(&W::Entity) -> f64,
+{
+    fn columns(&self) -> Vec<String> {
+        vec![
+            format!("{}_mean", self.name),
+            format!("{}_variance", self.name),
+        ]
+    }
+    fn measure(&mut self, world: &W) -> Vec<f64> {
+        let values: Vec<f64> = world.entities().iter().map(&mut self.extract).collect();
+        let (mean, variance) = mean_variance(&values);
+        vec![mean, variance]
+    }
+}
+
+/// Gini coefficient of a scalar extracted from every entity, such as
+/// wealth. NaN when the world has no entities
+pub struct Gini<F> {
+    name: String,
+    extract: F,
+}
+
+impl<F> Gini<F> {
+    /// Creates a probe producing `<name>_gini`
+    /// # Arguments
+    /// * `name` - Prefix of the column name
+    /// * `extract` - Extracts the scalar from an entity
+    pub fn new<S: Into<String>>(name: S, extract: F) -> Self {
+        Gini {
+            name: name.into(),
+            extract,
+        }
+    }
+}
+
+impl<W, F> Probe<W> for Gini<F>
+where
+    W: WorldView + ?Sized,
+    F: FnMut(&W::Entity) -> f64,
+{
+    fn columns(&self) -> Vec<String> {
+        vec![format!("{}_gini", self.name)]
+    }
+    fn measure(&mut self, world: &W) -> Vec<f64> {
+        let values: Vec<f64> = world.entities().iter().map(&mut self.extract).collect();
+        vec![gini(&values)]
+    }
+}
+
+/// A single value computed by a closure over the whole world
+pub struct Custom<F> {
+    name: String,
+    measure: F,
+}
+
+impl<F> Custom<F> {
+    /// Creates a probe producing a single column
+    /// # Arguments
+    /// * `name` - Name of the column
+    /// * `measure` - Computes the value from the world
+    pub fn new<S: Into<String>>(name: S, measure: F) -> Self {
+        Custom {
+            name: name.into(),
+            measure,
+        }
+    }
+}
+
+impl<W: ?Sized, F: FnMut(&W) -> f64> Probe<W> for Custom<F> {
+    fn columns(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+    fn measure(&mut self, world: &W) -> Vec<f64> {
+        vec![(self.measure)(world)]
+    }
+}
+
+/// The values of one named metric, one per observed tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSeries {
+    pub name: String,
+    pub values: Vec<f64>,
+}
+
+/// Observer which runs a set of probes every tick and accumulates their
+/// values into time series. Every row is also passed on to any sinks as it
+/// is measured
+pub struct Metrics<W: ?Sized> {
+    probes: Vec<Box<dyn Probe<W>>>,
+    sinks: Vec<Box<dyn MetricSink>>,
+    ticks: Vec<u64>,
+    series: Vec<TimeSeries>,
+    error: Option<io::Error>,
+}
+
+impl<W: ?Sized> Metrics<W> {
+    pub fn new() -> Self {
+        Metrics {
+            probes: Vec::new(),
+            sinks: Vec::new(),
+            ticks: Vec::new(),
+            series: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Adds a probe whose columns are appended after those already present
+    /// # Arguments
+    /// * `probe` - The probe to run every tick
+    pub fn add_probe<P: Probe<W> + 'static>(&mut self, probe: P) {
+        for name in probe.columns() {
+            self.series.push(TimeSeries {
+                name,
+                values: Vec::new(),
+            });
+        }
+        self.probes.push(Box::new(probe));
+    }
+
+    /// Adds a sink which receives every row after it is measured
+    /// # Arguments
+    /// * `sink` - Where to write the rows, e.g. an `output::CsvWriter`
+    pub fn add_sink<S: MetricSink + 'static>(&mut self, sink: S) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    /// Flushes every sink, returning the first error any sink has hit.
+    /// Observers cannot fail, so write errors are held until this is called
+    pub fn finish(&mut self) -> io::Result<()> {
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.flush() {
+                self.error.get_or_insert(e);
+            }
+        }
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Gets the names of all recorded columns
+    pub fn columns(&self) -> Vec<&str> {
+        self.series.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    /// Gets the ticks at which the probes ran
+    pub fn ticks(&self) -> &[u64] {
+        &self.ticks
+    }
+
+    /// Gets every recorded time series
+    pub fn all_series(&self) -> &[TimeSeries] {
+        &self.series
+    }
+
+    /// Gets a recorded time series by column name
+    /// # Arguments
+    /// * `name` - The column name
+    pub fn series(&self, name: &str) -> Option<&TimeSeries> {
+        self.series.iter().find(|s| s.name == name)
+    }
+
+    /// Gets the value of every column at the last observed tick, e.g. to
+    /// summarize a run. Empty if nothing was observed
+    pub fn final_values(&self) -> Vec<(String, f64)> {
+        if self.ticks.is_empty() {
+            return Vec::new();
+        }
+        self.series
+            .iter()
+            .map(|s| (s.name.clone(), s.values[s.values.len() - 1]))
+            .collect()
+    }
+
+    /// Runs every probe against the world, returning one value per column
+    fn measure(&mut self, world: &W) -> Vec<f64> {
+        let mut row = Vec::with_capacity(self.series.len());
+        for probe in &mut self.probes {
+            row.extend(probe.measure(world));
+        }
+        row
+    }
+}
+
+impl<W: ?Sized> Default for Metrics<W> {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+impl<W: ?Sized> Observer<W> for Metrics<W> {
+    fn observe(&mut self, tick: u64, world: &W) {
+        let row = self.measure(world);
+        assert_eq!(
+            row.len(),
+            self.series.len(),
+            "a probe produced a different number of values than it has columns"
+        );
+        {
+            let columns: Vec<&str> = self.series.iter().map(|s| s.name.as_str()).collect();
+            for sink in &mut self.sinks {
+                if let Err(e) = sink.write_row(tick, &columns, &row) {
+                    self.error.get_or_insert(e);
+                }
+            }
+        }
+        self.ticks.push(tick);
+        for (series, value) in self.series.iter_mut().zip(row) {
+            series.values.push(value);
+        }
+    }
+}
+
+/// Computes the mean and population variance of some values
+/// # Arguments
+/// * `values` - The values to summarize
+pub fn mean_variance(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (f64::NAN, f64::NAN);
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Jar {
+        beans: Vec<f64>,
+    }
+    impl WorldView for Jar {
+        type Entity = f64;
+        fn entities(&self) -> &[f64] {
+            &self.beans
+        }
+    }
+
+    #[test]
+    fn test_metrics_records_every_probe() {
+        let mut metrics = Metrics::new();
+        metrics.add_probe(EntityCount);
+        metrics.add_probe(ScalarStats::new("size", |bean: &f64| *bean));
+        metrics.add_probe(Custom::new("largest", |jar: &Jar| {
+            jar.beans.iter().cloned().fold(0.0, f64::max)
+        }));
+        assert_eq!(
+            metrics.columns(),
+            vec!["entities", "size_mean", "size_variance", "largest"]
+        );
+
+        let mut jar = Jar {
+            beans: vec![1.0, 3.0],
+        };
+        metrics.observe(1, &jar);
+        jar.beans.push(5.0);
+        metrics.observe(2, &jar);
+
+        assert_eq!(metrics.ticks(), &[1, 2]);
+        assert_eq!(metrics.series("entities").unwrap().values, vec![2.0, 3.0]);
+        assert_eq!(metrics.series("size_mean").unwrap().values, vec![2.0, 3.0]);
+        assert_eq!(
+            metrics.series("size_variance").unwrap().values,
+            vec![1.0, 8.0 / 3.0]
+        );
+        assert_eq!(metrics.series("largest").unwrap().values, vec![3.0, 5.0]);
+    }
+
+    #[test]
+    fn test_metrics_writes_to_sinks() {
+        use output::{CsvWriter, RunMetadata};
+        use std::fs;
+
+        let path = ::std::env::temp_dir().join("sekai_test_metrics_writes_to_sinks.csv");
+        let mut metrics = Metrics::new();
+        metrics.add_probe(EntityCount);
+        metrics.add_sink(CsvWriter::create(&path, RunMetadata::new("jar")).unwrap());
+        metrics.observe(1, &Jar { beans: vec![1.0] });
+
+        // rows are on disk before the run is finished
+        let csv = fs::read_to_string(&path).unwrap();
+        assert!(csv.ends_with("tick,entities\n1,1\n"));
+        metrics.finish().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_mean_variance_of_nothing() {
+        let (mean, variance) = mean_variance(&[]);
+        assert!(mean.is_nan() && variance.is_nan());
+    }
+}
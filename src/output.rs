@@ -0,0 +1,261 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+
+/// Describes a run so that recorded output can be traced back to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunMetadata {
+    /// Name of the model that was run
+    pub model: String,
+    /// Seed of the random number generator, if the run was seeded
+    pub seed: Option<u64>,
+    /// Parameters of the model
+    pub parameters: Value,
+    /// Version of sekai which produced the output
+    pub version: String,
+}
+
+impl RunMetadata {
+    /// Creates metadata for an unseeded run without parameters
+    /// # Arguments
+    /// * `model` - Name of the model being run
+    pub fn new<S: Into<String>>(model: S) -> Self {
+        RunMetadata {
+            model: model.into(),
+            seed: None,
+            parameters: Value::Object(Map::new()),
+            version: env!("CARGO_PKG_VERSION").into(),
+        }
+    }
+
+    /// Records the parameters of the model
+    /// # Arguments
+    /// * `parameters` - Anything serializable, usually the model's config
+    pub fn set_parameters<P: Serialize>(
+        &mut self,
+        parameters: &P,
+    ) -> Result<(), serde_json::Error> {
+        self.parameters = serde_json::to_value(parameters)?;
+        Ok(())
+    }
+}
+
+/// Destination for the rows recorded by `observer::Metrics`
+pub trait MetricSink {
+    /// Writes the values measured at one tick. Implementations write any
+    /// header before the first row
+    /// # Arguments
+    /// * `tick` - The tick the values were measured at
+    /// * `columns` - Names of the values, the same for every row
+    /// * `values` - One value per column
+    fn write_row(&mut self, tick: u64, columns: &[&str], values: &[f64]) -> io::Result<()>;
+    /// Flushes anything buffered to the underlying output
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Writes metrics as CSV. Run metadata is written first as `#` comment
+/// lines, which pandas (`comment='#'`) and R (`comment.char = "#"`) skip.
+/// Every row is flushed as soon as it is written
+pub struct CsvWriter<W: Write> {
+    out: W,
+    metadata: RunMetadata,
+    header_written: bool,
+}
+
+impl CsvWriter<BufWriter<File>> {
+    /// Creates a CSV file, truncating any existing file
+    /// # Arguments
+    /// * `path` - Where to write the file
+    /// * `metadata` - Description of the run
+    pub fn create<P: AsRef<Path>>(path: P, metadata: RunMetadata) -> io::Result<Self> {
+        Ok(CsvWriter::new(
+            BufWriter::new(File::create(path)?),
+            metadata,
+        ))
+    }
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Creates a CSV writer over any output
+    /// # Arguments
+    /// * `out` - Where to write the CSV
+    /// * `metadata` - Description of the run
+    pub fn new(out: W, metadata: RunMetadata) -> Self {
+        CsvWriter {
+            out,
+            metadata,
+            header_written: false,
+        }
+    }
+
+    /// Ends writing, giving back the output
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write_header(&mut self, columns: &[&str]) -> io::Result<()> {
+        writeln!(self.out, "# model: {}", self.metadata.model)?;
+        match self.metadata.seed {
+            Some(seed) => writeln!(self.out, "# seed: {}", seed)?,
+            None => writeln!(self.out, "# seed: none")?,
+        }
+        writeln!(self.out, "# parameters: {}", self.metadata.parameters)?;
+        writeln!(self.out, "# sekai_version: {}", self.metadata.version)?;
+        write!(self.out, "tick")?;
+        for column in columns {
+            write!(self.out, ",{}", csv_field(column))?;
+        }
+        writeln!(self.out)
+    }
+}
+
+impl<W: Write> MetricSink for CsvWriter<W> {
+    fn write_row(&mut self, tick: u64, columns: &[&str], values: &[f64]) -> io::Result<()> {
+        if !self.header_written {
+            self.write_header(columns)?;
+            self.header_written = true;
+        }
+        write!(self.out, "{}", tick)?;
+        for value in values {
+            write!(self.out, ",{}", value)?;
+        }
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Writes metrics as JSON Lines. The first line is
+/// `{"metadata": {...}, "columns": [...]}` and every following line is one
+/// tick, e.g. `{"tick": 3, "entities": 4.0}`. Values which are not finite
+/// are written as `null`. Every line is flushed as soon as it is written
+pub struct JsonLinesWriter<W: Write> {
+    out: W,
+    metadata: RunMetadata,
+    header_written: bool,
+}
+
+impl JsonLinesWriter<BufWriter<File>> {
+    /// Creates a JSON Lines file, truncating any existing file
+    /// # Arguments
+    /// * `path` - Where to write the file
+    /// * `metadata` - Description of the run
+    pub fn create<P: AsRef<Path>>(path: P, metadata: RunMetadata) -> io::Result<Self> {
+        Ok(JsonLinesWriter::new(
+            BufWriter::new(File::create(path)?),
+            metadata,
+        ))
+    }
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    /// Creates a JSON Lines writer over any output
+    /// # Arguments
+    /// * `out` - Where to write the lines
+    /// * `metadata` - Description of the run
+    pub fn new(out: W, metadata: RunMetadata) -> Self {
+        JsonLinesWriter {
+            out,
+            metadata,
+            header_written: false,
+        }
+    }
+
+    /// Ends writing, giving back the output
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write_line(&mut self, line: &Value) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, line)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+impl<W: Write> MetricSink for JsonLinesWriter<W> {
+    fn write_row(&mut self, tick: u64, columns: &[&str], values: &[f64]) -> io::Result<()> {
+        if !self.header_written {
+            let header = json!({
+                "metadata": self.metadata,
+                "columns": columns,
+            });
+            self.write_line(&header)?;
+            self.header_written = true;
+        }
+        let mut row = Map::new();
+        row.insert("tick".into(), Value::from(tick));
+        for (column, value) in columns.iter().zip(values) {
+            row.insert((*column).into(), Value::from(*value));
+        }
+        self.write_line(&Value::Object(row))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, quote or newline
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> RunMetadata {
+        let mut metadata = RunMetadata::new("jar");
+        metadata.seed = Some(7);
+        metadata
+            .set_parameters(&json!({ "beans": 3 }))
+            .expect("Failed to set parameters");
+        metadata
+    }
+
+    #[test]
+    fn test_csv_writer() {
+        let mut writer = CsvWriter::new(Vec::new(), metadata());
+        writer
+            .write_row(1, &["entities", "size, mean"], &[2.0, 0.5])
+            .unwrap();
+        writer
+            .write_row(2, &["entities", "size, mean"], &[3.0, f64::NAN])
+            .unwrap();
+        let csv = String::from_utf8(writer.into_inner()).unwrap();
+        let expected = format!(
+            "# model: jar\n# seed: 7\n# parameters: {{\"beans\":3}}\n\
+             # sekai_version: {}\ntick,entities,\"size, mean\"\n1,2,0.5\n2,3,NaN\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        assert_eq!(csv, expected);
+    }
+
+    #[test]
+    fn test_json_lines_writer() {
+        let mut writer = JsonLinesWriter::new(Vec::new(), metadata());
+        writer.write_row(1, &["entities"], &[2.0]).unwrap();
+        writer.write_row(2, &["entities"], &[f64::NAN]).unwrap();
+        let jsonl = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<Value> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 3);
+        let header: RunMetadata = serde_json::from_value(lines[0]["metadata"].clone()).unwrap();
+        assert_eq!(header, metadata());
+        assert_eq!(lines[0]["columns"], json!(["entities"]));
+        assert_eq!(lines[1], json!({ "tick": 1, "entities": 2.0 }));
+        assert_eq!(lines[2], json!({ "tick": 2, "entities": null }));
+    }
+}
@@ -0,0 +1,525 @@
+//! Models registered by name, so the command line and batch tools can find
+//! and run them without knowing their types.
+//!
+//! A model is registered with a `Definition`: its name, its parameters as a
+//! `config::Config`, a constructor building the world from the parameters
+//! and a seed, and the metrics it exposes.
+//!
+//! ```rust
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate sekai;
+//! # use sekai::config::Config;
+//! # use sekai::observer::{Custom, Metrics};
+//! # use sekai::registry::{Definition, Registry};
+//! # use sekai::world::World;
+//! #[derive(Default, Serialize, Deserialize)]
+//! #[serde(default)]
+//! struct Params {
+//!     rate: u64,
+//! }
+//! impl Config for Params {}
+//!
+//! struct Counter {
+//!     count: u64,
+//!     rate: u64,
+//! }
+//! impl World<()> for Counter {
+//!     fn update(&mut self) {
+//!         self.count += self.rate;
+//!     }
+//!     fn num_entities(&self) -> usize {
+//!         0
+//!     }
+//!     fn receive_message(&mut self, _message: ()) {}
+//! }
+//!
+//! # fn main() {
+//! let mut counter = Definition::new("counter", |params: &Params, seed| Counter {
+//!     count: seed,
+//!     rate: params.rate,
+//! });
+//! counter.set_description("Counts up by a fixed rate");
+//! counter.set_metrics(|_| {
+//!     let mut metrics = Metrics::new();
+//!     metrics.add_probe(Custom::new("count", |world: &Counter| world.count as f64));
+//!     metrics
+//! });
+//!
+//! let mut registry = Registry::new();
+//! registry.add(counter);
+//! assert_eq!(registry.get("counter").unwrap().metrics(), vec!["count"]);
+//! # }
+//! ```
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use serde_json::{self, Value};
+
+use config::{self, Config, ConfigError};
+use detect::Detector;
+use entity::Observable;
+use observer::{Metrics, Observer};
+use output::{CsvWriter, RunMetadata};
+use simulation::Simulation;
+use sugarscape;
+use trajectory::TrajectoryRecorder;
+use world::{World, WorldView};
+
+/// What a run returns: the value of every metric at the last tick
+pub type RunResult = Result<Vec<(String, f64)>, Box<dyn Error + Send + Sync>>;
+
+/// A simulation which can be run by name, with parameters only known at run
+/// time. Usually made with a `Definition`
+pub trait Model: Sync {
+    /// Gets the name the model is run by
+    fn name(&self) -> &str;
+
+    /// Gets a one line description of the model
+    fn description(&self) -> &str {
+        ""
+    }
+
+    /// Gets the default parameters, which also name every parameter the
+    /// model accepts
+    fn parameters(&self) -> Value;
+
+    /// Gets the names of the metrics a run reports
+    fn metrics(&self) -> Vec<String>;
+
+    /// Applies overrides to the default parameters and validates the result
+    /// # Arguments
+    /// * `overrides` - A JSON object of the keys to change
+    fn resolve(&self, overrides: Value) -> Result<Value, ConfigError>;
+
+    /// Runs one simulation, writing any outputs into `options.out`
+    /// # Arguments
+    /// * `parameters` - Parameters returned by `resolve`
+    /// * `options` - How long to run, the seed and where to write results
+    fn run(&self, parameters: &Value, options: &RunOptions) -> RunResult;
+}
+
+/// How a model should run, beyond its own parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOptions {
+    pub seed: u64,
+    pub ticks: u64,
+    /// Directory for the model's outputs, if it should write any
+    pub out: Option<PathBuf>,
+}
+
+/// Registers a model from its parameter type and world type. When a run
+/// has an output directory, its metrics are written to `metrics.csv` and,
+/// if enabled, its trajectory to `trajectory.trj`. Models that can stop
+/// early also report a `ticks` metric, and write why they stopped to
+/// `termination.txt`
+/// # Arguments
+/// * `C` - The parameters of the model
+/// * `W` - The world of the model
+/// * `M` - The type of message exchanged within the world
+pub struct Definition<C, W, M> {
+    name: String,
+    description: String,
+    build: Box<Build<C, W>>,
+    metrics: Box<MakeMetrics<C, W>>,
+    trajectory: Option<Box<MakeRecorder<W>>>,
+    detectors: Option<Box<MakeDetectors<C, W>>>,
+    message: PhantomData<fn(M)>,
+}
+
+type Build<C, W> = dyn Fn(&C, u64) -> W + Send + Sync;
+type MakeMetrics<C, W> = dyn Fn(&C) -> Metrics<W> + Send + Sync;
+type MakeDetectors<C, W> = dyn Fn(&C) -> Vec<Box<dyn Detector<W>>> + Send + Sync;
+type MakeRecorder<W> = dyn Fn(&Path) -> io::Result<Box<dyn Recorder<W>>> + Send + Sync;
+
+impl<C, W, M> Definition<C, W, M>
+where
+    C: Config + 'static,
+    W: World<M> + 'static,
+{
+    /// Creates a definition which reports no metrics
+    /// # Arguments
+    /// * `name` - The name the model is run by
+    /// * `build` - Builds the starting world from the parameters and a
+    ///   seed. Any randomness should come from the seed so runs repeat
+    pub fn new<S, F>(name: S, build: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(&C, u64) -> W + Send + Sync + 'static,
+    {
+        Definition {
+            name: name.into(),
+            description: String::new(),
+            build: Box::new(build),
+            metrics: Box::new(|_| Metrics::new()),
+            trajectory: None,
+            detectors: None,
+            message: PhantomData,
+        }
+    }
+
+    /// Sets the one line description of the model
+    /// # Arguments
+    /// * `description` - What the model simulates
+    pub fn set_description<S: Into<String>>(&mut self, description: S) {
+        self.description = description.into();
+    }
+
+    /// Sets the metrics recorded every tick of a run
+    /// # Arguments
+    /// * `metrics` - Builds the probes for a run from its parameters
+    pub fn set_metrics<F>(&mut self, metrics: F)
+    where
+        F: Fn(&C) -> Metrics<W> + Send + Sync + 'static,
+    {
+        self.metrics = Box::new(metrics);
+    }
+
+    /// Records the trajectory of every entity of runs with an output
+    /// directory
+    /// # Arguments
+    /// * `fields` - The entity fields to record alongside positions
+    pub fn record_trajectory(&mut self, fields: &[&str])
+    where
+        W: WorldView,
+        W::Entity: Observable,
+    {
+        let fields: Vec<String> = fields.iter().map(|f| f.to_string()).collect();
+        self.trajectory = Some(Box::new(move |path| {
+            let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+            let recorder = TrajectoryRecorder::create(path, &fields)?;
+            Ok(Box::new(recorder) as Box<dyn Recorder<W>>)
+        }));
+    }
+
+    /// Lets runs stop before their last tick, e.g. once a board has died or
+    /// started repeating
+    /// # Arguments
+    /// * `detectors` - Creates the detectors for a run from its parameters
+    pub fn stop_when<F>(&mut self, detectors: F)
+    where
+        F: Fn(&C) -> Vec<Box<dyn Detector<W>>> + Send + Sync + 'static,
+    {
+        self.detectors = Some(Box::new(detectors));
+    }
+}
+
+impl<C, W, M> Model for Definition<C, W, M>
+where
+    C: Config + 'static,
+    W: World<M> + 'static,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        config::to_value(&C::default()).unwrap_or(Value::Null)
+    }
+
+    fn metrics(&self) -> Vec<String> {
+        let mut metrics: Vec<String> = (self.metrics)(&C::default())
+            .columns()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        if self.detectors.is_some() {
+            metrics.push("ticks".to_string());
+        }
+        metrics
+    }
+
+    fn resolve(&self, overrides: Value) -> Result<Value, ConfigError> {
+        config::to_value(&config::resolve::<C>(overrides)?)
+    }
+
+    fn run(&self, parameters: &Value, options: &RunOptions) -> RunResult {
+        let params: C = serde_json::from_value(parameters.clone())?;
+        let mut metrics = (self.metrics)(&params);
+        let mut recorder = None;
+        if let Some(ref dir) = options.out {
+            let mut metadata = RunMetadata::new(self.name.clone());
+            metadata.seed = Some(options.seed);
+            metadata.set_parameters(&params)?;
+            metrics.add_sink(CsvWriter::create(dir.join("metrics.csv"), metadata)?);
+            if let Some(ref make) = self.trajectory {
+                recorder = Some(make(&dir.join("trajectory.trj"))?);
+            }
+        }
+
+        let mut detectors = match self.detectors {
+            Some(ref make) => make(&params),
+            None => Vec::new(),
+        };
+        let mut detectors: Vec<&mut dyn Detector<W>> = detectors
+            .iter_mut()
+            .map(|d| &mut **d as &mut dyn Detector<W>)
+            .collect();
+
+        let mut sim = Simulation::new((self.build)(&params, options.seed));
+        let termination = match recorder {
+            Some(ref mut recorder) => sim.run_until(
+                options.ticks,
+                &mut [&mut metrics, &mut **recorder as &mut dyn Observer<W>],
+                &mut detectors,
+            ),
+            None => sim.run_until(options.ticks, &mut [&mut metrics], &mut detectors),
+        };
+        metrics.finish()?;
+        if let Some(recorder) = recorder {
+            recorder.finish_recording()?;
+        }
+
+        let mut values = metrics.final_values();
+        if self.detectors.is_some() {
+            values.push(("ticks".to_string(), sim.tick() as f64));
+            if let Some(ref dir) = options.out {
+                fs::write(dir.join("termination.txt"), format!("{}\n", termination))?;
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// An observer writing a trajectory, whose writer type is hidden so any
+/// world's definition can hold one
+trait Recorder<W>: Observer<W> {
+    fn finish_recording(self: Box<Self>) -> io::Result<()>;
+}
+
+impl<W> Recorder<W> for TrajectoryRecorder<BufWriter<File>>
+where
+    W: WorldView,
+    W::Entity: Observable,
+{
+    fn finish_recording(self: Box<Self>) -> io::Result<()> {
+        self.finish().map(|_| ())
+    }
+}
+
+/// The models available by name
+#[derive(Default)]
+pub struct Registry {
+    models: Vec<Box<dyn Model>>,
+}
+
+impl Registry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// Creates a registry of the models in the library, which the `sekai`
+    /// binary runs. The examples each register their own models
+    pub fn builtin() -> Self {
+        let mut registry = Registry::new();
+        registry.add(sugarscape::definition());
+        registry
+    }
+
+    /// Adds a model
+    /// # Arguments
+    /// * `model` - The model, usually a `Definition`. Its name must not be
+    ///   taken already
+    pub fn add<T: Model + 'static>(&mut self, model: T) {
+        assert!(
+            self.get(model.name()).is_none(),
+            "a model named `{}` is already registered",
+            model.name()
+        );
+        self.models.push(Box::new(model));
+    }
+
+    /// Gets a model by name
+    /// # Arguments
+    /// * `name` - The name the model was registered with
+    pub fn get(&self, name: &str) -> Option<&dyn Model> {
+        self.models.iter().find(|m| m.name() == name).map(|m| &**m)
+    }
+
+    /// Gets every model, in the order they were added
+    pub fn models(&self) -> Vec<&dyn Model> {
+        self.models.iter().map(|m| &**m).collect()
+    }
+
+    /// Gets the names of every model, in the order they were added
+    pub fn names(&self) -> Vec<&str> {
+        self.models.iter().map(|m| m.name()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use detect::ExtinctionDetector;
+    use observer::{Custom, EntityCount};
+    use std::{env, fs};
+    use trajectory::Trajectory;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Params {
+        walkers: usize,
+        step: f32,
+    }
+    impl Default for Params {
+        fn default() -> Self {
+            Params {
+                walkers: 2,
+                step: 1.0,
+            }
+        }
+    }
+    impl Config for Params {}
+
+    struct Walker {
+        id: u64,
+        position: Vec<f32>,
+    }
+    impl Observable for Walker {
+        fn id(&self) -> u64 {
+            self.id
+        }
+        fn position(&self) -> &[f32] {
+            &self.position
+        }
+    }
+
+    struct Walk {
+        step: f32,
+        walkers: Vec<Walker>,
+    }
+    impl World<()> for Walk {
+        fn update(&mut self) {
+            for walker in &mut self.walkers {
+                walker.position[0] += self.step;
+            }
+        }
+        fn num_entities(&self) -> usize {
+            self.walkers.len()
+        }
+        fn receive_message(&mut self, _message: ()) {}
+    }
+    impl WorldView for Walk {
+        type Entity = Walker;
+        fn entities(&self) -> &[Walker] {
+            &self.walkers
+        }
+    }
+
+    fn walk() -> Definition<Params, Walk, ()> {
+        let mut walk = Definition::new("walk", |params: &Params, seed| Walk {
+            step: params.step,
+            walkers: (0..params.walkers as u64)
+                .map(|id| Walker {
+                    id,
+                    position: vec![seed as f32, 0.0],
+                })
+                .collect(),
+        });
+        walk.set_description("Walkers moving right");
+        walk.set_metrics(|_| {
+            let mut metrics = Metrics::new();
+            metrics.add_probe(EntityCount);
+            metrics.add_probe(Custom::new("x", |world: &Walk| {
+                world.walkers[0].position[0] as f64
+            }));
+            metrics
+        });
+        walk.record_trajectory(&[]);
+        walk
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = Registry::new();
+        registry.add(walk());
+        let model = registry.get("walk").unwrap();
+        assert_eq!(registry.names(), vec!["walk"]);
+        assert_eq!(model.description(), "Walkers moving right");
+        assert_eq!(model.parameters(), json!({ "walkers": 2, "step": 1.0 }));
+        assert_eq!(model.metrics(), vec!["entities", "x"]);
+        assert!(registry.get("run").is_none());
+    }
+
+    #[test]
+    fn test_builtin() {
+        let registry = Registry::builtin();
+        assert_eq!(registry.names(), vec!["sugarscape"]);
+        let model = registry.get("sugarscape").unwrap();
+        let parameters = model.resolve(json!({ "agents": 50 })).unwrap();
+        let options = RunOptions {
+            seed: 1,
+            ticks: 10,
+            out: None,
+        };
+        let values = model.run(&parameters, &options).unwrap();
+        assert_eq!(values[0], ("entities".to_string(), 50.0));
+        assert!(values.iter().any(|(name, _)| name == "wealth_gini"));
+    }
+
+    #[test]
+    #[should_panic(expected = "a model named `walk` is already registered")]
+    fn test_names_are_unique() {
+        let mut registry = Registry::new();
+        registry.add(walk());
+        registry.add(walk());
+    }
+
+    #[test]
+    fn test_run_writes_outputs() {
+        let dir = env::temp_dir().join("sekai_test_run_writes_outputs");
+        fs::create_dir_all(&dir).unwrap();
+        let model = walk();
+        let parameters = model.resolve(json!({ "step": 0.5 })).unwrap();
+        let options = RunOptions {
+            seed: 3,
+            ticks: 4,
+            out: Some(dir.clone()),
+        };
+        let values = model.run(&parameters, &options).unwrap();
+        assert_eq!(
+            values,
+            vec![("entities".to_string(), 2.0), ("x".to_string(), 5.0)]
+        );
+
+        let csv = fs::read_to_string(dir.join("metrics.csv")).unwrap();
+        assert!(csv.contains("# model: walk\n# seed: 3\n"));
+        let trajectory = Trajectory::open(dir.join("trajectory.trj")).unwrap();
+        assert_eq!(trajectory.frames.len(), 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_stops_early() {
+        let dir = env::temp_dir().join("sekai_test_run_stops_early");
+        fs::create_dir_all(&dir).unwrap();
+        let mut model = walk();
+        // walkers leave once they pass x = 5
+        model.stop_when(|_| {
+            let leaving = ExtinctionDetector::new(|world: &Walk| {
+                world.walkers.iter().filter(|w| w.position[0] < 5.0).count()
+            });
+            vec![Box::new(leaving) as Box<dyn Detector<Walk>>]
+        });
+        assert_eq!(model.metrics(), vec!["entities", "x", "ticks"]);
+
+        let options = RunOptions {
+            seed: 3,
+            ticks: 100,
+            out: Some(dir.clone()),
+        };
+        let values = model.run(&model.parameters(), &options).unwrap();
+        assert_eq!(values[2], ("ticks".to_string(), 2.0));
+        let termination = fs::read_to_string(dir.join("termination.txt")).unwrap();
+        assert_eq!(termination, "extinction at tick 2\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
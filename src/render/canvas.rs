@@ -0,0 +1,370 @@
+use std::io::{self, Write};
+
+use png;
+
+use super::{Rgb, Scene, Shape};
+
+/// An image held in memory, drawn in software
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<Rgb>,
+}
+
+impl Canvas {
+    /// Creates a canvas filled with one color
+    /// # Arguments
+    /// * `width` - Width in pixels
+    /// * `height` - Height in pixels
+    /// * `background` - The color of every pixel
+    pub fn new(width: u32, height: u32, background: Rgb) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: vec![background; width as usize * height as usize],
+        }
+    }
+
+    /// Draws a scene
+    /// # Arguments
+    /// * `scene` - The scene to draw
+    pub fn render(scene: &Scene) -> Self {
+        let mut canvas = Canvas::new(scene.width, scene.height, scene.background);
+        for shape in &scene.shapes {
+            canvas.draw(scene, shape);
+        }
+        canvas
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Gets every pixel, row by row from the top left
+    pub fn pixels(&self) -> &[Rgb] {
+        &self.pixels
+    }
+
+    /// Gets the color of a pixel
+    pub fn get(&self, x: u32, y: u32) -> Option<Rgb> {
+        if x < self.width && y < self.height {
+            Some(self.pixels[y as usize * self.width as usize + x as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Sets the color of a pixel, ignoring pixels outside the canvas
+    pub fn set(&mut self, x: i64, y: i64, color: Rgb) {
+        if x >= 0 && y >= 0 && x < self.width as i64 && y < self.height as i64 {
+            self.pixels[y as usize * self.width as usize + x as usize] = color;
+        }
+    }
+
+    /// Gets the pixels as packed 8-bit RGB triples
+    pub fn to_rgb_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 3);
+        for pixel in &self.pixels {
+            bytes.extend_from_slice(&[pixel.r, pixel.g, pixel.b]);
+        }
+        bytes
+    }
+
+    /// Writes the canvas as a binary PPM (P6) image
+    /// # Arguments
+    /// * `out` - Where to write the image
+    pub fn write_ppm<W: Write>(&self, mut out: W) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.to_rgb_bytes())?;
+        out.flush()
+    }
+
+    /// Writes the canvas as a PNG image
+    /// # Arguments
+    /// * `out` - Where to write the image
+    pub fn write_png<W: Write>(&self, out: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer
+            .write_image_data(&self.to_rgb_bytes())
+            .map_err(png_error)?;
+        writer.finish().map_err(png_error)
+    }
+
+    fn draw(&mut self, scene: &Scene, shape: &Shape) {
+        match *shape {
+            Shape::Point {
+                x,
+                y,
+                radius,
+                color,
+            } => {
+                let (px, py) = scene.to_pixel(x, y);
+                self.fill_circle(px, py, radius, color);
+            }
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+                color,
+            } => {
+                let (x0, y0) = scene.to_pixel(x, y);
+                let (x1, y1) = scene.to_pixel(x + width, y + height);
+                for py in pixel_range(y0, y1) {
+                    for px in pixel_range(x0, x1) {
+                        self.set(px, py, color);
+                    }
+                }
+            }
+            Shape::Raster {
+                x,
+                y,
+                width,
+                height,
+                cols,
+                rows,
+                ref colors,
+            } => {
+                let (x0, y0) = scene.to_pixel(x, y);
+                let (x1, y1) = scene.to_pixel(x + width, y + height);
+                // sample the cell under the center of every covered pixel
+                for py in pixel_range(y0, y1) {
+                    let row = ((py as f32 + 0.5 - y0) / (y1 - y0) * rows as f32) as usize;
+                    for px in pixel_range(x0, x1) {
+                        let col = ((px as f32 + 0.5 - x0) / (x1 - x0) * cols as f32) as usize;
+                        if row < rows && col < cols {
+                            self.set(px, py, colors[row * cols + col]);
+                        }
+                    }
+                }
+            }
+            Shape::Polyline { ref points, color } => {
+                for segment in points.windows(2) {
+                    let (x0, y0) = scene.to_pixel(segment[0].0, segment[0].1);
+                    let (x1, y1) = scene.to_pixel(segment[1].0, segment[1].1);
+                    self.draw_line(x0, y0, x1, y1, color);
+                }
+            }
+        }
+    }
+
+    /// Fills every pixel whose center lies within the circle. Circles
+    /// smaller than a pixel still color the pixel they fall in
+    fn fill_circle(&mut self, cx: f32, cy: f32, radius: f32, color: Rgb) {
+        if radius <= 0.5 {
+            self.set(cx.floor() as i64, cy.floor() as i64, color);
+            return;
+        }
+        for py in pixel_range(cy - radius, cy + radius) {
+            for px in pixel_range(cx - radius, cx + radius) {
+                let dx = px as f32 + 0.5 - cx;
+                let dy = py as f32 + 0.5 - cy;
+                if dx * dx + dy * dy <= radius * radius {
+                    self.set(px, py, color);
+                }
+            }
+        }
+    }
+
+    /// Draws a one pixel wide line with Bresenham's algorithm, after
+    /// clipping it to the canvas so far off lines take no longer to draw
+    fn draw_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, color: Rgb) {
+        let (width, height) = (f64::from(self.width), f64::from(self.height));
+        let (x0, y0, x1, y1) = match clip_line(x0, y0, x1, y1, width, height) {
+            Some(clipped) => clipped,
+            None => return,
+        };
+        let (mut x, mut y) = (x0.floor() as i64, y0.floor() as i64);
+        let (x1, y1) = (x1.floor() as i64, y1.floor() as i64);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let sx = if x < x1 { 1 } else { -1 };
+        let sy = if y < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        loop {
+            self.set(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+}
+
+/// Clips a line to the rectangle from the origin to `(width, height)` with
+/// the Cohen-Sutherland algorithm, giving None if none of it is inside.
+/// Lines with a coordinate which is infinite or NaN are not drawn at all
+fn clip_line(
+    x0: f32,
+    y0: f32,
+    x1: f32,
+    y1: f32,
+    width: f64,
+    height: f64,
+) -> Option<(f64, f64, f64, f64)> {
+    if ![x0, y0, x1, y1].iter().all(|c| c.is_finite()) {
+        return None;
+    }
+    const LEFT: u8 = 1;
+    const RIGHT: u8 = 2;
+    const TOP: u8 = 4;
+    const BOTTOM: u8 = 8;
+    let outside = |x: f64, y: f64| {
+        let mut code = 0;
+        if x < 0.0 {
+            code |= LEFT;
+        } else if x > width {
+            code |= RIGHT;
+        }
+        if y < 0.0 {
+            code |= TOP;
+        } else if y > height {
+            code |= BOTTOM;
+        }
+        code
+    };
+    let (mut x0, mut y0) = (f64::from(x0), f64::from(y0));
+    let (mut x1, mut y1) = (f64::from(x1), f64::from(y1));
+    let (mut code0, mut code1) = (outside(x0, y0), outside(x1, y1));
+    loop {
+        if code0 | code1 == 0 {
+            return Some((x0, y0, x1, y1));
+        }
+        if code0 & code1 != 0 {
+            return None;
+        }
+        // move an outside end onto the edge it is beyond, exactly on the
+        // edge so far away ends lose no precision
+        let code = if code0 != 0 { code0 } else { code1 };
+        let (x, y) = if code & (LEFT | RIGHT) != 0 {
+            let x = if code & LEFT != 0 { 0.0 } else { width };
+            (x, y0 + (y1 - y0) * (x - x0) / (x1 - x0))
+        } else {
+            let y = if code & TOP != 0 { 0.0 } else { height };
+            (x0 + (x1 - x0) * (y - y0) / (y1 - y0), y)
+        };
+        if code == code0 {
+            x0 = x;
+            y0 = y;
+            code0 = outside(x, y);
+        } else {
+            x1 = x;
+            y1 = y;
+            code1 = outside(x, y);
+        }
+    }
+}
+
+/// Gets the pixels whose centers lie between two fractional coordinates
+fn pixel_range(from: f32, to: f32) -> ::std::ops::Range<i64> {
+    let (from, to) = if from <= to { (from, to) } else { (to, from) };
+    (from - 0.5).ceil() as i64..(to - 0.5).ceil() as i64
+}
+
+fn png_error(e: png::EncodingError) -> io::Error {
+    match e {
+        png::EncodingError::IoError(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::Bounds;
+
+    fn scene() -> Scene {
+        Scene::new(4, 4, Bounds::new(0.0, 0.0, 4.0, 4.0))
+    }
+
+    #[test]
+    fn test_grid_fills_cells() {
+        let mut scene = Scene::new(4, 2, Bounds::new(0.0, 0.0, 2.0, 1.0));
+        scene.add_grid(
+            2,
+            1,
+            |col, _row| if col == 0 { Rgb::WHITE } else { Rgb::BLACK },
+        );
+        let canvas = Canvas::render(&scene);
+        assert_eq!(canvas.get(0, 0), Some(Rgb::WHITE));
+        assert_eq!(canvas.get(1, 1), Some(Rgb::WHITE));
+        assert_eq!(canvas.get(2, 0), Some(Rgb::BLACK));
+        assert_eq!(canvas.get(3, 1), Some(Rgb::BLACK));
+    }
+
+    #[test]
+    fn test_point_and_line() {
+        let mut scene = scene();
+        let red = Rgb::new(255, 0, 0);
+        scene.add(Shape::Point {
+            x: 0.5,
+            y: 0.5,
+            radius: 0.1,
+            color: red,
+        });
+        scene.add(Shape::Polyline {
+            points: vec![(0.5, 3.5), (3.5, 3.5)],
+            color: Rgb::WHITE,
+        });
+        let canvas = Canvas::render(&scene);
+        assert_eq!(canvas.get(0, 0), Some(red));
+        assert_eq!(canvas.get(1, 0), Some(Rgb::BLACK));
+        assert!((0..4).all(|x| canvas.get(x, 3) == Some(Rgb::WHITE)));
+    }
+
+    #[test]
+    fn test_far_lines_are_clipped() {
+        let mut scene = scene();
+        scene.add(Shape::Polyline {
+            points: vec![(-1e30, 1.5), (1e30, 1.5)],
+            color: Rgb::WHITE,
+        });
+        scene.add(Shape::Polyline {
+            points: vec![(0.5, 0.5), (f32::INFINITY, 0.5), (9.0, 9.0)],
+            color: Rgb::WHITE,
+        });
+        let canvas = Canvas::render(&scene);
+        assert!((0..4).all(|x| canvas.get(x, 1) == Some(Rgb::WHITE)));
+        assert!((0..4).all(|x| canvas.get(x, 0) == Some(Rgb::BLACK)));
+    }
+
+    #[test]
+    fn test_write_ppm() {
+        let mut canvas = Canvas::new(2, 1, Rgb::BLACK);
+        canvas.set(1, 0, Rgb::new(1, 2, 3));
+        let mut ppm = Vec::new();
+        canvas.write_ppm(&mut ppm).unwrap();
+        assert_eq!(ppm, b"P6\n2 1\n255\n\x00\x00\x00\x01\x02\x03".to_vec());
+    }
+
+    #[test]
+    fn test_write_png() {
+        let mut png_bytes = Vec::new();
+        Canvas::new(3, 2, Rgb::WHITE)
+            .write_png(&mut png_bytes)
+            .unwrap();
+
+        let decoder = png::Decoder::new(&png_bytes[..]);
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (3, 2));
+        assert!(pixels[..info.buffer_size()].iter().all(|&p| p == 255));
+    }
+}
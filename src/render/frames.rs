@@ -0,0 +1,132 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use observer::Observer;
+
+use super::{write_svg, Canvas, Scene};
+
+/// Image file formats a `FrameRenderer` can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+    Svg,
+}
+
+impl ImageFormat {
+    /// Gets the file extension of the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Svg => "svg",
+        }
+    }
+
+    /// Draws a scene to a file in this format
+    /// # Arguments
+    /// * `scene` - The scene to draw
+    /// * `path` - Where to write the image
+    pub fn save<P: AsRef<Path>>(self, scene: &Scene, path: P) -> io::Result<()> {
+        let out = BufWriter::new(File::create(path)?);
+        match self {
+            ImageFormat::Png => Canvas::render(scene).write_png(out),
+            ImageFormat::Ppm => Canvas::render(scene).write_ppm(out),
+            ImageFormat::Svg => write_svg(scene, out),
+        }
+    }
+}
+
+/// Observer which renders the world after every tick and writes one image
+/// per tick, named `frame_000001.png` and so on
+/// # Arguments
+/// * `F` - Describes the scene to draw for a world
+pub struct FrameRenderer<F> {
+    dir: PathBuf,
+    format: ImageFormat,
+    draw: F,
+    error: Option<io::Error>,
+}
+
+impl<F> FrameRenderer<F> {
+    /// Creates a renderer, creating the output directory if needed
+    /// # Arguments
+    /// * `dir` - Where to write the frames
+    /// * `format` - The image format of the frames
+    /// * `draw` - Describes the scene to draw for a world
+    pub fn new<P: AsRef<Path>>(dir: P, format: ImageFormat, draw: F) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(FrameRenderer {
+            dir: dir.as_ref().to_path_buf(),
+            format,
+            draw,
+            error: None,
+        })
+    }
+
+    /// Gets the path of the frame for a tick
+    /// # Arguments
+    /// * `tick` - The tick of the frame
+    pub fn frame_path(&self, tick: u64) -> PathBuf {
+        self.dir
+            .join(format!("frame_{:06}.{}", tick, self.format.extension()))
+    }
+
+    /// Ends rendering, returning the first error hit while observing
+    pub fn finish(self) -> io::Result<()> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: ?Sized, F: FnMut(&W) -> Scene> Observer<W> for FrameRenderer<F> {
+    fn observe(&mut self, tick: u64, world: &W) {
+        if self.error.is_some() {
+            return;
+        }
+        let scene = (self.draw)(world);
+        if let Err(e) = self.format.save(&scene, self.frame_path(tick)) {
+            self.error = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::{Bounds, Rgb};
+
+    #[test]
+    fn test_writes_one_frame_per_tick() {
+        let dir = ::std::env::temp_dir().join("sekai_test_writes_one_frame_per_tick");
+        let mut renderer = FrameRenderer::new(&dir, ImageFormat::Ppm, |cells: &Vec<bool>| {
+            let mut scene = Scene::new(
+                cells.len() as u32,
+                1,
+                Bounds::new(0.0, 0.0, cells.len() as f32, 1.0),
+            );
+            scene.add_grid(cells.len(), 1, |col, _| {
+                if cells[col] {
+                    Rgb::WHITE
+                } else {
+                    Rgb::BLACK
+                }
+            });
+            scene
+        })
+        .unwrap();
+        renderer.observe(1, &vec![true, false]);
+        renderer.observe(2, &vec![false, true]);
+        let second = renderer.frame_path(2);
+        renderer.finish().unwrap();
+
+        assert_eq!(second, dir.join("frame_000002.ppm"));
+        let ppm = fs::read(&second).unwrap();
+        assert_eq!(ppm, b"P6\n2 1\n255\n\x00\x00\x00\xff\xff\xff".to_vec());
+        assert!(dir.join("frame_000001.ppm").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use gif;
+
+use observer::Observer;
+
+use super::{Canvas, Scene};
+
+/// Encodes a sequence of canvases as an animated GIF which loops forever.
+/// Each frame is reduced to its own 256 color palette
+pub struct GifEncoder<W: Write> {
+    encoder: gif::Encoder<W>,
+    width: u16,
+    height: u16,
+    delay: u16,
+}
+
+impl GifEncoder<BufWriter<File>> {
+    /// Creates a GIF file, truncating any existing file
+    /// # Arguments
+    /// * `path` - Where to write the animation
+    /// * `width` - Width of every frame in pixels
+    /// * `height` - Height of every frame in pixels
+    /// * `delay_ms` - How long each frame is shown, in milliseconds
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        width: u32,
+        height: u32,
+        delay_ms: u32,
+    ) -> io::Result<Self> {
+        GifEncoder::new(BufWriter::new(File::create(path)?), width, height, delay_ms)
+    }
+}
+
+impl<W: Write> GifEncoder<W> {
+    /// Creates an animation over any output
+    /// # Arguments
+    /// * `out` - Where to write the animation
+    /// * `width` - Width of every frame in pixels
+    /// * `height` - Height of every frame in pixels
+    /// * `delay_ms` - How long each frame is shown, in milliseconds. GIF
+    ///   stores delays in hundredths of a second, so this is rounded
+    pub fn new(out: W, width: u32, height: u32, delay_ms: u32) -> io::Result<Self> {
+        if width > u16::MAX as u32 || height > u16::MAX as u32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "GIF frames are at most 65535 pixels wide and high",
+            ));
+        }
+        let (width, height) = (width as u16, height as u16);
+        let mut encoder = gif::Encoder::new(out, width, height, &[]).map_err(gif_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(gif_error)?;
+        Ok(GifEncoder {
+            encoder,
+            width,
+            height,
+            delay: ((delay_ms + 5) / 10).min(u16::MAX as u32) as u16,
+        })
+    }
+
+    /// Appends a frame to the animation
+    /// # Arguments
+    /// * `canvas` - The frame, which must match the size of the animation
+    pub fn add_frame(&mut self, canvas: &Canvas) -> io::Result<()> {
+        if canvas.width() != self.width as u32 || canvas.height() != self.height as u32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame size does not match the animation",
+            ));
+        }
+        let mut frame =
+            gif::Frame::from_rgb_speed(self.width, self.height, &canvas.to_rgb_bytes(), 10);
+        frame.delay = self.delay;
+        self.encoder.write_frame(&frame).map_err(gif_error)
+    }
+
+    /// Ends the animation, giving back the output
+    pub fn finish(self) -> io::Result<W> {
+        self.encoder.into_inner()
+    }
+}
+
+/// Observer which renders the world after every tick into one animated GIF
+/// # Arguments
+/// * `F` - Describes the scene to draw for a world
+pub struct GifRenderer<W: Write, F> {
+    encoder: GifEncoder<W>,
+    draw: F,
+    error: Option<io::Error>,
+}
+
+impl<W: Write, F> GifRenderer<W, F> {
+    /// Creates a renderer. Every scene must match the size of the encoder
+    /// # Arguments
+    /// * `encoder` - The animation to append frames to
+    /// * `draw` - Describes the scene to draw for a world
+    pub fn new(encoder: GifEncoder<W>, draw: F) -> Self {
+        GifRenderer {
+            encoder,
+            draw,
+            error: None,
+        }
+    }
+
+    /// Ends the animation, returning the output or the first error hit
+    /// while observing
+    pub fn finish(self) -> io::Result<W> {
+        match self.error {
+            Some(e) => Err(e),
+            None => self.encoder.finish(),
+        }
+    }
+}
+
+impl<V: ?Sized, W: Write, F: FnMut(&V) -> Scene> Observer<V> for GifRenderer<W, F> {
+    fn observe(&mut self, _tick: u64, world: &V) {
+        if self.error.is_some() {
+            return;
+        }
+        let canvas = Canvas::render(&(self.draw)(world));
+        if let Err(e) = self.encoder.add_frame(&canvas) {
+            self.error = Some(e);
+        }
+    }
+}
+
+fn gif_error(e: gif::EncodingError) -> io::Error {
+    match e {
+        gif::EncodingError::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::{Bounds, Rgb};
+
+    #[test]
+    fn test_animation_round_trip() {
+        let mut renderer = GifRenderer::new(
+            GifEncoder::new(Vec::new(), 4, 2, 100).unwrap(),
+            |lit: &usize| {
+                let mut scene = Scene::new(4, 2, Bounds::new(0.0, 0.0, 4.0, 2.0));
+                scene.add_grid(
+                    4,
+                    2,
+                    |col, _| if col == *lit { Rgb::WHITE } else { Rgb::BLACK },
+                );
+                scene
+            },
+        );
+        for (tick, lit) in (0..3).enumerate() {
+            renderer.observe(tick as u64, &lit);
+        }
+        let bytes = renderer.finish().unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(&bytes[..]).unwrap();
+        let mut lit_columns = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 10);
+            let lit = (0..4).find(|col| frame.buffer[col * 4] == 255).unwrap();
+            lit_columns.push(lit);
+        }
+        assert_eq!(lit_columns, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_frame_size_must_match() {
+        let mut encoder = GifEncoder::new(Vec::new(), 4, 2, 100).unwrap();
+        assert!(encoder.add_frame(&Canvas::new(3, 2, Rgb::BLACK)).is_err());
+    }
+}
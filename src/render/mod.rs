@@ -0,0 +1,446 @@
+//! Headless rendering of 2D worlds.
+//!
+//! Drawing happens in two steps: a `Scene` describes what to draw in world
+//! coordinates, then an output format such as a `Canvas`, `GifEncoder` or
+//! `to_svg` draws it. World coordinates map onto the image with x growing
+//! to the right and y growing downwards, so a grid's row 0 is at the top.
+
+mod canvas;
+mod frames;
+mod gif;
+mod svg;
+
+pub use self::canvas::Canvas;
+pub use self::frames::{FrameRenderer, ImageFormat};
+pub use self::gif::{GifEncoder, GifRenderer};
+pub use self::svg::{to_svg, write_svg};
+
+use std::collections::BTreeMap;
+
+use entity::Observable;
+use trajectory::Trajectory;
+use world::WorldView;
+
+/// A 24-bit color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+    pub const WHITE: Rgb = Rgb {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r, g, b }
+    }
+
+    /// Mixes two colors
+    /// # Arguments
+    /// * `other` - The color to mix towards
+    /// * `t` - How much of `other` to use, from 0 to 1
+    pub fn lerp(self, other: Rgb, t: f64) -> Rgb {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Rgb::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+}
+
+/// Maps a value from 0 to 1 onto a perceptually ordered palette running from
+/// dark purple through teal to yellow. Values outside the range are clamped
+/// # Arguments
+/// * `t` - The value to color
+pub fn colormap(t: f64) -> Rgb {
+    const STOPS: [Rgb; 5] = [
+        Rgb { r: 68, g: 1, b: 84 },
+        Rgb {
+            r: 59,
+            g: 82,
+            b: 139,
+        },
+        Rgb {
+            r: 33,
+            g: 145,
+            b: 140,
+        },
+        Rgb {
+            r: 94,
+            g: 201,
+            b: 98,
+        },
+        Rgb {
+            r: 253,
+            g: 231,
+            b: 37,
+        },
+    ];
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let scaled = t * (STOPS.len() - 1) as f64;
+    let i = (scaled.floor() as usize).min(STOPS.len() - 2);
+    STOPS[i].lerp(STOPS[i + 1], scaled - i as f64)
+}
+
+/// The rectangle of world coordinates shown in an image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl Bounds {
+    pub fn new(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Self {
+        Bounds {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    /// Finds the smallest bounds containing every entity of a world, grown
+    /// on each side by a margin. Only the first two coordinates are used
+    /// # Arguments
+    /// * `world` - The world to fit
+    /// * `margin` - Extra space around the entities, in world units
+    pub fn around<V>(world: &V, margin: f32) -> Self
+    where
+        V: WorldView + ?Sized,
+        V::Entity: Observable,
+    {
+        let mut bounds = Bounds::new(
+            f32::INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NEG_INFINITY,
+        );
+        for entity in world.entities() {
+            let (x, y) = xy(entity.position());
+            bounds.min_x = bounds.min_x.min(x);
+            bounds.min_y = bounds.min_y.min(y);
+            bounds.max_x = bounds.max_x.max(x);
+            bounds.max_y = bounds.max_y.max(y);
+        }
+        if bounds.min_x > bounds.max_x {
+            bounds = Bounds::new(0.0, 0.0, 0.0, 0.0);
+        }
+        Bounds::new(
+            bounds.min_x - margin,
+            bounds.min_y - margin,
+            bounds.max_x + margin,
+            bounds.max_y + margin,
+        )
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f32 {
+        self.max_y - self.min_y
+    }
+}
+
+/// Something drawn in a scene, in world coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// A filled circle whose radius is in pixels, so agents stay visible
+    /// however far the view is zoomed out
+    Point {
+        x: f32,
+        y: f32,
+        radius: f32,
+        color: Rgb,
+    },
+    /// A filled rectangle whose top left corner is at `(x, y)`
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: Rgb,
+    },
+    /// A grid of colored cells, stored row by row, stretched over a
+    /// rectangle. Used for grid worlds and for heatmaps of scalar fields
+    Raster {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        cols: usize,
+        rows: usize,
+        colors: Vec<Rgb>,
+    },
+    /// Connected line segments, e.g. the trail of an agent
+    Polyline { points: Vec<(f32, f32)>, color: Rgb },
+}
+
+/// A description of one image of a world
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene {
+    /// Width of the image in pixels
+    pub width: u32,
+    /// Height of the image in pixels
+    pub height: u32,
+    /// The world coordinates shown in the image
+    pub bounds: Bounds,
+    pub background: Rgb,
+    /// Shapes in drawing order, later shapes on top
+    pub shapes: Vec<Shape>,
+}
+
+impl Scene {
+    /// Creates an empty scene with a black background
+    /// # Arguments
+    /// * `width` - Width of the image in pixels
+    /// * `height` - Height of the image in pixels
+    /// * `bounds` - The world coordinates to show
+    pub fn new(width: u32, height: u32, bounds: Bounds) -> Self {
+        Scene {
+            width,
+            height,
+            bounds,
+            background: Rgb::BLACK,
+            shapes: Vec::new(),
+        }
+    }
+
+    /// Adds a shape on top of those already in the scene
+    pub fn add(&mut self, shape: Shape) {
+        self.shapes.push(shape);
+    }
+
+    /// Adds a point for every entity of a world, at the first two
+    /// coordinates of its position
+    /// # Arguments
+    /// * `world` - The world whose entities to draw
+    /// * `radius` - Radius of each point in pixels
+    /// * `color` - Chooses the color of an entity
+    pub fn add_points<V, F>(&mut self, world: &V, radius: f32, mut color: F)
+    where
+        V: WorldView + ?Sized,
+        V::Entity: Observable,
+        F: FnMut(&V::Entity) -> Rgb,
+    {
+        for entity in world.entities() {
+            let (x, y) = xy(entity.position());
+            self.add(Shape::Point {
+                x,
+                y,
+                radius,
+                color: color(entity),
+            });
+        }
+    }
+
+    /// Adds the path of every entity recorded in a trajectory between two
+    /// ticks, inclusive, using the first two coordinates of its position
+    /// # Arguments
+    /// * `trajectory` - The recorded run
+    /// * `from` - The first tick of the trails
+    /// * `to` - The last tick of the trails
+    /// * `color` - Chooses the color of the trail of an entity by its id
+    pub fn add_trails<F: FnMut(u64) -> Rgb>(
+        &mut self,
+        trajectory: &Trajectory,
+        from: u64,
+        to: u64,
+        mut color: F,
+    ) {
+        let mut trails: BTreeMap<u64, Vec<(f32, f32)>> = BTreeMap::new();
+        for frame in trajectory
+            .frames
+            .iter()
+            .filter(|f| f.tick >= from && f.tick <= to)
+        {
+            for entity in &frame.entities {
+                trails
+                    .entry(entity.id)
+                    .or_default()
+                    .push(xy(&entity.position));
+            }
+        }
+        for (id, points) in trails {
+            self.add(Shape::Polyline {
+                points,
+                color: color(id),
+            });
+        }
+    }
+
+    /// Adds a grid whose cell `(col, row)` covers the world rectangle from
+    /// `(col, row)` to `(col + 1, row + 1)`
+    /// # Arguments
+    /// * `cols` - Number of columns
+    /// * `rows` - Number of rows
+    /// * `color` - Chooses the color of the cell at `(col, row)`
+    pub fn add_grid<F: FnMut(usize, usize) -> Rgb>(
+        &mut self,
+        cols: usize,
+        rows: usize,
+        mut color: F,
+    ) {
+        let mut colors = Vec::with_capacity(cols * rows);
+        for row in 0..rows {
+            for col in 0..cols {
+                colors.push(color(col, row));
+            }
+        }
+        self.add(Shape::Raster {
+            x: 0.0,
+            y: 0.0,
+            width: cols as f32,
+            height: rows as f32,
+            cols,
+            rows,
+            colors,
+        });
+    }
+
+    /// Adds a heatmap of a scalar field laid out like `add_grid`
+    /// # Arguments
+    /// * `cols` - Number of columns
+    /// * `rows` - Number of rows
+    /// * `values` - The field, row by row
+    /// * `min` - The value drawn with the coldest color
+    /// * `max` - The value drawn with the hottest color
+    pub fn add_heatmap(&mut self, cols: usize, rows: usize, values: &[f64], min: f64, max: f64) {
+        assert_eq!(
+            values.len(),
+            cols * rows,
+            "heatmap needs one value per cell"
+        );
+        let range = if max > min { max - min } else { 1.0 };
+        self.add_grid(cols, rows, |col, row| {
+            colormap((values[row * cols + col] - min) / range)
+        });
+    }
+
+    /// Maps world coordinates to fractional pixel coordinates
+    pub fn to_pixel(&self, x: f32, y: f32) -> (f32, f32) {
+        let extent = |size: f32| if size > 0.0 { size } else { 1.0 };
+        (
+            (x - self.bounds.min_x) / extent(self.bounds.width()) * self.width as f32,
+            (y - self.bounds.min_y) / extent(self.bounds.height()) * self.height as f32,
+        )
+    }
+}
+
+/// Gets the first two coordinates of a position, padding with zeros
+fn xy(position: &[f32]) -> (f32, f32) {
+    (
+        position.first().cloned().unwrap_or(0.0),
+        position.get(1).cloned().unwrap_or(0.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Dot {
+        pos: Vec<f32>,
+    }
+    impl Observable for Dot {
+        fn id(&self) -> u64 {
+            0
+        }
+        fn position(&self) -> &[f32] {
+            &self.pos
+        }
+    }
+    struct Dots {
+        dots: Vec<Dot>,
+    }
+    impl WorldView for Dots {
+        type Entity = Dot;
+        fn entities(&self) -> &[Dot] {
+            &self.dots
+        }
+    }
+
+    #[test]
+    fn test_colormap() {
+        assert_eq!(colormap(0.0), Rgb::new(68, 1, 84));
+        assert_eq!(colormap(0.5), Rgb::new(33, 145, 140));
+        assert_eq!(colormap(1.0), Rgb::new(253, 231, 37));
+        // out of range values are clamped, and NaN is the coldest
+        assert_eq!(colormap(-1.0), colormap(0.0));
+        assert_eq!(colormap(2.0), colormap(1.0));
+        assert_eq!(colormap(f64::NAN), colormap(0.0));
+        // halfway between two stops mixes them
+        assert_eq!(colormap(0.125), Rgb::new(64, 42, 112));
+    }
+
+    #[test]
+    fn test_bounds_around() {
+        let world = Dots {
+            dots: vec![
+                Dot {
+                    pos: vec![1.0, 2.0],
+                },
+                Dot {
+                    pos: vec![-3.0, 5.0],
+                },
+                Dot { pos: vec![0.5] },
+            ],
+        };
+        let bounds = Bounds::around(&world, 1.0);
+        assert_eq!(bounds, Bounds::new(-4.0, -1.0, 2.0, 6.0));
+        assert_eq!((bounds.width(), bounds.height()), (6.0, 7.0));
+
+        let empty = Dots { dots: Vec::new() };
+        assert_eq!(
+            Bounds::around(&empty, 1.0),
+            Bounds::new(-1.0, -1.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_to_pixel() {
+        let scene = Scene::new(100, 50, Bounds::new(-1.0, -1.0, 1.0, 1.0));
+        assert_eq!(scene.to_pixel(-1.0, -1.0), (0.0, 0.0));
+        assert_eq!(scene.to_pixel(0.0, 0.0), (50.0, 25.0));
+        assert_eq!(scene.to_pixel(1.0, 1.0), (100.0, 50.0));
+
+        // bounds without extent still map, as if they were one unit wide
+        let flat = Scene::new(10, 10, Bounds::new(2.0, 2.0, 2.0, 2.0));
+        assert_eq!(flat.to_pixel(2.5, 2.0), (5.0, 0.0));
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let mut scene = Scene::new(2, 2, Bounds::new(0.0, 0.0, 2.0, 1.0));
+        scene.add_heatmap(2, 1, &[10.0, 20.0], 10.0, 20.0);
+        scene.add_heatmap(2, 1, &[3.0, 3.0], 3.0, 3.0);
+        let colors: Vec<&Vec<Rgb>> = scene
+            .shapes
+            .iter()
+            .map(|shape| match *shape {
+                Shape::Raster { ref colors, .. } => colors,
+                _ => panic!("a heatmap is a raster"),
+            })
+            .collect();
+        assert_eq!(colors[0], &vec![colormap(0.0), colormap(1.0)]);
+        // with no range, every value is drawn coldest
+        assert_eq!(colors[1], &vec![colormap(0.0), colormap(0.0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "one value per cell")]
+    fn test_heatmap_needs_every_value() {
+        let mut scene = Scene::new(2, 2, Bounds::new(0.0, 0.0, 2.0, 2.0));
+        scene.add_heatmap(2, 2, &[0.0; 3], 0.0, 1.0);
+    }
+}
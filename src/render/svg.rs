@@ -0,0 +1,176 @@
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+
+use super::{Rgb, Scene, Shape};
+
+/// Describes a scene as an SVG document. Rasters become one rectangle per
+/// run of equally colored cells in a row, so everything stays vector output
+/// # Arguments
+/// * `scene` - The scene to describe
+pub fn to_svg(scene: &Scene) -> String {
+    let mut svg = String::new();
+    // writing to a String cannot fail
+    let _ = write_shapes(&mut svg, scene);
+    svg
+}
+
+/// Writes a scene as an SVG document
+/// # Arguments
+/// * `scene` - The scene to write
+/// * `out` - Where to write the document
+pub fn write_svg<W: Write>(scene: &Scene, mut out: W) -> io::Result<()> {
+    out.write_all(to_svg(scene).as_bytes())?;
+    out.flush()
+}
+
+fn write_shapes(svg: &mut String, scene: &Scene) -> ::std::fmt::Result {
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = scene.width,
+        h = scene.height
+    )?;
+    writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        hex(scene.background)
+    )?;
+    for shape in &scene.shapes {
+        match *shape {
+            Shape::Point {
+                x,
+                y,
+                radius,
+                color,
+            } => {
+                let (cx, cy) = scene.to_pixel(x, y);
+                writeln!(
+                    svg,
+                    r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+                    cx,
+                    cy,
+                    radius.max(0.5),
+                    hex(color)
+                )?;
+            }
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+                color,
+            } => {
+                let (x0, y0) = scene.to_pixel(x, y);
+                let (x1, y1) = scene.to_pixel(x + width, y + height);
+                write_rect(svg, x0, y0, x1 - x0, y1 - y0, color)?;
+            }
+            Shape::Raster {
+                x,
+                y,
+                width,
+                height,
+                cols,
+                rows,
+                ref colors,
+            } => {
+                let cell_width = width / cols as f32;
+                let cell_height = height / rows as f32;
+                for row in 0..rows {
+                    let mut start = 0;
+                    while start < cols {
+                        let color = colors[row * cols + start];
+                        let mut end = start + 1;
+                        while end < cols && colors[row * cols + end] == color {
+                            end += 1;
+                        }
+                        let (x0, y0) = scene
+                            .to_pixel(x + start as f32 * cell_width, y + row as f32 * cell_height);
+                        let (x1, y1) = scene.to_pixel(
+                            x + end as f32 * cell_width,
+                            y + (row + 1) as f32 * cell_height,
+                        );
+                        write_rect(svg, x0, y0, x1 - x0, y1 - y0, color)?;
+                        start = end;
+                    }
+                }
+            }
+            Shape::Polyline { ref points, color } => {
+                let points: Vec<String> = points
+                    .iter()
+                    .map(|&(x, y)| {
+                        let (px, py) = scene.to_pixel(x, y);
+                        format!("{},{}", px, py)
+                    })
+                    .collect();
+                writeln!(
+                    svg,
+                    r#"<polyline points="{}" fill="none" stroke="{}"/>"#,
+                    points.join(" "),
+                    hex(color)
+                )?;
+            }
+        }
+    }
+    writeln!(svg, "</svg>")
+}
+
+fn write_rect(
+    svg: &mut String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    color: Rgb,
+) -> ::std::fmt::Result {
+    writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+        x,
+        y,
+        width,
+        height,
+        hex(color)
+    )
+}
+
+fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::Bounds;
+
+    #[test]
+    fn test_svg_shapes() {
+        let mut scene = Scene::new(20, 10, Bounds::new(0.0, 0.0, 4.0, 2.0));
+        scene.add_grid(4, 1, |col, _| if col < 3 { Rgb::WHITE } else { Rgb::BLACK });
+        scene.add(Shape::Point {
+            x: 1.0,
+            y: 1.0,
+            radius: 2.0,
+            color: Rgb::new(255, 0, 0),
+        });
+        scene.add(Shape::Polyline {
+            points: vec![(0.0, 2.0), (4.0, 0.0)],
+            color: Rgb::new(0, 255, 0),
+        });
+        let svg = to_svg(&scene);
+
+        let lines: Vec<&str> = svg.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10" viewBox="0 0 20 10">"#,
+                r##"<rect width="100%" height="100%" fill="#000000"/>"##,
+                // the three white cells are merged into one rectangle
+                r##"<rect x="0" y="0" width="15" height="5" fill="#ffffff"/>"##,
+                r##"<rect x="15" y="0" width="5" height="5" fill="#000000"/>"##,
+                r##"<circle cx="5" cy="5" r="2" fill="#ff0000"/>"##,
+                r##"<polyline points="0,10 20,0" fill="none" stroke="#00ff00"/>"##,
+                "</svg>",
+            ]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use std::io;
+use std::path::Path;
+use std::slice;
+use std::sync::Arc;
+
+use entity::Observable;
+use observer::Observer;
+use trajectory::{EntityState, Trajectory};
+use world::WorldView;
+
+/// An entity as it was recorded in a trajectory
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedEntity {
+    state: EntityState,
+    fields: Arc<Vec<String>>,
+}
+
+impl RecordedEntity {
+    /// Gets the raw recorded state
+    pub fn state(&self) -> &EntityState {
+        &self.state
+    }
+}
+
+impl Observable for RecordedEntity {
+    fn id(&self) -> u64 {
+        self.state.id
+    }
+    fn position(&self) -> &[f32] {
+        &self.state.position
+    }
+    // Fields which were recorded as NaN, because the live entity did not
+    // have them, are reported as missing again
+    fn field(&self, name: &str) -> Option<f64> {
+        self.fields
+            .iter()
+            .position(|f| f == name)
+            .and_then(|i| self.state.fields.get(i).cloned())
+            .filter(|value| !value.is_nan())
+    }
+}
+
+/// One recorded tick, presented as a read-only world
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedWorld {
+    tick: u64,
+    entities: Vec<RecordedEntity>,
+}
+
+impl RecordedWorld {
+    /// Gets the tick this world was recorded at
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+}
+
+impl WorldView for RecordedWorld {
+    type Entity = RecordedEntity;
+    fn entities(&self) -> &[RecordedEntity] {
+        &self.entities
+    }
+}
+
+/// A recorded run which can be scrubbed through and fed to observers
+/// without re-simulating the model. Observers and probes written against
+/// `WorldView` and `Observable` work the same on a replay as on the live run
+pub struct Replay {
+    fields: Arc<Vec<String>>,
+    worlds: Vec<RecordedWorld>,
+}
+
+impl Replay {
+    /// Creates a replay of a trajectory held in memory
+    /// # Arguments
+    /// * `trajectory` - The recorded run
+    pub fn new(trajectory: Trajectory) -> Self {
+        let fields = Arc::new(trajectory.fields);
+        let worlds = trajectory
+            .frames
+            .into_iter()
+            .map(|frame| RecordedWorld {
+                tick: frame.tick,
+                entities: frame
+                    .entities
+                    .into_iter()
+                    .map(|state| RecordedEntity {
+                        state,
+                        fields: fields.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Replay { fields, worlds }
+    }
+
+    /// Creates a replay of a trajectory file
+    /// # Arguments
+    /// * `path` - The file written by a `trajectory::TrajectoryRecorder`
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Replay::new(Trajectory::open(path)?))
+    }
+
+    /// Gets the names of the recorded fields
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// Gets the number of recorded frames
+    pub fn len(&self) -> usize {
+        self.worlds.len()
+    }
+
+    /// Checks whether nothing was recorded
+    pub fn is_empty(&self) -> bool {
+        self.worlds.is_empty()
+    }
+
+    /// Gets the world recorded as the nth frame
+    /// # Arguments
+    /// * `index` - The index of the frame
+    pub fn frame(&self, index: usize) -> Option<&RecordedWorld> {
+        self.worlds.get(index)
+    }
+
+    /// Gets the world recorded at a tick
+    /// # Arguments
+    /// * `tick` - The tick to look up
+    pub fn at_tick(&self, tick: u64) -> Option<&RecordedWorld> {
+        self.worlds
+            .binary_search_by_key(&tick, |w| w.tick)
+            .ok()
+            .map(|i| &self.worlds[i])
+    }
+
+    /// Iterates over every recorded world in order
+    pub fn frames(&self) -> slice::Iter<'_, RecordedWorld> {
+        self.worlds.iter()
+    }
+
+    /// Feeds every recorded world to the observers, as a live simulation
+    /// would
+    /// # Arguments
+    /// * `observers` - The observers to call for each frame
+    pub fn run(&self, observers: &mut [&mut dyn Observer<RecordedWorld>]) {
+        self.run_between(0, u64::MAX, observers)
+    }
+
+    /// Feeds the worlds recorded between two ticks, inclusive, to the
+    /// observers
+    /// # Arguments
+    /// * `from` - The first tick to replay
+    /// * `to` - The last tick to replay
+    /// * `observers` - The observers to call for each frame
+    pub fn run_between(
+        &self,
+        from: u64,
+        to: u64,
+        observers: &mut [&mut dyn Observer<RecordedWorld>],
+    ) {
+        for world in self.frames().filter(|w| w.tick >= from && w.tick <= to) {
+            for observer in observers.iter_mut() {
+                observer.observe(world.tick, world);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use observer::{Custom, EntityCount, Metrics, ScalarStats};
+    use trajectory::TrajectoryRecorder;
+
+    struct Fish {
+        id: u64,
+        pos: Vec<f32>,
+        depth: f64,
+    }
+    impl Observable for Fish {
+        fn id(&self) -> u64 {
+            self.id
+        }
+        fn position(&self) -> &[f32] {
+            &self.pos
+        }
+        fn field(&self, name: &str) -> Option<f64> {
+            match name {
+                "depth" => Some(self.depth),
+                _ => None,
+            }
+        }
+    }
+    struct School {
+        fish: Vec<Fish>,
+    }
+    impl WorldView for School {
+        type Entity = Fish;
+        fn entities(&self) -> &[Fish] {
+            &self.fish
+        }
+    }
+
+    /// The same analysis, written once for live and recorded worlds
+    fn analysis<V>() -> Metrics<V>
+    where
+        V: WorldView + 'static,
+        V::Entity: Observable,
+    {
+        let mut metrics = Metrics::new();
+        metrics.add_probe(EntityCount);
+        metrics.add_probe(ScalarStats::new("depth", |e: &V::Entity| {
+            e.field("depth").unwrap_or(0.0)
+        }));
+        metrics.add_probe(Custom::new("spread", |world: &V| {
+            world
+                .entities()
+                .iter()
+                .map(|e| e.position()[0] as f64)
+                .fold(0.0, f64::max)
+        }));
+        metrics
+    }
+
+    #[test]
+    fn test_replay_matches_live_run() {
+        let mut school = School { fish: Vec::new() };
+        let mut live = analysis::<School>();
+        let mut recorder = TrajectoryRecorder::new(Vec::new(), &["depth"]);
+        for tick in 1..6 {
+            school.fish.push(Fish {
+                id: tick,
+                pos: vec![0.0],
+                depth: tick as f64,
+            });
+            for fish in &mut school.fish {
+                fish.pos[0] += 2.0;
+            }
+            live.observe(tick, &school);
+            recorder.observe(tick, &school);
+        }
+
+        let recorded = recorder.finish().unwrap();
+        let replay = Replay::new(Trajectory::read(&recorded[..]).unwrap());
+        let mut replayed = analysis::<RecordedWorld>();
+        replay.run(&mut [&mut replayed]);
+
+        assert_eq!(replay.len(), 5);
+        assert_eq!(replayed.ticks(), live.ticks());
+        assert_eq!(replayed.all_series(), live.all_series());
+    }
+
+    #[test]
+    fn test_scrub_through_replay() {
+        let trajectory = Trajectory {
+            fields: vec!["depth".into()],
+            frames: (1..4)
+                .map(|tick| ::trajectory::Frame {
+                    tick: tick * 10,
+                    entities: vec![EntityState {
+                        id: 0,
+                        position: vec![tick as f32],
+                        fields: vec![f64::NAN],
+                    }],
+                })
+                .collect(),
+        };
+        let replay = Replay::new(trajectory);
+
+        let world = replay.at_tick(20).unwrap();
+        assert_eq!(world.tick(), 20);
+        assert_eq!(world.entities()[0].position(), &[2.0]);
+        assert_eq!(world.entities()[0].field("depth"), None);
+        assert!(replay.at_tick(25).is_none());
+
+        let mut seen = Vec::new();
+        {
+            let mut record = |tick: u64, _world: &RecordedWorld| seen.push(tick);
+            replay.run_between(15, 30, &mut [&mut record]);
+        }
+        assert_eq!(seen, vec![20, 30]);
+
+        // a hand-built state short of values is missing the rest
+        let short = Replay::new(Trajectory {
+            fields: vec!["depth".into(), "length".into()],
+            frames: vec![::trajectory::Frame {
+                tick: 0,
+                entities: vec![EntityState {
+                    id: 0,
+                    position: vec![],
+                    fields: vec![1.0],
+                }],
+            }],
+        });
+        let fish = &short.frame(0).unwrap().entities()[0];
+        assert_eq!(fish.field("depth"), Some(1.0));
+        assert_eq!(fish.field("length"), None);
+    }
+}
@@ -0,0 +1,221 @@
+//! Activation regimes deciding in which order, and how often, the entities
+//! of a world update within a tick.
+//!
+//! Emergent behavior that only appears under one regime is usually an
+//! artifact of it, so worlds can take a `Schedule` as a parameter and
+//! update their entities through a `Scheduler`.
+
+use std::fmt;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use config::{Config, ConfigError};
+
+/// The highest rate of a Poisson schedule, as every activation of a tick is
+/// drawn up front
+pub const MAX_RATE: f64 = 1000.0;
+
+/// How the entities of a world are activated every tick
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    /// Every entity updates once, all reading the state from before the tick
+    #[default]
+    Synchronous,
+    /// Every entity updates once in storage order, seeing the updates made
+    /// before it
+    FixedOrder,
+    /// Every entity updates once in an order shuffled every tick, seeing the
+    /// updates made before it
+    RandomOrder,
+    /// Every entity updates at the times of its own Poisson process, so it
+    /// may update several times in a tick or not at all. Updates see those
+    /// made before them
+    Poisson { rate: f64 },
+}
+
+impl Config for Schedule {
+    fn validate(&self) -> Result<(), ConfigError> {
+        if let Schedule::Poisson { rate } = *self {
+            // also rejects NaN
+            if !(0.0..=MAX_RATE).contains(&rate) {
+                return Err(ConfigError::invalid(
+                    "poisson.rate",
+                    format!("must be between 0 and {}", MAX_RATE),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Activates entities following a schedule, with its own random number
+/// generator so runs repeat for a seed
+#[derive(Clone)]
+pub struct Scheduler {
+    schedule: Schedule,
+    rng: StdRng,
+}
+
+impl Scheduler {
+    /// Creates a scheduler
+    /// # Arguments
+    /// * `schedule` - How entities are activated. A Poisson rate must be
+    ///   between 0 and `MAX_RATE`
+    /// * `seed` - Seeds the random orders and times
+    pub fn new(schedule: Schedule, seed: u64) -> Self {
+        if let Err(e) = schedule.validate() {
+            panic!("invalid schedule: {}", e);
+        }
+        Scheduler {
+            schedule,
+            rng: StdRng::from_seed(&[seed as usize][..]),
+        }
+    }
+
+    /// Gets how entities are activated
+    pub fn schedule(&self) -> Schedule {
+        self.schedule
+    }
+
+    /// Draws the activations of one tick, as indices of the entities in the
+    /// order they update. Under `Synchronous` the order does not matter, as
+    /// every update should read the state from before the tick
+    /// # Arguments
+    /// * `count` - The number of entities
+    pub fn activations(&mut self, count: usize) -> Vec<usize> {
+        match self.schedule {
+            Schedule::Synchronous | Schedule::FixedOrder => (0..count).collect(),
+            Schedule::RandomOrder => {
+                let mut order: Vec<usize> = (0..count).collect();
+                self.rng.shuffle(&mut order);
+                order
+            }
+            Schedule::Poisson { rate } => {
+                let mut events = Vec::new();
+                if rate > 0.0 {
+                    for index in 0..count {
+                        let mut time = self.wait(rate);
+                        while time < 1.0 {
+                            events.push((time, index));
+                            time += self.wait(rate);
+                        }
+                    }
+                }
+                events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+                events.into_iter().map(|(_, index)| index).collect()
+            }
+        }
+    }
+
+    /// Updates entities for one tick
+    /// # Arguments
+    /// * `entities` - The entities to update
+    /// * `update` - Updates the entity at an index, given the state of every
+    ///   entity it should read: from before the tick under `Synchronous`,
+    ///   and as updated so far otherwise
+    pub fn update<E, F>(&mut self, entities: &mut [E], mut update: F)
+    where
+        E: Clone,
+        F: FnMut(usize, &mut E, &[E]),
+    {
+        let activations = self.activations(entities.len());
+        if self.schedule == Schedule::Synchronous {
+            let before = entities.to_vec();
+            for index in activations {
+                update(index, &mut entities[index], &before);
+            }
+        } else {
+            for index in activations {
+                let mut entity = entities[index].clone();
+                update(index, &mut entity, entities);
+                entities[index] = entity;
+            }
+        }
+    }
+
+    /// Draws the time until the next event of a Poisson process
+    fn wait(&mut self, rate: f64) -> f64 {
+        -(1.0 - self.rng.gen::<f64>()).ln() / rate
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("schedule", &self.schedule)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every entity copies its left neighbor
+    fn copy_left(schedule: Schedule) -> Vec<u32> {
+        let mut cells = vec![1, 0, 0, 0];
+        Scheduler::new(schedule, 0).update(&mut cells, |i, cell, cells| {
+            if i > 0 {
+                *cell = cells[i - 1];
+            }
+        });
+        cells
+    }
+
+    #[test]
+    fn test_synchronous_reads_old_state() {
+        assert_eq!(copy_left(Schedule::Synchronous), vec![1, 1, 0, 0]);
+        assert_eq!(copy_left(Schedule::FixedOrder), vec![1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_random_order() {
+        let mut scheduler = Scheduler::new(Schedule::RandomOrder, 7);
+        let first = scheduler.activations(20);
+        let second = scheduler.activations(20);
+        assert_ne!(first, second);
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+        // the same seed gives the same orders
+        assert_eq!(
+            Scheduler::new(Schedule::RandomOrder, 7).activations(20),
+            first
+        );
+    }
+
+    #[test]
+    fn test_poisson_activations() {
+        let mut scheduler = Scheduler::new(Schedule::Poisson { rate: 2.0 }, 3);
+        let ticks = 1000;
+        let total: usize = (0..ticks).map(|_| scheduler.activations(10).len()).sum();
+        // 10 entities at 2 activations per tick
+        let mean = total as f64 / ticks as f64;
+        assert!((mean - 20.0).abs() < 1.0, "mean activations {}", mean);
+        assert!(Scheduler::new(Schedule::Poisson { rate: 0.0 }, 3)
+            .activations(10)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_poisson_rate_is_checked() {
+        for &rate in &[-1.0, 1e9, f64::INFINITY, f64::NAN] {
+            let error = Schedule::Poisson { rate }.validate().unwrap_err();
+            assert_eq!(error.key(), Some("poisson.rate"));
+        }
+        assert!(Schedule::Poisson { rate: MAX_RATE }.validate().is_ok());
+        assert!(Schedule::RandomOrder.validate().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid schedule")]
+    fn test_infinite_rate_panics() {
+        Scheduler::new(
+            Schedule::Poisson {
+                rate: f64::INFINITY,
+            },
+            0,
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::marker::PhantomData;
+
+use detect::{Detector, Termination};
+use observer::Observer;
+use world::World;
+
+/// Drives a world forward one tick at a time, calling observers after each
+/// tick
+/// # Arguments
+/// * `W` - The type of world being simulated
+/// * `M` - The type of message exchanged within the world
+pub struct Simulation<W, M> {
+    world: W,
+    tick: u64,
+    termination: Option<Termination>,
+    message: PhantomData<fn(M)>,
+}
+
+impl<W: World<M>, M> Simulation<W, M> {
+    /// Creates a simulation starting at tick 0
+    /// # Arguments
+    /// * `world` - The world to simulate
+    pub fn new(world: W) -> Self {
+        Simulation {
+            world,
+            tick: 0,
+            termination: None,
+            message: PhantomData,
+        }
+    }
+
+    /// Gets the number of ticks completed so far
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Gets why the last `run_until` ended, if it has been called
+    pub fn termination(&self) -> Option<Termination> {
+        self.termination
+    }
+
+    /// Gets the world being simulated
+    pub fn world(&self) -> &W {
+        &self.world
+    }
+
+    /// Gets the world being simulated, mutably
+    pub fn world_mut(&mut self) -> &mut W {
+        &mut self.world
+    }
+
+    /// Ends the simulation, giving back the world
+    pub fn into_world(self) -> W {
+        self.world
+    }
+
+    /// Updates the world 1 tick, then calls every observer
+    /// # Arguments
+    /// * `observers` - The observers to call after the tick
+    pub fn step(&mut self, observers: &mut [&mut dyn Observer<W>]) {
+        self.world.update();
+        self.tick += 1;
+        for observer in observers.iter_mut() {
+            observer.observe(self.tick, &self.world);
+        }
+    }
+
+    /// Updates the world several ticks, calling every observer after each
+    /// # Arguments
+    /// * `ticks` - The number of ticks to run
+    /// * `observers` - The observers to call after every tick
+    pub fn run(&mut self, ticks: u64, observers: &mut [&mut dyn Observer<W>]) {
+        for _ in 0..ticks {
+            self.step(observers);
+        }
+    }
+
+    /// Updates the world up to several ticks, stopping early as soon as a
+    /// detector fires. The detectors see the world before the first tick
+    /// too, so a world that starts in a cycle has no transient
+    /// # Arguments
+    /// * `ticks` - The most ticks to run
+    /// * `observers` - The observers to call after every tick
+    /// * `detectors` - The detectors that can end the run, called after the
+    ///   observers
+    pub fn run_until(
+        &mut self,
+        ticks: u64,
+        observers: &mut [&mut dyn Observer<W>],
+        detectors: &mut [&mut dyn Detector<W>],
+    ) -> Termination {
+        let end = self.tick + ticks;
+        let mut termination = self.detect(detectors);
+        while termination.is_none() && self.tick < end {
+            self.step(observers);
+            termination = self.detect(detectors);
+        }
+        let termination = termination.unwrap_or(Termination::TickLimit { tick: self.tick });
+        self.termination = Some(termination);
+        termination
+    }
+
+    fn detect(&self, detectors: &mut [&mut dyn Detector<W>]) -> Option<Termination> {
+        for detector in detectors.iter_mut() {
+            detector.observe(self.tick, &self.world);
+        }
+        detectors.iter().filter_map(|d| d.detected()).next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use detect::{CycleDetector, ExtinctionDetector};
+    use observer::{EntityCount, Metrics};
+    use world::WorldView;
+
+    struct Hatchery {
+        eggs: Vec<u32>,
+    }
+    impl World<u32> for Hatchery {
+        fn update(&mut self) {
+            self.eggs.push(0);
+        }
+        fn num_entities(&self) -> usize {
+            self.eggs.len()
+        }
+        fn receive_message(&mut self, _message: u32) {}
+    }
+    impl WorldView for Hatchery {
+        type Entity = u32;
+        fn entities(&self) -> &[u32] {
+            &self.eggs
+        }
+    }
+
+    #[test]
+    fn test_run_observes_every_tick() {
+        let mut sim = Simulation::new(Hatchery { eggs: Vec::new() });
+        let mut metrics = Metrics::new();
+        metrics.add_probe(EntityCount);
+        let mut seen = Vec::new();
+        {
+            let mut record = |tick: u64, _world: &Hatchery| seen.push(tick);
+            sim.run(3, &mut [&mut metrics, &mut record]);
+        }
+
+        assert_eq!(sim.tick(), 3);
+        assert_eq!(seen, vec![1, 2, 3]);
+        assert_eq!(
+            metrics.series("entities").unwrap().values,
+            vec![1.0, 2.0, 3.0]
+        );
+    }
+
+    // a hatchery that loses its oldest egg every tick once it holds 3
+    struct Nest {
+        eggs: Vec<u32>,
+    }
+    impl World<u32> for Nest {
+        fn update(&mut self) {
+            if self.eggs.len() < 3 {
+                self.eggs.push(0);
+            } else {
+                self.eggs.remove(0);
+            }
+        }
+        fn num_entities(&self) -> usize {
+            self.eggs.len()
+        }
+        fn receive_message(&mut self, _message: u32) {}
+    }
+
+    #[test]
+    fn test_run_until_stops_on_detection() {
+        let mut sim = Simulation::new(Nest { eggs: Vec::new() });
+        let mut sizes = CycleDetector::new(|nest: &Nest| nest.eggs.len() as u64);
+        let termination = sim.run_until(100, &mut [], &mut [&mut sizes]);
+        // sizes go 0, 1, 2, 3, 2, 3, ...
+        assert_eq!(
+            termination,
+            Termination::Cycle {
+                tick: 4,
+                period: 2,
+                transient: 2
+            }
+        );
+        assert_eq!(sim.tick(), 4);
+        assert_eq!(sim.termination(), Some(termination));
+
+        let mut sim = Simulation::new(Hatchery { eggs: Vec::new() });
+        let mut extinction = ExtinctionDetector::entities();
+        let termination = sim.run_until(4, &mut [], &mut [&mut extinction]);
+        assert_eq!(termination, Termination::Extinction { tick: 0 });
+        assert_eq!(sim.tick(), 0);
+    }
+}
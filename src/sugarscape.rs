@@ -0,0 +1,740 @@
+//! Sugarscape, Epstein and Axtell's artificial society: agents wander a
+//! landscape of regrowing sugar, harvesting it to pay for their metabolism.
+//!
+//! Every tick, in a random order, each agent follows rule M: it looks as
+//! far as its vision allows along the four lattice directions, moves to the
+//! nearest unoccupied site with the most sugar and harvests all of it. It
+//! then burns sugar at its metabolic rate, and dies when it cannot pay or
+//! reaches its maximum age. Afterwards rule G grows the sugar on every site
+//! back towards its capacity. With replacement on, each agent that died is
+//! replaced by a newborn somewhere random, keeping the population steady.
+//!
+//! Though every agent follows the same rules, differences in vision,
+//! metabolism and luck concentrate wealth in a few of them. The
+//! `observer::Gini` probe tracks how unequal it gets.
+//!
+//! ```rust
+//! # use sekai::sugarscape::{Sugarscape, SugarscapeParams};
+//! # use sekai::world::World;
+//! let params = SugarscapeParams {
+//!     agents: 100,
+//!     ..SugarscapeParams::default()
+//! };
+//! let mut society = Sugarscape::from_params(&params, 0);
+//! for _ in 0..50 {
+//!     society.update();
+//! }
+//! // dead agents were replaced
+//! assert_eq!(society.num_entities(), 100);
+//! ```
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use config::{Config, ConfigError};
+use entity::Observable;
+use grid::Grid;
+use observer::{Custom, EntityCount, Gini, Metrics, ScalarStats};
+use registry::Definition;
+use render::{Rgb, Scene};
+use world::{World, WorldView};
+
+/// The four lattice directions agents look and move along
+const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// A torus of sites, each holding sugar which regrows up to a capacity
+#[derive(Debug, Clone, PartialEq)]
+pub struct Landscape {
+    sugar: Grid<u32>,
+    capacity: Grid<u32>,
+    growback: u32,
+}
+
+impl Landscape {
+    /// Creates a landscape with every site full
+    /// # Arguments
+    /// * `capacity` - The most sugar each site can hold
+    /// * `growback` - The sugar each site regrows per tick
+    pub fn new(capacity: Grid<u32>, growback: u32) -> Self {
+        Landscape {
+            sugar: capacity.clone(),
+            capacity,
+            growback,
+        }
+    }
+
+    /// Creates the classic landscape of two sugar mountains, in the upper
+    /// right and lower left, each falling off in rings from `peak` at its
+    /// summit to nothing in the far corners
+    /// # Arguments
+    /// * `width` - The number of columns
+    /// * `height` - The number of rows
+    /// * `peak` - The capacity at the summits
+    /// * `growback` - The sugar each site regrows per tick
+    pub fn two_peaks(width: usize, height: usize, peak: u32, growback: u32) -> Self {
+        let summits = [
+            (width as f64 * 0.7, height as f64 * 0.3),
+            (width as f64 * 0.3, height as f64 * 0.7),
+        ];
+        let ring = (width.min(height) as f64 / 8.0).max(1.0);
+        let mut capacity = Grid::new(width, height, 0);
+        for y in 0..height {
+            for x in 0..width {
+                let distance = summits
+                    .iter()
+                    .map(|&(sx, sy)| (x as f64 - sx).hypot(y as f64 - sy))
+                    .fold(f64::INFINITY, f64::min);
+                capacity.set(x, y, peak.saturating_sub((distance / ring) as u32));
+            }
+        }
+        Landscape::new(capacity, growback)
+    }
+
+    /// Gets the number of columns
+    pub fn width(&self) -> usize {
+        self.sugar.width()
+    }
+
+    /// Gets the number of rows
+    pub fn height(&self) -> usize {
+        self.sugar.height()
+    }
+
+    /// Gets the sugar on a site
+    /// # Arguments
+    /// * `x` - The column
+    /// * `y` - The row
+    pub fn sugar(&self, x: usize, y: usize) -> u32 {
+        self.sugar.get(x, y)
+    }
+
+    /// Gets the most sugar a site can hold
+    /// # Arguments
+    /// * `x` - The column
+    /// * `y` - The row
+    pub fn capacity(&self, x: usize, y: usize) -> u32 {
+        self.capacity.get(x, y)
+    }
+
+    /// Gets the sugar on every site together
+    pub fn total(&self) -> u64 {
+        self.sugar
+            .cells()
+            .iter()
+            .map(|&sugar| u64::from(sugar))
+            .sum()
+    }
+
+    /// Takes all the sugar from a site
+    /// # Arguments
+    /// * `x` - The column
+    /// * `y` - The row
+    pub fn harvest(&mut self, x: usize, y: usize) -> u32 {
+        let sugar = self.sugar.get(x, y);
+        self.sugar.set(x, y, 0);
+        sugar
+    }
+
+    /// Regrows every site by the growback rate, up to its capacity
+    pub fn grow(&mut self) {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let grown = self.sugar.get(x, y).saturating_add(self.growback);
+                self.sugar.set(x, y, grown.min(self.capacity.get(x, y)));
+            }
+        }
+    }
+
+    /// Gets the site `distance` steps from another along a direction,
+    /// wrapping around the edges
+    fn step(
+        &self,
+        x: usize,
+        y: usize,
+        (dx, dy): (isize, isize),
+        distance: usize,
+    ) -> (usize, usize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let d = distance as isize;
+        (
+            (x as isize + dx * d).rem_euclid(width) as usize,
+            (y as isize + dy * d).rem_euclid(height) as usize,
+        )
+    }
+}
+
+/// An agent living off the sugar it harvests
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agent {
+    id: u64,
+    site: (usize, usize),
+    position: [f32; 2],
+    vision: usize,
+    metabolism: u32,
+    wealth: u32,
+    age: u32,
+    max_age: u32,
+}
+
+impl Agent {
+    /// Creates a newborn agent. Its id is assigned when it joins a world
+    /// # Arguments
+    /// * `x` - The column of its site
+    /// * `y` - The row of its site
+    /// * `vision` - How many sites it sees in each direction
+    /// * `metabolism` - The sugar it burns every tick
+    /// * `wealth` - The sugar it is born with
+    /// * `max_age` - The tick of its life it dies of old age
+    pub fn new(
+        x: usize,
+        y: usize,
+        vision: usize,
+        metabolism: u32,
+        wealth: u32,
+        max_age: u32,
+    ) -> Self {
+        Agent {
+            id: 0,
+            site: (x, y),
+            position: [x as f32, y as f32],
+            vision,
+            metabolism,
+            wealth,
+            age: 0,
+            max_age,
+        }
+    }
+
+    /// Gets the column and row of the agent's site
+    pub fn site(&self) -> (usize, usize) {
+        self.site
+    }
+
+    /// Gets how many sites the agent sees in each direction
+    pub fn vision(&self) -> usize {
+        self.vision
+    }
+
+    /// Gets the sugar the agent burns every tick
+    pub fn metabolism(&self) -> u32 {
+        self.metabolism
+    }
+
+    /// Gets the sugar the agent has stored
+    pub fn wealth(&self) -> u32 {
+        self.wealth
+    }
+
+    /// Gets the number of ticks the agent has lived
+    pub fn age(&self) -> u32 {
+        self.age
+    }
+
+    fn move_to(&mut self, (x, y): (usize, usize)) {
+        self.site = (x, y);
+        self.position = [x as f32, y as f32];
+    }
+}
+
+impl Observable for Agent {
+    fn id(&self) -> u64 {
+        self.id
+    }
+    fn position(&self) -> &[f32] {
+        &self.position
+    }
+    fn field(&self, name: &str) -> Option<f64> {
+        match name {
+            "wealth" => Some(f64::from(self.wealth)),
+            "vision" => Some(self.vision as f64),
+            "metabolism" => Some(f64::from(self.metabolism)),
+            "age" => Some(f64::from(self.age)),
+            _ => None,
+        }
+    }
+}
+
+/// Parameters of a Sugarscape society. Newborns draw each trait uniformly
+/// from its range, bounds included
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SugarscapeParams {
+    pub width: usize,
+    pub height: usize,
+    pub agents: usize,
+    /// Capacity at the summits of the two sugar mountains
+    pub peak: u32,
+    /// Sugar each site regrows per tick
+    pub growback: u32,
+    pub max_vision: usize,
+    pub max_metabolism: u32,
+    /// Range of the sugar agents are born with
+    pub endowment: [u32; 2],
+    /// Range of the age agents die at
+    pub lifespan: [u32; 2],
+    /// Whether every agent that dies is replaced by a newborn
+    pub replace: bool,
+}
+
+impl Default for SugarscapeParams {
+    /// The society of Growing Artificial Societies, chapter II
+    fn default() -> Self {
+        SugarscapeParams {
+            width: 50,
+            height: 50,
+            agents: 400,
+            peak: 4,
+            growback: 1,
+            max_vision: 6,
+            max_metabolism: 4,
+            endowment: [5, 25],
+            lifespan: [60, 100],
+            replace: true,
+        }
+    }
+}
+
+impl Config for SugarscapeParams {
+    fn validate(&self) -> Result<(), ConfigError> {
+        match self.width.checked_mul(self.height) {
+            None => {
+                return Err(ConfigError::invalid(
+                    "width",
+                    "the landscape has too many sites",
+                ))
+            }
+            Some(sites) if self.agents > sites => {
+                return Err(ConfigError::invalid(
+                    "agents",
+                    "must not exceed the number of sites",
+                ))
+            }
+            Some(_) => {}
+        }
+        if self.max_vision == 0 || self.max_metabolism == 0 {
+            let key = if self.max_vision == 0 {
+                "max_vision"
+            } else {
+                "max_metabolism"
+            };
+            return Err(ConfigError::invalid(key, "must be at least 1"));
+        }
+        // looking further would only see the same sites again
+        if self.max_vision > self.width.max(self.height) {
+            return Err(ConfigError::invalid(
+                "max_vision",
+                "must not exceed the width or height",
+            ));
+        }
+        let ranges = [("endowment", self.endowment), ("lifespan", self.lifespan)];
+        for &(key, [min, max]) in &ranges {
+            if min > max {
+                return Err(ConfigError::invalid(key, "min must not exceed max"));
+            }
+        }
+        if self.lifespan[0] == 0 {
+            return Err(ConfigError::invalid("lifespan", "must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Agents on a sugar landscape, at most one per site
+#[derive(Debug, Clone)]
+pub struct Sugarscape {
+    landscape: Landscape,
+    agents: Vec<Agent>,
+    occupied: Grid<bool>,
+    params: SugarscapeParams,
+    rng: StdRng,
+    next_id: u64,
+    deaths: u64,
+}
+
+impl Sugarscape {
+    /// Creates a society on the two-peaked landscape
+    /// # Arguments
+    /// * `params` - The landscape and the agents
+    /// * `seed` - Seeds the placement, traits and order of agents
+    pub fn from_params(params: &SugarscapeParams, seed: u64) -> Self {
+        let landscape =
+            Landscape::two_peaks(params.width, params.height, params.peak, params.growback);
+        Sugarscape::new(landscape, params, seed)
+    }
+
+    /// Creates a society on any landscape, with `params.agents` newborns on
+    /// random sites. The landscape's size and growback take the place of
+    /// those in the parameters
+    /// # Arguments
+    /// * `landscape` - The sugar the agents live off
+    /// * `params` - The agents
+    /// * `seed` - Seeds the placement, traits and order of agents
+    pub fn new(landscape: Landscape, params: &SugarscapeParams, seed: u64) -> Self {
+        let (width, height) = (landscape.width(), landscape.height());
+        assert!(
+            params.agents <= width * height,
+            "{} agents do not fit on {} sites",
+            params.agents,
+            width * height
+        );
+        let mut society = Sugarscape {
+            landscape,
+            agents: Vec::with_capacity(params.agents),
+            occupied: Grid::new(width, height, false),
+            params: params.clone(),
+            rng: StdRng::from_seed(&[seed as usize][..]),
+            next_id: 0,
+            deaths: 0,
+        };
+        for _ in 0..params.agents {
+            society.add_newborn();
+        }
+        society
+    }
+
+    /// Gets the sugar landscape
+    pub fn landscape(&self) -> &Landscape {
+        &self.landscape
+    }
+
+    /// Gets the living agents
+    pub fn agents(&self) -> &[Agent] {
+        &self.agents
+    }
+
+    /// Gets the number of agents that have died, of hunger or old age
+    pub fn deaths(&self) -> u64 {
+        self.deaths
+    }
+
+    /// Draws the landscape in shades of yellow, brighter with more sugar,
+    /// and the agents as red squares on it
+    /// # Arguments
+    /// * `cell_size` - The width and height of each site in pixels
+    pub fn draw(&self, cell_size: u32) -> Scene {
+        let (width, height) = (self.landscape.width(), self.landscape.height());
+        let peak = self
+            .landscape
+            .capacity
+            .cells()
+            .iter()
+            .cloned()
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let mut colors = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                colors.push(if self.occupied.get(x, y) {
+                    Rgb::new(200, 30, 30)
+                } else {
+                    let sugar = u64::from(self.landscape.sugar(x, y));
+                    let shade = 255 - (sugar * 200 / u64::from(peak)) as u8;
+                    Rgb::new(255, 255, shade)
+                });
+            }
+        }
+        Grid::from_cells(width.max(1), colors).draw(cell_size, |&color| color)
+    }
+
+    /// Places an agent on its site, giving it a new id
+    /// # Arguments
+    /// * `agent` - The agent, whose site must be free
+    pub fn add_agent(&mut self, mut agent: Agent) {
+        let (x, y) = agent.site;
+        assert!(!self.occupied.get(x, y), "site ({}, {}) is occupied", x, y);
+        self.occupied.set(x, y, true);
+        agent.id = self.next_id;
+        self.next_id += 1;
+        self.agents.push(agent);
+    }
+
+    /// Adds an agent with random traits on a random free site, if any
+    fn add_newborn(&mut self) {
+        let free: Vec<(usize, usize)> = (0..self.landscape.height())
+            .flat_map(|y| (0..self.landscape.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| !self.occupied.get(x, y))
+            .collect();
+        if free.is_empty() {
+            return;
+        }
+        let (x, y) = free[self.rng.gen_range(0, free.len())];
+        let params = &self.params;
+        let rng = &mut self.rng;
+        let vision = gen_inclusive(rng, 1, params.max_vision as u64) as usize;
+        let mut between = |min: u32, max: u32| gen_inclusive(rng, min.into(), max.into()) as u32;
+        let agent = Agent::new(
+            x,
+            y,
+            vision,
+            between(1, params.max_metabolism),
+            between(params.endowment[0], params.endowment[1]),
+            between(params.lifespan[0], params.lifespan[1]),
+        );
+        self.add_agent(agent);
+    }
+
+    /// Finds the nearest free site with the most sugar an agent can see,
+    /// choosing at random between equally good ones. The agent's own site
+    /// counts, so it stays put when nothing better is in sight
+    fn best_site(&mut self, agent: &Agent) -> (usize, usize) {
+        let mut directions = DIRECTIONS;
+        self.rng.shuffle(&mut directions);
+        let (x, y) = agent.site;
+        let mut best = (self.landscape.sugar(x, y), 0, agent.site);
+        for distance in 1..=agent.vision {
+            for &direction in &directions {
+                let (sx, sy) = self.landscape.step(x, y, direction, distance);
+                let sugar = self.landscape.sugar(sx, sy);
+                // the nearest site wins ties, and the shuffled order ties
+                // between directions
+                if sugar > best.0 && !self.occupied.get(sx, sy) {
+                    best = (sugar, distance, (sx, sy));
+                }
+            }
+        }
+        best.2
+    }
+
+    /// Moves an agent, lets it harvest and eat, and ages it. Returns
+    /// whether it survived
+    fn live(&mut self, index: usize) -> bool {
+        let agent = self.agents[index].clone();
+        let (x, y) = self.best_site(&agent);
+        let (old_x, old_y) = agent.site;
+        self.occupied.set(old_x, old_y, false);
+        self.occupied.set(x, y, true);
+
+        let harvest = self.landscape.harvest(x, y);
+        let agent = &mut self.agents[index];
+        agent.move_to((x, y));
+        agent.wealth = agent.wealth.saturating_add(harvest);
+        agent.age += 1;
+        if agent.wealth < agent.metabolism || agent.age >= agent.max_age {
+            self.occupied.set(x, y, false);
+            return false;
+        }
+        agent.wealth -= agent.metabolism;
+        true
+    }
+}
+
+impl World<()> for Sugarscape {
+    fn update(&mut self) {
+        let mut order: Vec<usize> = (0..self.agents.len()).collect();
+        self.rng.shuffle(&mut order);
+        let mut alive = vec![true; order.len()];
+        for index in order {
+            alive[index] = self.live(index);
+        }
+        let died = alive.iter().filter(|&&survived| !survived).count();
+        let mut survived = alive.into_iter();
+        self.agents.retain(|_| survived.next().unwrap_or(false));
+        self.deaths += died as u64;
+        self.landscape.grow();
+        if self.params.replace {
+            for _ in 0..died {
+                self.add_newborn();
+            }
+        }
+    }
+    fn num_entities(&self) -> usize {
+        self.agents.len()
+    }
+    fn receive_message(&mut self, _message: ()) {}
+}
+
+impl WorldView for Sugarscape {
+    type Entity = Agent;
+    fn entities(&self) -> &[Agent] {
+        &self.agents
+    }
+}
+
+/// Draws a number from `min` to `max`, both included, even when `max` is
+/// the largest `u64`
+fn gen_inclusive<R: Rng>(rng: &mut R, min: u64, max: u64) -> u64 {
+    if max < u64::MAX {
+        rng.gen_range(min, max + 1)
+    } else if min > 0 {
+        rng.gen_range(min - 1, max) + 1
+    } else {
+        rng.gen()
+    }
+}
+
+/// Creates the metrics of a run: the population, the mean, spread and Gini
+/// coefficient of wealth, the sugar on the landscape and the deaths so far
+/// # Arguments
+/// * `_params` - The parameters of the run
+pub fn metrics(_params: &SugarscapeParams) -> Metrics<Sugarscape> {
+    let wealth = |agent: &Agent| f64::from(agent.wealth());
+    let mut metrics = Metrics::new();
+    metrics.add_probe(EntityCount);
+    metrics.add_probe(ScalarStats::new("wealth", wealth));
+    metrics.add_probe(Gini::new("wealth", wealth));
+    metrics.add_probe(Custom::new("sugar", |world: &Sugarscape| {
+        world.landscape().total() as f64
+    }));
+    metrics.add_probe(Custom::new("deaths", |world: &Sugarscape| {
+        world.deaths() as f64
+    }));
+    metrics
+}
+
+/// Defines the society as the `sugarscape` model, recording the wealth,
+/// vision, metabolism and age of agents in trajectories
+pub fn definition() -> Definition<SugarscapeParams, Sugarscape, ()> {
+    let mut sugarscape = Definition::new("sugarscape", Sugarscape::from_params);
+    sugarscape.set_description("Agents harvesting a regrowing sugar landscape");
+    sugarscape.set_metrics(metrics);
+    sugarscape.record_trajectory(&["wealth", "vision", "metabolism", "age"]);
+    sugarscape
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analysis::gini;
+    use config;
+    use observer::{Gini, Metrics, Observer};
+
+    fn params(agents: usize) -> SugarscapeParams {
+        SugarscapeParams {
+            agents,
+            replace: false,
+            ..SugarscapeParams::default()
+        }
+    }
+
+    /// A 7 by 1 strip of sugar, for following one agent
+    fn strip(sugar: &[u32]) -> Landscape {
+        Landscape::new(Grid::from_cells(sugar.len(), sugar.to_vec()), 0)
+    }
+
+    #[test]
+    fn test_landscape_regrows() {
+        let mut landscape = Landscape::two_peaks(50, 50, 4, 1);
+        assert_eq!(landscape.capacity(35, 15), 4);
+        assert_eq!(landscape.capacity(15, 35), 4);
+        assert_eq!(landscape.capacity(0, 0), 0);
+        assert_eq!(landscape.sugar(35, 15), 4);
+
+        let total = landscape.total();
+        assert_eq!(landscape.harvest(35, 15), 4);
+        assert_eq!(landscape.harvest(35, 15), 0);
+        landscape.grow();
+        assert_eq!(landscape.sugar(35, 15), 1);
+        for _ in 0..10 {
+            landscape.grow();
+        }
+        assert_eq!(landscape.sugar(35, 15), 4);
+        assert_eq!(landscape.total(), total);
+    }
+
+    #[test]
+    fn test_agents_seek_sugar() {
+        let mut society = Sugarscape::new(strip(&[0, 1, 0, 0, 9, 3, 8]), &params(0), 1);
+        // sees 2 sites each way, wrapping around: 9 is out of sight
+        society.add_agent(Agent::new(1, 0, 2, 2, 0, 100));
+        society.update();
+        assert_eq!(society.agents()[0].site(), (6, 0));
+        assert_eq!(society.agents()[0].wealth(), 6);
+        assert_eq!(society.landscape().sugar(6, 0), 0);
+
+        // now 9 is in sight, and it beats the nearer 3
+        society.update();
+        assert_eq!(society.agents()[0].site(), (4, 0));
+        assert_eq!(society.agents()[0].wealth(), 13);
+        society.update();
+        assert_eq!(society.agents()[0].site(), (5, 0));
+        assert_eq!(society.agents()[0].wealth(), 14);
+
+        // with no sugar in sight it stays put, living off its wealth
+        for _ in 0..7 {
+            society.update();
+        }
+        assert_eq!(society.agents()[0].site(), (5, 0));
+        assert_eq!(society.agents()[0].wealth(), 0);
+        society.update();
+        assert!(society.agents().is_empty());
+        assert_eq!(society.deaths(), 1);
+    }
+
+    #[test]
+    fn test_occupied_sites_and_old_age() {
+        let mut society = Sugarscape::new(strip(&[0, 5, 0, 0, 0, 0, 0]), &params(0), 2);
+        society.add_agent(Agent::new(0, 0, 1, 1, 10, 3));
+        society.add_agent(Agent::new(2, 0, 1, 1, 10, 100));
+        society.update();
+        // whoever moves first takes the pile; the other cannot share its site
+        let sites: Vec<(usize, usize)> = society.agents().iter().map(Agent::site).collect();
+        assert!(sites.contains(&(1, 0)));
+        assert_ne!(sites[0], sites[1]);
+
+        society.update();
+        society.update();
+        assert_eq!(society.num_entities(), 1);
+        assert_eq!(society.agents()[0].age(), 3);
+
+        let mut crowd = params(2500);
+        crowd.replace = true;
+        assert!(config::resolve::<SugarscapeParams>(config::to_value(&crowd).unwrap()).is_ok());
+        crowd.agents = 2501;
+        let error = config::resolve::<SugarscapeParams>(config::to_value(&crowd).unwrap());
+        assert_eq!(error.unwrap_err().key(), Some("agents"));
+    }
+
+    #[test]
+    fn test_extreme_params() {
+        let mut huge = params(0);
+        huge.width = usize::MAX;
+        huge.height = 2;
+        assert_eq!(huge.validate().unwrap_err().key(), Some("width"));
+        let mut farsighted = params(0);
+        farsighted.max_vision = 51;
+        assert_eq!(farsighted.validate().unwrap_err().key(), Some("max_vision"));
+
+        // the largest traits and sugar are sampled and rendered without overflow
+        let extreme = SugarscapeParams {
+            width: 5,
+            height: 5,
+            agents: 5,
+            peak: u32::MAX,
+            max_vision: 5,
+            max_metabolism: u32::MAX,
+            endowment: [0, u32::MAX],
+            lifespan: [u32::MAX, u32::MAX],
+            ..SugarscapeParams::default()
+        };
+        assert!(extreme.validate().is_ok());
+        let mut society = Sugarscape::from_params(&extreme, 1);
+        society.draw(1);
+        society.update();
+        society.draw(1);
+    }
+
+    #[test]
+    fn test_wealth_becomes_unequal() {
+        let params = SugarscapeParams::default();
+        let mut society = Sugarscape::from_params(&params, 3);
+        let mut metrics = Metrics::new();
+        metrics.add_probe(Gini::new("wealth", |agent: &Agent| {
+            f64::from(agent.wealth())
+        }));
+        for tick in 0..200 {
+            society.update();
+            metrics.observe(tick, &society);
+        }
+        // replacement keeps the population, while wealth piles up
+        assert_eq!(society.num_entities(), 400);
+        assert!(society.deaths() > 400);
+        let inequality = &metrics.series("wealth_gini").unwrap().values;
+        let wealth: Vec<f64> = society
+            .agents()
+            .iter()
+            .map(|a| f64::from(a.wealth()))
+            .collect();
+        assert_eq!(inequality[199], gini(&wealth));
+        assert!(inequality[199] > 0.4, "gini {}", inequality[199]);
+    }
+}
@@ -0,0 +1,442 @@
+//! Live view of a running simulation in an ANSI terminal.
+//!
+//! Each character cell shows two pixels using the upper half block, with the
+//! top pixel as the foreground color and the bottom pixel as the background,
+//! so a terminal of 80 by 24 characters shows an 80 by 46 pixel image plus a
+//! status line.
+
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::io::Read;
+#[cfg(unix)]
+use std::process::{Command, Stdio};
+#[cfg(unix)]
+use std::sync::mpsc;
+
+use observer::Observer;
+use render::{Canvas, Rgb, Scene};
+
+/// Draws a canvas with 24-bit ANSI colors, two pixel rows per text line.
+/// Each line ends by resetting the colors
+/// # Arguments
+/// * `canvas` - The image to draw
+pub fn to_ansi(canvas: &Canvas) -> String {
+    let mut ansi = String::new();
+    let bottom_of = |x: u32, y: u32| canvas.get(x, y + 1).unwrap_or(Rgb::BLACK);
+    for y in (0..canvas.height()).step_by(2) {
+        let mut current: Option<(Rgb, Rgb)> = None;
+        for x in 0..canvas.width() {
+            let colors = (canvas.get(x, y).unwrap_or(Rgb::BLACK), bottom_of(x, y));
+            if current != Some(colors) {
+                let (top, bottom) = colors;
+                let _ = write!(
+                    ansi,
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                    top.r, top.g, top.b, bottom.r, bottom.g, bottom.b
+                );
+                current = Some(colors);
+            }
+            ansi.push('\u{2580}');
+        }
+        ansi.push_str("\x1b[0m\n");
+    }
+    ansi
+}
+
+/// Gets the size of the terminal in characters as `(columns, rows)`.
+/// Falls back to the `COLUMNS` and `LINES` variables, then to 80 by 24
+pub fn terminal_size() -> (u32, u32) {
+    let from_stty = stty(&["size"]).and_then(|size| {
+        let mut parts = size.split_whitespace().map(|n| n.parse::<u32>().ok());
+        match (parts.next(), parts.next()) {
+            (Some(Some(rows)), Some(Some(cols))) if rows > 0 && cols > 0 => Some((cols, rows)),
+            _ => None,
+        }
+    });
+    from_stty.unwrap_or_else(|| {
+        let var = |name: &str, default: u32| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        (var("COLUMNS", 80), var("LINES", 24))
+    })
+}
+
+/// Playback state of a live view, driven by single key presses:
+/// * space - pause or resume
+/// * `s` - run one tick while paused
+/// * `+` or `=` - run faster
+/// * `-` - run slower
+/// * `q` - quit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Playback {
+    /// Whether the view is waiting for a key before the next tick
+    pub paused: bool,
+    /// How long each tick stays on screen
+    pub delay: Duration,
+    step: bool,
+    quit: bool,
+}
+
+impl Playback {
+    const MIN_DELAY: Duration = Duration::from_millis(1);
+    const MAX_DELAY: Duration = Duration::from_secs(5);
+
+    /// Creates a running playback
+    /// # Arguments
+    /// * `delay` - How long each tick stays on screen
+    pub fn new(delay: Duration) -> Self {
+        Playback {
+            paused: false,
+            delay,
+            step: false,
+            quit: false,
+        }
+    }
+
+    /// Updates the playback for a key press
+    /// # Arguments
+    /// * `key` - The byte read from the terminal
+    pub fn handle_key(&mut self, key: u8) {
+        match key {
+            b' ' => self.paused = !self.paused,
+            b's' => {
+                self.paused = true;
+                self.step = true;
+            }
+            b'+' | b'=' => self.delay = (self.delay / 2).max(Playback::MIN_DELAY),
+            b'-' => self.delay = (self.delay * 2).min(Playback::MAX_DELAY),
+            b'q' => self.quit = true,
+            _ => {}
+        }
+    }
+
+    /// Checks whether quitting was requested
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    /// Checks whether the next tick may run, consuming a pending step
+    fn advance(&mut self) -> bool {
+        if self.quit || !self.paused {
+            return true;
+        }
+        if self.step {
+            self.step = false;
+            return true;
+        }
+        false
+    }
+}
+
+/// Observer which redraws the world in the terminal after every tick and
+/// paces the simulation according to its `Playback`. Key presses are read
+/// from the terminal when there is one, on Unix only; otherwise the view
+/// just plays.
+///
+/// Observers cannot stop a simulation, so a driving loop should check
+/// `quit_requested`:
+///
+/// ```rust,no_run
+/// # use sekai::observer::Observer;
+/// # use sekai::render::{Bounds, Rgb, Scene};
+/// # use sekai::terminal::LiveView;
+/// let mut view = LiveView::new(|cells: &Vec<bool>| {
+///     let mut scene = Scene::new(80, 1, Bounds::new(0.0, 0.0, 80.0, 1.0));
+///     scene.add_grid(80, 1, |col, _| if cells[col] { Rgb::WHITE } else { Rgb::BLACK });
+///     scene
+/// });
+/// let mut cells = vec![false; 80];
+/// let mut tick = 0;
+/// while !view.quit_requested() {
+///     cells[tick as usize % 80] ^= true;
+///     tick += 1;
+///     view.observe(tick, &cells);
+/// }
+/// ```
+pub struct LiveView<F> {
+    draw: F,
+    playback: Playback,
+    keys: Option<Keyboard>,
+    out: io::Stdout,
+    /// The terminal size in characters, queried once as it takes a process
+    size: (u32, u32),
+}
+
+impl<F> LiveView<F> {
+    /// Switches the terminal to a blank screen and starts reading keys.
+    /// Scenes are fitted to the size of the terminal at this point
+    /// # Arguments
+    /// * `draw` - Describes the scene to draw for a world. The scene is
+    ///   scaled to fit the terminal, keeping its aspect ratio
+    pub fn new(draw: F) -> Self {
+        let mut out = io::stdout();
+        let _ = write!(out, "\x1b[?1049h\x1b[?25l");
+        let _ = out.flush();
+        LiveView {
+            draw,
+            playback: Playback::new(Duration::from_millis(100)),
+            keys: Keyboard::open(),
+            out,
+            size: terminal_size(),
+        }
+    }
+
+    /// Gets the playback state
+    pub fn playback(&self) -> &Playback {
+        &self.playback
+    }
+
+    /// Gets the playback state, mutably, e.g. to start paused
+    pub fn playback_mut(&mut self) -> &mut Playback {
+        &mut self.playback
+    }
+
+    /// Checks whether quitting was requested from the keyboard
+    pub fn quit_requested(&self) -> bool {
+        self.playback.quit_requested()
+    }
+
+    fn poll_keys(&mut self, wait: bool) {
+        if let Some(ref keys) = self.keys {
+            let keys = keys.poll(wait);
+            for key in keys {
+                self.playback.handle_key(key);
+            }
+        }
+    }
+
+    fn redraw(&mut self, tick: u64, scene: &Scene) {
+        let (cols, rows) = self.size;
+        let canvas = Canvas::render(&fit(scene, cols, rows.saturating_sub(1) * 2));
+        let state = if self.playback.paused {
+            "paused"
+        } else {
+            "running"
+        };
+        let _ = write!(
+            self.out,
+            "\x1b[H{}tick {} | {} | {}ms | [space] pause [s] step [+/-] speed [q] quit\x1b[K\x1b[J",
+            to_ansi(&canvas),
+            tick,
+            state,
+            self.playback.delay.as_millis()
+        );
+        let _ = self.out.flush();
+    }
+}
+
+impl<W: ?Sized, F: FnMut(&W) -> Scene> Observer<W> for LiveView<F> {
+    fn observe(&mut self, tick: u64, world: &W) {
+        if self.playback.quit_requested() {
+            return;
+        }
+        let scene = (self.draw)(world);
+        self.poll_keys(false);
+        self.redraw(tick, &scene);
+        thread::sleep(self.playback.delay);
+        self.poll_keys(false);
+        // hold the simulation here while paused, redrawing on every key so
+        // the status line stays current. Without a keyboard nothing could
+        // resume it, so never pause
+        while self.keys.is_some() && !self.playback.advance() {
+            self.poll_keys(true);
+            self.redraw(tick, &scene);
+        }
+    }
+}
+
+impl<F> Drop for LiveView<F> {
+    fn drop(&mut self) {
+        let _ = write!(self.out, "\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = self.out.flush();
+    }
+}
+
+/// Scales a scene to fit within a number of pixels, keeping its aspect ratio
+fn fit(scene: &Scene, width: u32, height: u32) -> Scene {
+    let scale =
+        (width as f32 / scene.width.max(1) as f32).min(height as f32 / scene.height.max(1) as f32);
+    let mut fitted = scene.clone();
+    fitted.width = ((scene.width as f32 * scale) as u32).max(1);
+    fitted.height = ((scene.height as f32 * scale) as u32).max(1);
+    fitted
+}
+
+/// Opens the controlling terminal, if there is one
+#[cfg(unix)]
+fn tty() -> Option<File> {
+    File::open("/dev/tty").ok()
+}
+
+/// Reads single key presses from the terminal on a background thread. The
+/// terminal is put into non-canonical mode with `stty` and restored on drop.
+/// Only Unix terminals are read; elsewhere `open` finds no keyboard
+#[cfg_attr(not(unix), allow(dead_code))]
+struct Keyboard {
+    keys: Receiver<u8>,
+    saved_mode: String,
+    stop: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl Keyboard {
+    #[cfg(unix)]
+    fn open() -> Option<Self> {
+        let mut input = tty()?;
+        let saved_mode = stty(&["-g"])?;
+        // reads give up after a tenth of a second, so the reader can notice
+        // it should stop instead of waiting for a key that may never come
+        if stty(&["-icanon", "-echo", "min", "0", "time", "1"]).is_none() {
+            // the mode may have partly changed, so put it back
+            let _ = stty(&[saved_mode.trim()]);
+            return None;
+        }
+        let (sender, keys) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let reader = thread::spawn(move || {
+            let mut key = [0; 1];
+            while !stopped.load(Ordering::Relaxed) {
+                match input.read(&mut key) {
+                    Ok(1) => {
+                        if sender.send(key[0]).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+        });
+        Some(Keyboard {
+            keys,
+            saved_mode: saved_mode.trim().into(),
+            stop,
+            reader: Some(reader),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn open() -> Option<Self> {
+        None
+    }
+
+    /// Gets the keys pressed so far, optionally waiting for at least one
+    fn poll(&self, wait: bool) -> Vec<u8> {
+        let mut keys = Vec::new();
+        if wait {
+            match self.keys.recv() {
+                Ok(key) => keys.push(key),
+                // the terminal went away, so act as if quit was pressed
+                Err(_) => return vec![b'q'],
+            }
+        }
+        loop {
+            match self.keys.try_recv() {
+                Ok(key) => keys.push(key),
+                Err(TryRecvError::Empty) => return keys,
+                Err(TryRecvError::Disconnected) => {
+                    if wait && keys.is_empty() {
+                        keys.push(b'q');
+                    }
+                    return keys;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        // stop the reader before restoring the mode, so it never blocks on
+        // a terminal that waits for whole lines again
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+        let _ = stty(&[self.saved_mode.as_str()]);
+    }
+}
+
+/// Runs `stty` against the terminal, returning its output on success
+#[cfg(unix)]
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(tty()?)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if output.status.success() {
+        String::from_utf8(output.stdout).ok()
+    } else {
+        None
+    }
+}
+
+/// There is no `stty` to run off Unix
+#[cfg(not(unix))]
+fn stty(_args: &[&str]) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::Bounds;
+
+    #[test]
+    fn test_to_ansi_uses_half_blocks() {
+        let mut canvas = Canvas::new(2, 2, Rgb::BLACK);
+        canvas.set(0, 0, Rgb::WHITE);
+        let ansi = to_ansi(&canvas);
+        assert_eq!(
+            ansi,
+            "\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m\u{2580}\
+             \x1b[38;2;0;0;0m\x1b[48;2;0;0;0m\u{2580}\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_playback_keys() {
+        let mut playback = Playback::new(Duration::from_millis(100));
+        assert!(playback.advance());
+
+        playback.handle_key(b' ');
+        assert!(!playback.advance());
+        playback.handle_key(b's');
+        assert!(playback.advance());
+        assert!(!playback.advance());
+
+        playback.handle_key(b'+');
+        assert_eq!(playback.delay, Duration::from_millis(50));
+        playback.handle_key(b'-');
+        playback.handle_key(b'-');
+        assert_eq!(playback.delay, Duration::from_millis(200));
+
+        playback.handle_key(b'q');
+        assert!(playback.quit_requested());
+        assert!(playback.advance());
+    }
+
+    #[test]
+    fn test_fit_keeps_aspect_ratio() {
+        let scene = Scene::new(100, 50, Bounds::new(0.0, 0.0, 1.0, 1.0));
+        let fitted = fit(&scene, 80, 46);
+        assert_eq!((fitted.width, fitted.height), (80, 40));
+    }
+}
@@ -0,0 +1,580 @@
+//! Recording of entity state over time.
+//!
+//! Trajectories are streamed to a compact little-endian binary format:
+//!
+//! ```text
+//! header: b"SEKAITRJ", version: u16, field count: u16,
+//!         per field: name length: u16, name: utf-8 bytes
+//! frame:  tick: u64, entity count: u32,
+//!         per entity: id: u64, dimensions: u16, position: f32 * dimensions,
+//!                     fields: f64 * field count
+//! ```
+//!
+//! Frames follow the header until the end of the file. Fields an entity does
+//! not have are recorded as NaN.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use serde_json;
+
+use entity::Observable;
+use observer::Observer;
+use world::WorldView;
+
+const MAGIC: &[u8; 8] = b"SEKAITRJ";
+const VERSION: u16 = 1;
+
+/// The recorded state of one entity at one tick
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityState {
+    pub id: u64,
+    pub position: Vec<f32>,
+    /// Values of the recorded fields, in the order of `Trajectory::fields`,
+    /// NaN where the entity did not have the field
+    #[serde(with = "missing_as_null")]
+    pub fields: Vec<f64>,
+}
+
+/// The recorded state of every entity at one tick
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub tick: u64,
+    pub entities: Vec<EntityState>,
+}
+
+impl Frame {
+    /// Gets the state of an entity in this frame
+    /// # Arguments
+    /// * `id` - The id of the entity
+    pub fn entity(&self, id: u64) -> Option<&EntityState> {
+        self.entities.iter().find(|e| e.id == id)
+    }
+}
+
+/// A whole recorded run, held in memory
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Trajectory {
+    /// Names of the recorded fields
+    pub fields: Vec<String>,
+    /// Frames in the order they were recorded, which must be tick order
+    /// for `frame_at` to find them
+    pub frames: Vec<Frame>,
+}
+
+impl Trajectory {
+    /// Reads a whole binary trajectory
+    /// # Arguments
+    /// * `input` - The binary trajectory
+    pub fn read<R: Read>(input: R) -> io::Result<Self> {
+        let mut reader = TrajectoryReader::new(input)?;
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.next_frame()? {
+            frames.push(frame);
+        }
+        Ok(Trajectory {
+            fields: reader.fields,
+            frames,
+        })
+    }
+
+    /// Reads a whole binary trajectory from a file
+    /// # Arguments
+    /// * `path` - The file written by a `TrajectoryRecorder`
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Trajectory::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the trajectory in the binary format. Every entity must have a
+    /// value for each field, or nothing is written
+    /// # Arguments
+    /// * `out` - Where to write the trajectory
+    pub fn write<W: Write>(&self, out: W) -> io::Result<W> {
+        let mut entities = self.frames.iter().flat_map(|frame| &frame.entities);
+        if let Some(entity) = entities.find(|entity| entity.fields.len() != self.fields.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "entity {} has {} field values for {} fields",
+                    entity.id,
+                    entity.fields.len(),
+                    self.fields.len()
+                ),
+            ));
+        }
+        let mut out = out;
+        write_header(&mut out, &self.fields)?;
+        for frame in &self.frames {
+            write_frame(&mut out, frame)?;
+        }
+        Ok(out)
+    }
+
+    /// Writes the trajectory as a single JSON document. Missing fields are
+    /// written as `null`
+    /// # Arguments
+    /// * `out` - Where to write the JSON
+    pub fn write_json<W: Write>(&self, out: W) -> io::Result<()> {
+        serde_json::to_writer(out, self)?;
+        Ok(())
+    }
+
+    /// Gets the position of a field within `EntityState::fields`
+    /// # Arguments
+    /// * `name` - The name of the field
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|f| f == name)
+    }
+
+    /// Gets the frame recorded at a tick, searching frames in tick order
+    /// # Arguments
+    /// * `tick` - The tick to look up
+    pub fn frame_at(&self, tick: u64) -> Option<&Frame> {
+        self.frames
+            .binary_search_by_key(&tick, |f| f.tick)
+            .ok()
+            .map(|i| &self.frames[i])
+    }
+
+    /// Gets the recorded states of one entity between two ticks, inclusive.
+    /// Ticks at which the entity did not exist are skipped
+    /// # Arguments
+    /// * `id` - The id of the entity
+    /// * `from` - The first tick to include
+    /// * `to` - The last tick to include
+    pub fn entity_between(&self, id: u64, from: u64, to: u64) -> Vec<(u64, &EntityState)> {
+        self.frames
+            .iter()
+            .filter(|f| f.tick >= from && f.tick <= to)
+            .filter_map(|f| f.entity(id).map(|e| (f.tick, e)))
+            .collect()
+    }
+}
+
+/// Streams frames out of a binary trajectory one at a time, so long runs
+/// do not need to fit in memory
+pub struct TrajectoryReader<R: Read> {
+    input: R,
+    fields: Vec<String>,
+}
+
+impl<R: Read> TrajectoryReader<R> {
+    /// Reads the header of a binary trajectory
+    /// # Arguments
+    /// * `input` - The binary trajectory
+    pub fn new(input: R) -> io::Result<Self> {
+        let mut input = input;
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a sekai trajectory"));
+        }
+        let version = read_u16(&mut input)?;
+        if version != VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported trajectory version {}",
+                version
+            )));
+        }
+        let num_fields = read_u16(&mut input)?;
+        let mut fields = Vec::with_capacity(num_fields as usize);
+        for _ in 0..num_fields {
+            let mut name = vec![0; read_u16(&mut input)? as usize];
+            input.read_exact(&mut name)?;
+            fields.push(
+                String::from_utf8(name).map_err(|_| invalid_data("field name is not utf-8"))?,
+            );
+        }
+        Ok(TrajectoryReader { input, fields })
+    }
+
+    /// Gets the names of the recorded fields
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// Reads the next frame, or `None` at the end of the trajectory
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let mut tick = [0; 8];
+        if !read_or_eof(&mut self.input, &mut tick)? {
+            return Ok(None);
+        }
+        let tick = u64::from_le_bytes(tick);
+        let count = read_u32(&mut self.input)?;
+        // the count comes from the file, so grow as entities are actually read
+        let mut entities = Vec::new();
+        for _ in 0..count {
+            let id = read_u64(&mut self.input)?;
+            let dims = read_u16(&mut self.input)?;
+            let mut position = Vec::with_capacity(dims as usize);
+            for _ in 0..dims {
+                position.push(f32::from_bits(read_u32(&mut self.input)?));
+            }
+            let mut fields = Vec::with_capacity(self.fields.len());
+            for _ in 0..self.fields.len() {
+                fields.push(f64::from_bits(read_u64(&mut self.input)?));
+            }
+            entities.push(EntityState {
+                id,
+                position,
+                fields,
+            });
+        }
+        Ok(Some(Frame { tick, entities }))
+    }
+}
+
+impl<R: Read> Iterator for TrajectoryReader<R> {
+    type Item = io::Result<Frame>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_frame() {
+            Ok(Some(frame)) => Some(Ok(frame)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Observer which appends the state of every entity to a binary trajectory
+/// after each tick. Frames are flushed as they are written, so a run which is
+/// killed keeps every complete frame
+pub struct TrajectoryRecorder<W: Write> {
+    out: W,
+    fields: Vec<String>,
+    header_written: bool,
+    error: Option<io::Error>,
+}
+
+impl TrajectoryRecorder<BufWriter<File>> {
+    /// Creates a trajectory file, truncating any existing file
+    /// # Arguments
+    /// * `path` - Where to write the trajectory
+    /// * `fields` - Names of the entity fields to record
+    pub fn create<P: AsRef<Path>>(path: P, fields: &[&str]) -> io::Result<Self> {
+        Ok(TrajectoryRecorder::new(
+            BufWriter::new(File::create(path)?),
+            fields,
+        ))
+    }
+}
+
+impl<W: Write> TrajectoryRecorder<W> {
+    /// Creates a recorder over any output
+    /// # Arguments
+    /// * `out` - Where to write the trajectory
+    /// * `fields` - Names of the entity fields to record
+    pub fn new(out: W, fields: &[&str]) -> Self {
+        TrajectoryRecorder {
+            out,
+            fields: fields.iter().map(|&f| f.into()).collect(),
+            header_written: false,
+            error: None,
+        }
+    }
+
+    /// Records the entities of a world as one frame
+    /// # Arguments
+    /// * `tick` - The tick the world is at
+    /// * `world` - The world to record
+    pub fn record<V>(&mut self, tick: u64, world: &V) -> io::Result<()>
+    where
+        V: WorldView + ?Sized,
+        V::Entity: Observable,
+    {
+        if !self.header_written {
+            write_header(&mut self.out, &self.fields)?;
+            self.header_written = true;
+        }
+        let entities = world.entities();
+        self.out.write_all(&tick.to_le_bytes())?;
+        self.out
+            .write_all(&length_u32(entities.len(), "entities")?.to_le_bytes())?;
+        for entity in entities {
+            let position = entity.position();
+            self.out.write_all(&entity.id().to_le_bytes())?;
+            self.out
+                .write_all(&length_u16(position.len(), "position dimensions")?.to_le_bytes())?;
+            for coord in position {
+                self.out.write_all(&coord.to_bits().to_le_bytes())?;
+            }
+            for name in &self.fields {
+                let value = entity.field(name).unwrap_or(f64::NAN);
+                self.out.write_all(&value.to_bits().to_le_bytes())?;
+            }
+        }
+        self.out.flush()
+    }
+
+    /// Ends recording, returning the output or the first error hit while
+    /// observing
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if !self.header_written {
+            write_header(&mut self.out, &self.fields)?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl<V, W> Observer<V> for TrajectoryRecorder<W>
+where
+    V: WorldView + ?Sized,
+    V::Entity: Observable,
+    W: Write,
+{
+    fn observe(&mut self, tick: u64, world: &V) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.record(tick, world) {
+            self.error = Some(e);
+        }
+    }
+}
+
+fn write_header<W: Write>(out: &mut W, fields: &[String]) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&length_u16(fields.len(), "fields")?.to_le_bytes())?;
+    for name in fields {
+        out.write_all(&length_u16(name.len(), "field name bytes")?.to_le_bytes())?;
+        out.write_all(name.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_frame<W: Write>(out: &mut W, frame: &Frame) -> io::Result<()> {
+    out.write_all(&frame.tick.to_le_bytes())?;
+    out.write_all(&length_u32(frame.entities.len(), "entities")?.to_le_bytes())?;
+    for entity in &frame.entities {
+        out.write_all(&entity.id.to_le_bytes())?;
+        out.write_all(&length_u16(entity.position.len(), "position dimensions")?.to_le_bytes())?;
+        for coord in &entity.position {
+            out.write_all(&coord.to_bits().to_le_bytes())?;
+        }
+        for value in &entity.fields {
+            out.write_all(&value.to_bits().to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Fills the buffer, returning false if the input was already at its end
+fn read_or_eof<R: Read>(input: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    let mut read = 0;
+    while read < buf.len() {
+        match input.read(&mut buf[read..]) {
+            Ok(0) if read == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+fn read_u16<R: Read>(input: &mut R) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    input.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Checks a length fits the u16 the format stores it in
+fn length_u16(length: usize, what: &str) -> io::Result<u16> {
+    u16::try_from(length).map_err(|_| too_long(length, what, u16::MAX.into()))
+}
+
+/// Checks a length fits the u32 the format stores it in
+fn length_u32(length: usize, what: &str) -> io::Result<u32> {
+    u32::try_from(length).map_err(|_| too_long(length, what, u32::MAX.into()))
+}
+
+fn too_long(length: usize, what: &str, max: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "{} {} do not fit a trajectory, which allows {}",
+            length, what, max
+        ),
+    )
+}
+
+/// Writes NaN fields, which entities do not have, as JSON `null` and reads
+/// them back as NaN
+mod missing_as_null {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(fields: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+        let fields: Vec<Option<f64>> = fields
+            .iter()
+            .map(|&value| if value.is_nan() { None } else { Some(value) })
+            .collect();
+        fields.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f64>, D::Error> {
+        let fields: Vec<Option<f64>> = Vec::deserialize(deserializer)?;
+        Ok(fields
+            .into_iter()
+            .map(|value| value.unwrap_or(f64::NAN))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Bird {
+        id: u64,
+        pos: Vec<f32>,
+        energy: f64,
+    }
+    impl Observable for Bird {
+        fn id(&self) -> u64 {
+            self.id
+        }
+        fn position(&self) -> &[f32] {
+            &self.pos
+        }
+        fn field(&self, name: &str) -> Option<f64> {
+            match name {
+                "energy" => Some(self.energy),
+                _ => None,
+            }
+        }
+    }
+    struct Flock {
+        birds: Vec<Bird>,
+    }
+    impl WorldView for Flock {
+        type Entity = Bird;
+        fn entities(&self) -> &[Bird] {
+            &self.birds
+        }
+    }
+
+    fn record_flight() -> Vec<u8> {
+        let mut flock = Flock {
+            birds: vec![
+                Bird {
+                    id: 1,
+                    pos: vec![0.0, 0.0],
+                    energy: 10.0,
+                },
+                Bird {
+                    id: 2,
+                    pos: vec![5.0, 5.0],
+                    energy: 3.0,
+                },
+            ],
+        };
+        let mut recorder = TrajectoryRecorder::new(Vec::new(), &["energy", "age"]);
+        for tick in 1..5 {
+            for bird in &mut flock.birds {
+                bird.pos[0] += 1.0;
+                bird.energy -= 1.0;
+            }
+            // bird 2 runs out of energy after tick 2
+            flock.birds.retain(|b| b.energy > 0.0);
+            recorder.observe(tick, &flock);
+        }
+        recorder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_record_and_read() {
+        let trajectory = Trajectory::read(&record_flight()[..]).unwrap();
+        assert_eq!(trajectory.fields, vec!["energy", "age"]);
+        assert_eq!(trajectory.frames.len(), 4);
+        assert_eq!(trajectory.frame_at(2).unwrap().entities.len(), 2);
+        assert_eq!(trajectory.frame_at(3).unwrap().entities.len(), 1);
+
+        let bird = &trajectory.frame_at(3).unwrap().entities[0];
+        assert_eq!(bird.id, 1);
+        assert_eq!(bird.position, vec![3.0, 0.0]);
+        assert_eq!(bird.fields[0], 7.0);
+        assert!(bird.fields[1].is_nan());
+    }
+
+    #[test]
+    fn test_entity_between() {
+        let trajectory = Trajectory::read(&record_flight()[..]).unwrap();
+        let path: Vec<(u64, f32)> = trajectory
+            .entity_between(2, 1, 3)
+            .into_iter()
+            .map(|(tick, state)| (tick, state.position[0]))
+            .collect();
+        assert_eq!(path, vec![(1, 6.0), (2, 7.0)]);
+        assert_eq!(trajectory.entity_between(1, 2, 3).len(), 2);
+    }
+
+    #[test]
+    fn test_binary_and_json_round_trip() {
+        let trajectory = Trajectory::read(&record_flight()[..]).unwrap();
+        let binary = trajectory.write(Vec::new()).unwrap();
+        assert_eq!(binary, record_flight());
+
+        // missing fields go through JSON as null and come back as NaN
+        let mut json = Vec::new();
+        trajectory.write_json(&mut json).unwrap();
+        let parsed: Trajectory = serde_json::from_slice(&json).unwrap();
+        assert_eq!(parsed.write(Vec::new()).unwrap(), binary);
+        assert!(parsed.frame_at(3).unwrap().entities[0].fields[1].is_nan());
+    }
+
+    #[test]
+    fn test_oversized_lengths_are_errors() {
+        // a corrupt entity count fails on the missing entities, without
+        // allocating for all of them
+        let mut binary = Vec::new();
+        write_header(&mut binary, &["energy".to_string()]).unwrap();
+        binary.extend_from_slice(&0u64.to_le_bytes());
+        binary.extend_from_slice(&u32::MAX.to_le_bytes());
+        let error = TrajectoryReader::new(&binary[..]).unwrap().next_frame();
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        // an entity without a value for every field cannot be written
+        let mut short = Trajectory::read(&record_flight()[..]).unwrap();
+        short.frames[1].entities[0].fields.pop();
+        let error = short.write(Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        let long_name = "x".repeat(70000);
+        let error = TrajectoryRecorder::new(Vec::new(), &[&long_name[..]]).finish();
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_truncated_frame_is_an_error() {
+        let mut binary = record_flight();
+        let len = binary.len();
+        binary.truncate(len - 3);
+        let frames: Vec<io::Result<Frame>> = TrajectoryReader::new(&binary[..]).unwrap().collect();
+        assert_eq!(frames.len(), 4);
+        assert!(frames[..3].iter().all(|f| f.is_ok()));
+        assert!(frames[3].is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! Wireworld circuits, for building logic out of cellular automata.
+//!
+//! Circuits are drawn as text, one character per cell:
+//!
+//! * ` ` is empty
+//! * `.` is a conductor
+//! * `H` is an electron head and `t` an electron tail
+//! * any other capital letter is a conductor with that label, e.g. `A` and
+//!   `B` for inputs and `O` for an output
+//!
+//! Lines starting with `!` are comments. Labelled cells let tests pulse
+//! inputs and watch outputs, and `OutputProbe` records an output as a
+//! metric.
+//!
+//! ```rust
+//! # use sekai::wireworld::Circuit;
+//! let mut wire = Circuit::parse("A....O").unwrap();
+//! wire.pulse('A');
+//! let fired: Vec<bool> = (0..6)
+//!     .map(|_| {
+//!         let firing = wire.firing('O');
+//!         wire.step();
+//!         firing
+//!     })
+//!     .collect();
+//! assert_eq!(fired, vec![false, false, false, false, false, true]);
+//! ```
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use grid::{Edge, Grid, GridAutomaton, Wire, Wireworld};
+use observer::Probe;
+use render::{Rgb, Scene};
+use world::World;
+
+/// A Wireworld circuit with labelled cells
+#[derive(Debug, Clone)]
+pub struct Circuit {
+    automaton: GridAutomaton<Wireworld>,
+    labels: BTreeMap<char, Vec<(usize, usize)>>,
+}
+
+/// Why a circuit could not be loaded
+#[derive(Debug)]
+pub enum CircuitError {
+    /// The file could not be read
+    Io(PathBuf, io::Error),
+    /// A character which is not a cell, counting lines and columns from 1
+    Syntax {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+}
+
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CircuitError::Io(ref path, ref e) => write!(f, "{}: {}", path.display(), e),
+            CircuitError::Syntax {
+                line,
+                column,
+                found,
+            } => write!(
+                f,
+                "line {}, column {}: `{}` is not a wireworld cell",
+                line, column, found
+            ),
+        }
+    }
+}
+
+impl Error for CircuitError {}
+
+impl Circuit {
+    /// Reads a circuit drawn as text. Beyond the drawing every cell is empty
+    /// # Arguments
+    /// * `text` - The drawing
+    pub fn parse(text: &str) -> Result<Circuit, CircuitError> {
+        let mut rows = Vec::new();
+        let mut labels: BTreeMap<char, Vec<(usize, usize)>> = BTreeMap::new();
+        let drawing = text
+            .lines()
+            .enumerate()
+            .filter(|&(_, line)| !line.starts_with('!'));
+        for (number, line) in drawing {
+            let y = rows.len();
+            let mut row = Vec::new();
+            for (x, found) in line.trim_end().chars().enumerate() {
+                row.push(match found {
+                    ' ' => Wire::Empty,
+                    '.' => Wire::Conductor,
+                    'H' => Wire::Head,
+                    't' => Wire::Tail,
+                    'A'..='Z' => {
+                        labels.entry(found).or_default().push((x, y));
+                        Wire::Conductor
+                    }
+                    _ => {
+                        return Err(CircuitError::Syntax {
+                            line: number + 1,
+                            column: x + 1,
+                            found,
+                        })
+                    }
+                });
+            }
+            rows.push(row);
+        }
+        // blank lines at the end draw nothing
+        while rows.last().is_some_and(Vec::is_empty) {
+            rows.pop();
+        }
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut grid = Grid::new(width, rows.len(), Wire::Empty);
+        grid.set_edge(Edge::Fixed(Wire::Empty));
+        for (y, row) in rows.into_iter().enumerate() {
+            for (x, cell) in row.into_iter().enumerate() {
+                grid.set(x, y, cell);
+            }
+        }
+        Ok(Circuit {
+            automaton: GridAutomaton::new(grid, Wireworld, 0),
+            labels,
+        })
+    }
+
+    /// Loads a circuit from a text file
+    /// # Arguments
+    /// * `path` - The file, in the format `parse` reads
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Circuit, CircuitError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| CircuitError::Io(path.to_path_buf(), e))?;
+        Circuit::parse(&text)
+    }
+
+    /// Gets the cells of the circuit
+    pub fn grid(&self) -> &Grid<Wire> {
+        self.automaton.grid()
+    }
+
+    /// Gets the number of steps taken so far
+    pub fn generation(&self) -> u64 {
+        self.automaton.generation()
+    }
+
+    /// Gets the labels used in the circuit, in order
+    pub fn labels(&self) -> Vec<char> {
+        self.labels.keys().cloned().collect()
+    }
+
+    /// Gets the cells with a label, empty if there are none
+    /// # Arguments
+    /// * `label` - The label
+    pub fn cells(&self, label: char) -> &[(usize, usize)] {
+        self.labels.get(&label).map_or(&[], Vec::as_slice)
+    }
+
+    /// Puts an electron head on every cell with a label. At the dead end of
+    /// a wire, the electron travels along the wire away from the end
+    /// # Arguments
+    /// * `label` - The label of the input
+    pub fn pulse(&mut self, label: char) {
+        let cells = self.labels.get(&label).cloned().unwrap_or_default();
+        for (x, y) in cells {
+            self.automaton.grid_mut().set(x, y, Wire::Head);
+        }
+    }
+
+    /// Checks whether an electron head is on any cell with a label
+    /// # Arguments
+    /// * `label` - The label of the output
+    pub fn firing(&self, label: char) -> bool {
+        self.cells(label)
+            .iter()
+            .any(|&(x, y)| self.grid().get(x, y) == Wire::Head)
+    }
+
+    /// Checks whether no electrons are left anywhere
+    pub fn is_quiet(&self) -> bool {
+        self.grid()
+            .cells()
+            .iter()
+            .all(|&cell| cell != Wire::Head && cell != Wire::Tail)
+    }
+
+    /// Moves every electron one step
+    pub fn step(&mut self) {
+        self.automaton.step();
+    }
+
+    /// Draws the circuit in the usual colors: conductors yellow, heads blue
+    /// and tails red on black
+    /// # Arguments
+    /// * `cell_size` - The width and height of each cell in pixels
+    pub fn draw(&self, cell_size: u32) -> Scene {
+        self.grid().draw(cell_size, |&cell| match cell {
+            Wire::Empty => Rgb::BLACK,
+            Wire::Conductor => Rgb::new(255, 200, 0),
+            Wire::Head => Rgb::new(0, 100, 255),
+            Wire::Tail => Rgb::new(255, 60, 0),
+        })
+    }
+}
+
+/// Lets a simulation step the circuit, one generation per tick
+impl World<()> for Circuit {
+    fn update(&mut self) {
+        self.step();
+    }
+    fn num_entities(&self) -> usize {
+        self.grid().cells().len()
+    }
+    fn receive_message(&mut self, _message: ()) {}
+}
+
+/// Probe reporting 1 while an electron head is on an output of a circuit,
+/// and 0 otherwise, in a column named after the label
+pub struct OutputProbe {
+    label: char,
+}
+
+impl OutputProbe {
+    /// Creates a probe
+    /// # Arguments
+    /// * `label` - The label of the output cells
+    pub fn new(label: char) -> Self {
+        OutputProbe { label }
+    }
+}
+
+impl Probe<Circuit> for OutputProbe {
+    fn columns(&self) -> Vec<String> {
+        vec![self.label.to_string()]
+    }
+    fn measure(&mut self, circuit: &Circuit) -> Vec<f64> {
+        vec![if circuit.firing(self.label) { 1.0 } else { 0.0 }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use observer::{Metrics, Observer};
+
+    // input pairs, one every 12 ticks
+    const INPUTS: [(bool, bool); 8] = [
+        (false, false),
+        (true, false),
+        (false, true),
+        (true, true),
+        (true, true),
+        (false, true),
+        (true, false),
+        (false, false),
+    ];
+
+    /// Feeds the inputs to a gate, returning the ticks its output fired at
+    fn drive(mut gate: Circuit) -> Vec<u64> {
+        let mut metrics = Metrics::new();
+        metrics.add_probe(OutputProbe::new('O'));
+        for tick in 0..150 {
+            if tick % 12 == 0 {
+                if let Some(&(a, b)) = INPUTS.get(tick as usize / 12) {
+                    if a {
+                        gate.pulse('A');
+                    }
+                    if b {
+                        gate.pulse('B');
+                    }
+                }
+            }
+            metrics.observe(tick, &gate);
+            gate.step();
+        }
+        assert!(gate.is_quiet());
+        let fired = &metrics.series("O").unwrap().values;
+        metrics
+            .ticks()
+            .iter()
+            .zip(fired)
+            .filter(|&(_, &value)| value == 1.0)
+            .map(|(&tick, _)| tick)
+            .collect()
+    }
+
+    #[test]
+    fn test_xor_gate() {
+        let xor = Circuit::parse(include_str!("../examples/circuits/xor.ww")).unwrap();
+        assert_eq!(xor.labels(), vec!['A', 'B', 'O']);
+        // 1 0, 0 1, 0 1 and 1 0 each give one electron, 16 ticks later
+        assert_eq!(drive(xor), vec![28, 40, 76, 88]);
+    }
+
+    #[test]
+    fn test_and_gate() {
+        let and = Circuit::parse(include_str!("../examples/circuits/and.ww")).unwrap();
+        // only the two 1 1 pairs get through, 33 ticks later
+        assert_eq!(drive(and), vec![69, 81]);
+    }
+
+    #[test]
+    fn test_load() {
+        let circuit = Circuit::parse("! a diode\n\n  ..\nA.. .O\n  ..\n\n").unwrap();
+        assert_eq!(circuit.grid().width(), 6);
+        assert_eq!(circuit.grid().height(), 4);
+        assert_eq!(circuit.cells('O'), &[(5, 2)]);
+        assert_eq!(circuit.grid().get(2, 1), Wire::Conductor);
+
+        let error = Circuit::parse("..\n.#.").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2, column 2: `#` is not a wireworld cell"
+        );
+        assert!(Circuit::load("no/such/circuit.ww").is_err());
+    }
+}
@@ -9,3 +9,13 @@ pub trait World<M> {
     /// * `message` - The message being received from an entity
     fn receive_message(&mut self, message: M);
 }
+
+/// Read-only access to the entities of a world. Observers and probes only
+/// ever see a world through this trait, so they do not depend on the message
+/// type or on how the world updates itself
+pub trait WorldView {
+    /// The type of entity stored in the world
+    type Entity;
+    /// Gets the entities currently in the world
+    fn entities(&self) -> &[Self::Entity];
+}